@@ -0,0 +1,324 @@
+//! Mock contracts for the integration tests of the auction contract.
+//!
+//! `cis2_mock` is a minimal CIS-2 token contract: anyone can mint, and it
+//! supports `transfer`, `updateOperator`, `balanceOf`, `operatorOf` and the
+//! CIS-0 `supports`. Transfers can be made to fail with `setFailTransfers`, or to
+//! skip calling the receive hook of contracts with `setSkipReceiveHook`, and
+//! `supports` can be made to deny CIS-2 with `setSupportsCis2`.
+//!
+//! `swap_mock` is a swap contract exchanging the CCD attached to `swap` for
+//! as many tokens as µCCD, minted on a `cis2_mock` contract. Swaps can be
+//! made to fail with `setFailSwaps`.
+//!
+//! `proxy_mock` forwards calls with `forward`, so that tests can act as a
+//! contract. It accepts all CIS-2 tokens, and CCD sent to `deposit`.
+//!
+//! `treasury_mock` is a treasury recording the CCD sent to `deposit` with the
+//! auction ID given as memo, returned by `deposits`. Deposits can be made to
+//! fail with `setFailDeposits`.
+//!
+//! None is meant to be deployed anywhere but a test chain.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use concordium_cis2::*;
+use concordium_std::*;
+
+type ContractTokenId = TokenIdU8;
+type ContractTokenAmount = TokenAmountU64;
+
+/// The state of the mock token contract.
+#[derive(Serial, DeserialWithState)]
+#[concordium(state_parameter = "S")]
+pub struct State<S = ExternStateApi> {
+    balances: StateMap<(ContractTokenId, Address), ContractTokenAmount, S>,
+    operators: StateSet<(Address, Address), S>, // (owner, operator)
+    fail_transfers: bool, // Reject all transfers
+    skip_receive_hook: bool, // Transfer to contracts without calling their receive hook
+    denies_cis2: bool, // Answer `supports` queries for CIS-2 with no support
+}
+
+impl State {
+    fn balance(&self, token_id: &ContractTokenId, address: &Address) -> ContractTokenAmount {
+        self.balances.get(&(*token_id, *address)).map_or(TokenAmountU64(0), |amount| *amount)
+    }
+}
+
+/// Type of the parameter to mint tokens.
+#[derive(Serialize, SchemaType)]
+pub struct MintParams {
+    pub owner: Address,
+    pub token_id: ContractTokenId,
+    pub amount: ContractTokenAmount,
+}
+
+/// Errors of the mock token contract.
+#[derive(Debug, PartialEq, Eq, Reject, Serialize, SchemaType)]
+pub enum MockError {
+    #[from(ParseError)]
+    ParseParams,
+    Unauthorized,
+    InsufficientFunds,
+    InvokeContractError,
+    TransfersDisabled,
+    SwapsDisabled,
+    DepositsDisabled,
+}
+
+#[init(contract = "cis2_mock")]
+pub fn cis2_mock_init(_ctx: &InitContext, state_builder: &mut StateBuilder<ExternStateApi>) -> InitResult<State> {
+    Ok(State {
+        balances: state_builder.new_map(),
+        operators: state_builder.new_set(),
+        fail_transfers: false,
+        skip_receive_hook: false,
+        denies_cis2: false,
+    })
+}
+
+/// Mint tokens to any address. Unrestricted, as this is only used in tests.
+#[receive(contract = "cis2_mock", name = "mint", parameter = "MintParams", mutable, error = "MockError")]
+pub fn cis2_mock_mint(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), MockError> {
+    let params: MintParams = ctx.parameter_cursor().get()?;
+    let state = host.state_mut();
+    let balance = state.balance(&params.token_id, &params.owner);
+    let _ = state.balances.insert((params.token_id, params.owner), TokenAmountU64(balance.0 + params.amount.0));
+    Ok(())
+}
+
+#[receive(
+    contract = "cis2_mock",
+    name = "transfer",
+    parameter = "TransferParams<ContractTokenId, ContractTokenAmount>",
+    mutable,
+    error = "MockError"
+)]
+pub fn cis2_mock_transfer(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), MockError> {
+    let TransferParams(transfers): TransferParams<ContractTokenId, ContractTokenAmount> =
+        ctx.parameter_cursor().get()?;
+    let sender = ctx.sender();
+    ensure!(!host.state().fail_transfers, MockError::TransfersDisabled);
+    let skip_receive_hook = host.state().skip_receive_hook;
+
+    for Transfer { token_id, amount, from, to, data } in transfers {
+        let state = host.state_mut();
+        ensure!(from == sender || state.operators.contains(&(from, sender)), MockError::Unauthorized);
+
+        let from_balance = state.balance(&token_id, &from);
+        ensure!(from_balance.0 >= amount.0, MockError::InsufficientFunds);
+        let to_address = to.address();
+        let _ = state.balances.insert((token_id, from), TokenAmountU64(from_balance.0 - amount.0));
+        let to_balance = state.balance(&token_id, &to_address);
+        let _ = state.balances.insert((token_id, to_address), TokenAmountU64(to_balance.0 + amount.0));
+
+        if let (Receiver::Contract(address, entrypoint), false) = (to, skip_receive_hook) {
+            let parameter = OnReceivingCis2Params { token_id, amount, from, data };
+            host.invoke_contract(&address, &parameter, entrypoint.as_entrypoint_name(), Amount::zero())
+                .map_err(|_| MockError::InvokeContractError)?;
+        }
+    }
+    Ok(())
+}
+
+/// Make all subsequent transfers fail, or succeed again. Unrestricted, as
+/// this is only used in tests.
+#[receive(contract = "cis2_mock", name = "setFailTransfers", parameter = "bool", mutable, error = "MockError")]
+pub fn cis2_mock_set_fail_transfers(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), MockError> {
+    host.state_mut().fail_transfers = ctx.parameter_cursor().get()?;
+    Ok(())
+}
+
+/// Make all subsequent transfers to contracts skip their receive hook, or
+/// call it again. Unrestricted, as this is only used in tests.
+#[receive(contract = "cis2_mock", name = "setSkipReceiveHook", parameter = "bool", mutable, error = "MockError")]
+pub fn cis2_mock_set_skip_receive_hook(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), MockError> {
+    host.state_mut().skip_receive_hook = ctx.parameter_cursor().get()?;
+    Ok(())
+}
+
+/// Make `supports` report CIS-2 support, or deny it. Unrestricted, as this is
+/// only used in tests.
+#[receive(contract = "cis2_mock", name = "setSupportsCis2", parameter = "bool", mutable, error = "MockError")]
+pub fn cis2_mock_set_supports_cis2(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), MockError> {
+    let supports: bool = ctx.parameter_cursor().get()?;
+    host.state_mut().denies_cis2 = !supports;
+    Ok(())
+}
+
+#[receive(
+    contract = "cis2_mock",
+    name = "supports",
+    parameter = "SupportsQueryParams",
+    return_value = "SupportsQueryResponse",
+    error = "MockError"
+)]
+pub fn cis2_mock_supports(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<SupportsQueryResponse, MockError> {
+    let params: SupportsQueryParams = ctx.parameter_cursor().get()?;
+    let results: Vec<_> = params
+        .queries
+        .iter()
+        .map(|standard| {
+            let standard = standard.as_standard_identifier();
+            let cis2 = standard == CIS2_STANDARD_IDENTIFIER && !host.state().denies_cis2;
+            match cis2 || standard == CIS0_STANDARD_IDENTIFIER {
+                true => SupportResult::Support,
+                false => SupportResult::NoSupport,
+            }
+        })
+        .collect();
+    Ok(SupportsQueryResponse::from(results))
+}
+
+#[receive(contract = "cis2_mock", name = "updateOperator", parameter = "UpdateOperatorParams", mutable, error = "MockError")]
+pub fn cis2_mock_update_operator(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), MockError> {
+    let UpdateOperatorParams(updates) = ctx.parameter_cursor().get()?;
+    let sender = ctx.sender();
+    let state = host.state_mut();
+    for update in updates {
+        match update.update {
+            OperatorUpdate::Add => state.operators.insert((sender, update.operator)),
+            OperatorUpdate::Remove => state.operators.remove(&(sender, update.operator)),
+        };
+    }
+    Ok(())
+}
+
+#[receive(
+    contract = "cis2_mock",
+    name = "balanceOf",
+    parameter = "BalanceOfQueryParams<ContractTokenId>",
+    return_value = "BalanceOfQueryResponse<ContractTokenAmount>",
+    error = "MockError"
+)]
+pub fn cis2_mock_balance_of(
+    ctx: &impl HasReceiveContext,
+    host: &Host<State>,
+) -> Result<BalanceOfQueryResponse<ContractTokenAmount>, MockError> {
+    let params: BalanceOfQueryParams<ContractTokenId> = ctx.parameter_cursor().get()?;
+    let balances: Vec<_> = params.queries.iter().map(|query| host.state().balance(&query.token_id, &query.address)).collect();
+    Ok(BalanceOfQueryResponse::from(balances))
+}
+
+#[receive(
+    contract = "cis2_mock",
+    name = "operatorOf",
+    parameter = "OperatorOfQueryParams",
+    return_value = "OperatorOfQueryResponse",
+    error = "MockError"
+)]
+pub fn cis2_mock_operator_of(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<OperatorOfQueryResponse, MockError> {
+    let params: OperatorOfQueryParams = ctx.parameter_cursor().get()?;
+    let operators: Vec<_> = params.queries.iter().map(|query| host.state().operators.contains(&(query.owner, query.address))).collect();
+    Ok(OperatorOfQueryResponse::from(operators))
+}
+
+/// The state of the mock swap contract.
+#[derive(Serial, Deserial)]
+pub struct SwapState {
+    fail_swaps: bool, // Reject all swaps
+}
+
+/// Type of the parameter to swap the attached CCD for tokens.
+#[derive(Serialize, SchemaType)]
+pub struct SwapParams {
+    pub token_contract: ContractAddress,
+    pub token_id: ContractTokenId,
+    pub recipient: Address,
+}
+
+#[init(contract = "swap_mock")]
+pub fn swap_mock_init(_ctx: &InitContext, _state_builder: &mut StateBuilder<ExternStateApi>) -> InitResult<SwapState> {
+    Ok(SwapState { fail_swaps: false })
+}
+
+/// Swap the attached CCD for as many tokens as µCCD, minted to the recipient.
+/// The CCD stay with the swap contract.
+#[receive(contract = "swap_mock", name = "swap", parameter = "SwapParams", payable, mutable, error = "MockError")]
+pub fn swap_mock_swap(ctx: &impl HasReceiveContext, host: &mut Host<SwapState>, amount: Amount) -> Result<(), MockError> {
+    let params: SwapParams = ctx.parameter_cursor().get()?;
+    ensure!(!host.state().fail_swaps, MockError::SwapsDisabled);
+
+    let mint = MintParams {
+        owner: params.recipient,
+        token_id: params.token_id,
+        amount: TokenAmountU64(amount.micro_ccd),
+    };
+    host.invoke_contract(&params.token_contract, &mint, EntrypointName::new_unchecked("mint"), Amount::zero())
+        .map_err(|_| MockError::InvokeContractError)?;
+    Ok(())
+}
+
+/// Make all subsequent swaps fail, or succeed again. Unrestricted, as this is
+/// only used in tests.
+#[receive(contract = "swap_mock", name = "setFailSwaps", parameter = "bool", mutable, error = "MockError")]
+pub fn swap_mock_set_fail_swaps(ctx: &impl HasReceiveContext, host: &mut Host<SwapState>) -> Result<(), MockError> {
+    host.state_mut().fail_swaps = ctx.parameter_cursor().get()?;
+    Ok(())
+}
+
+/// Type of the parameter to forward a call to a contract.
+#[derive(Serialize, SchemaType)]
+pub struct ForwardParams {
+    pub to: ContractAddress,
+    pub entrypoint: OwnedEntrypointName,
+    pub parameter: Vec<u8>, // Serialized parameter of the call
+}
+
+#[init(contract = "proxy_mock")]
+pub fn proxy_mock_init(_ctx: &InitContext, _state_builder: &mut StateBuilder<ExternStateApi>) -> InitResult<()> { Ok(()) }
+
+/// Forward a call to a contract, with the attached CCD.
+#[receive(contract = "proxy_mock", name = "forward", parameter = "ForwardParams", payable, mutable, error = "MockError")]
+pub fn proxy_mock_forward(ctx: &impl HasReceiveContext, host: &mut Host<()>, amount: Amount) -> Result<(), MockError> {
+    let params: ForwardParams = ctx.parameter_cursor().get()?;
+    host.invoke_contract_raw(
+        &params.to,
+        Parameter::new_unchecked(&params.parameter),
+        params.entrypoint.as_entrypoint_name(),
+        amount,
+    )
+    .map_err(|_| MockError::InvokeContractError)?;
+    Ok(())
+}
+
+/// Accept any CIS-2 tokens.
+#[receive(contract = "proxy_mock", name = "onReceivingCIS2", error = "MockError")]
+pub fn proxy_mock_on_receiving_cis2(_ctx: &impl HasReceiveContext, _host: &Host<()>) -> Result<(), MockError> { Ok(()) }
+
+/// Accept any CCD.
+#[receive(contract = "proxy_mock", name = "deposit", payable, error = "MockError")]
+pub fn proxy_mock_deposit(_ctx: &impl HasReceiveContext, _host: &Host<()>, _amount: Amount) -> Result<(), MockError> { Ok(()) }
+
+/// The state of the mock treasury contract.
+#[derive(Serial, Deserial)]
+pub struct TreasuryState {
+    deposits: Vec<(u32, Amount)>, // Memo and amount of each deposit
+    fail_deposits: bool, // Reject all deposits
+}
+
+#[init(contract = "treasury_mock")]
+pub fn treasury_mock_init(_ctx: &InitContext, _state_builder: &mut StateBuilder<ExternStateApi>) -> InitResult<TreasuryState> {
+    Ok(TreasuryState { deposits: Vec::new(), fail_deposits: false })
+}
+
+/// Record the attached CCD with the memo.
+#[receive(contract = "treasury_mock", name = "deposit", parameter = "u32", payable, mutable, error = "MockError")]
+pub fn treasury_mock_deposit(ctx: &impl HasReceiveContext, host: &mut Host<TreasuryState>, amount: Amount) -> Result<(), MockError> {
+    let memo: u32 = ctx.parameter_cursor().get()?;
+    ensure!(!host.state().fail_deposits, MockError::DepositsDisabled);
+    host.state_mut().deposits.push((memo, amount));
+    Ok(())
+}
+
+/// Make all subsequent deposits fail, or succeed again. Unrestricted, as this
+/// is only used in tests.
+#[receive(contract = "treasury_mock", name = "setFailDeposits", parameter = "bool", mutable, error = "MockError")]
+pub fn treasury_mock_set_fail_deposits(ctx: &impl HasReceiveContext, host: &mut Host<TreasuryState>) -> Result<(), MockError> {
+    host.state_mut().fail_deposits = ctx.parameter_cursor().get()?;
+    Ok(())
+}
+
+/// The memo and amount of each deposit, in order.
+#[receive(contract = "treasury_mock", name = "deposits", return_value = "Vec<(u32, Amount)>", error = "MockError")]
+pub fn treasury_mock_deposits(_ctx: &impl HasReceiveContext, host: &Host<TreasuryState>) -> Result<Vec<(u32, Amount)>, MockError> {
+    Ok(host.state().deposits.clone())
+}
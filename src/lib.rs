@@ -3,308 +3,5294 @@
 use concordium_std::*;
 type ContractTokenId = TokenIdU8; // Define ContractTokenId as an alias for TokenIdU8
 type ContractTokenAmount = TokenAmountU64; // Define ContractTokenAmount as an alias for TokenAmountU64
-use concordium_cis2::{AdditionalData, Cis2Client, Cis2ClientError, OnReceivingCis2Params, Receiver, TokenAmountU64, TokenIdU8, Transfer, TransferParams};
+use concordium_cis2::{AdditionalData, Cis2Client, Cis2ClientError, OnReceivingCis2Params, Receiver, SupportResult, TokenAmountU64, TokenIdU8, Transfer};
 
 
 /// The state of an auction.
 #[derive(Debug, Serialize, SchemaType, Eq, PartialEq, PartialOrd, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AuctionState {
     NotSoldYet,
-    Sold(AccountAddress),
+    /// The tokens went to `winner` for `price`, settled at `settled_at`, so
+    /// the sale stays known when `highest_bid` no longer reflects it.
+    ///
+    /// Migration: this variant used to hold only the winner. Its tag is
+    /// unchanged, but states serialized with the old layout do not parse with
+    /// this one, so an upgraded instance has to rewrite its sold auctions with
+    /// the price from their `Finalize` event and the time of that block.
+    Sold {
+        winner:     AccountAddress,
+        price:      Amount,
+        settled_at: Timestamp,
+    },
+    /// The auction ended without bids and the tokens were returned to the
+    /// owner. If returning them failed, they stay escrowed as the
+    /// `amount_remaining` of the auction until the owner reclaims them with
+    /// `reclaim_unsold_remainder`.
+    Unsold,
+    /// The auction ended without bids and its escrowed tokens were moved to
+    /// the auction with the given id.
+    Superseded(u32),
+    /// The auction was not finalized within the grace period after its end,
+    /// and the highest bidder and the owner reclaim their bid and tokens.
+    Lapsed {
+        bid_reclaimed:    bool,
+        tokens_reclaimed: bool,
+    },
+    /// The auction was won by the given account, but the tokens could not be
+    /// transferred to it. The contract keeps the tokens and the highest bid
+    /// until `retry_delivery` succeeds.
+    PendingDelivery(AccountAddress),
+    /// The auction was cancelled by the owner before its end, and the tokens
+    /// were returned to the owner.
+    Cancelled,
+    /// The owner rejected the winning bid, which was refunded, and the tokens
+    /// were returned to the owner.
+    Rejected,
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AuctionEventData {
     pub auction_id:       u32,
 }
 
-#[derive(Debug, PartialEq, Serialize, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FinalizeEventData {
+    pub auction_id:       u32,
+    pub finalizer:        Address, // Who settled the auction
+    pub commission_payouts: Vec<(AccountAddress, Amount)>, // Commission paid to each account recipient, see `CommissionDeposited`
+    pub finalize_reward:  Amount, // Reward paid to the finalizer out of the owner's share
+    pub price:            Amount, // Price charged to the winner, from which the commission is taken
+    pub winner:           Option<AccountAddress>, // Account receiving the tokens, if the auction was sold
+    pub settled_at:       Timestamp,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BidEventData {
+    pub auction_id:       u32,
+    pub bidder:           AccountAddress, // Who paid the bid and is refunded when outbid
+    pub beneficiary:      AccountAddress, // Who receives the tokens if the bid wins
+    pub amount:           Amount, // The new highest bid, including any earlier bid topped up
+    #[concordium(size_length = 2)]
+    pub data:             Vec<u8>, // Data attached by the bidder, laid out like `AdditionalData`
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RescueEventData {
+    pub token_contract:   ContractAddress,
+    pub token_id:         TokenIdU8,
+    pub amount:           TokenAmountU64,
+    pub to:               Address,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReclaimEventData {
+    pub auction_id:       u32,
+    pub account:          Address,
+    pub refund:           Amount,         // CCD returned to the highest bidder
+    pub tokens:           TokenAmountU64, // Tokens returned to the owner
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CancelEventData {
+    pub auction_id:       u32,
+    pub bidder:           Option<AccountAddress>, // Highest bidder at the time of the cancellation
+    pub refund:           Amount, // Highest bid returned to the bidder
+    pub fee:              Amount, // Cancellation fee paid by the owner to the bidder
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetractEventData {
+    pub auction_id:       u32,
+    pub bidder:           AccountAddress,
+    pub bid:              Amount, // Retracted bid
+    pub penalty_payouts:  Vec<(AccountAddress, Amount)>, // Penalty kept from the bid, per account commission recipient
+}
+
+/// An offer made, cancelled, accepted or expired.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OfferEventData {
+    pub offer_id:         u64,
+    pub listing_id:       u32,
+    pub offerer:          AccountAddress,
+    pub amount:           Amount, // CCD escrowed with the offer, refunded unless it was accepted
+}
+
+/// The owner changed the item description of an auction. The description
+/// can be longer than an event, so only its SHA-256 hash is logged.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ItemUpdatedEventData {
+    pub auction_id:       u32,
+    pub item_hash:        [u8; 32], // SHA-256 hash of the new item description
+}
+
+/// The owner's share of a sold auction was paid in CCD, as swapping it for
+/// the payout token of the auction failed.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PayoutFallbackEventData {
+    pub auction_id:       u32,
+    pub owner:            Address,
+    pub amount:           Amount, // Owner's share paid in CCD instead
+    pub payout_token:     (ContractAddress, TokenIdU8), // Token the share was meant to be swapped for
+}
+
+/// What a payout of a sold auction was for.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PayoutKind {
+    /// A share of the commission, or of a retraction penalty.
+    Commission,
+    /// The owner's share of the price, or the ask paid to the lowest asker of
+    /// a reverse auction.
+    Proceeds,
+    /// The finalize reward of the account that finalized the auction.
+    FinalizeReward,
+    /// The unspent budget of a reverse auction, returned to its owner.
+    Budget,
+}
+
+/// A payout to an account failed, and the CCD are kept for the account to
+/// claim with `claim_refund` instead of blocking the settlement. This is also
+/// how commission recipient accounts claim a share they could not be paid, and
+/// how owner accounts claim their proceeds; `claim_proceeds` rejects accounts
+/// with `UseClaimRefund`.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PayoutDeferredEventData {
+    pub auction_id: u32,
+    pub account:    AccountAddress,
+    pub amount:     Amount,
+    pub payout:     PayoutKind,
+}
+
+/// A bid recorded as the backup bid of an auction, or a backup bid that
+/// became the highest bid as the highest bidder retracted or was rejected.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackupBidEventData {
+    pub auction_id:       u32,
+    pub bidder:           AccountAddress,
+    pub amount:           Amount,
+}
+
+/// Tokens of an auction or listing in the marketplace vocabulary, see
+/// `AuctionEvent`.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ItemEventData {
+    pub auction_id:       u32,
+    pub collection:       ContractAddress, // CIS-2 token contract address
+    pub token_id:         TokenIdU8,
+    pub amount:           TokenAmountU64,
+    pub price:            Amount,
+    pub actor:            Address,
+}
+
+/// A change of the balance an account deposited to bid from.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BalanceEventData {
+    pub account:          AccountAddress,
+    pub amount:           Amount, // CCD credited or debited
+    pub balance:          Amount, // The new balance
+}
+
+/// Commission of an auction sent to a contract commission sink, or credited
+/// to the sink as proceeds to claim as the contract rejected it.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommissionSinkEventData {
+    pub auction_id:       u32,
+    pub contract:         ContractAddress, // The contract commission sink
+    pub amount:           Amount,
+}
+
+/// A bid in the alternative bid token of an auction became the highest bid.
+/// It is logged next to the `Bid` event, which carries the value of the bid.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenBidEventData {
+    pub auction_id:       u32,
+    pub bidder:           AccountAddress,
+    pub tokens:           TokenAmountU64, // The new highest bid, including any earlier bid topped up
+    pub value:            Amount,         // Value of the tokens the bid is compared by
+}
+
+/// Tokens of a bid in the alternative bid token returned to the bidder.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenRefundEventData {
+    pub auction_id:       u32,
+    pub account:          AccountAddress,
+    pub tokens:           TokenAmountU64,
+    pub reason:           RefundReason,
+}
+
+/// The winning bid of an auction was paid out in the alternative bid token
+/// it was placed in.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenPayoutEventData {
+    pub auction_id:        u32,
+    pub owner:             Address,
+    pub owner_tokens:      TokenAmountU64, // Paid to the owner
+    pub commission_tokens: TokenAmountU64, // Split between the commission recipients
+}
+
+/// The unsold tokens of an auction could not be returned to its owner.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReturnDeferredEventData {
+    pub auction_id: u32,
+    pub owner:      Address,
+    pub amount:     TokenAmountU64, // Tokens kept for the owner to reclaim
+}
+
+/// Tokens of an alternative bid token could not be refunded or paid out, and
+/// are kept for the recipient to claim with `claim_tokens` instead of
+/// blocking the call.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokensDeferredEventData {
+    pub auction_id:     u32,
+    pub recipient:      Address,
+    pub token_contract: ContractAddress,
+    pub token_id:       TokenIdU8,
+    pub amount:         TokenAmountU64,
+}
+
+/// How the winning bid of an auction requiring owner approval was decided.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ApprovalOutcome {
+    Accepted,
+    Rejected,
+    /// The owner did not decide within the approval window, so the auction
+    /// was settled.
+    SettledByDefault,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApprovalEventData {
+    pub auction_id:       u32,
+    pub outcome:          ApprovalOutcome,
+}
+
+/// A commission rate applying to sale prices from a threshold on.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommissionTier {
+    pub threshold:        Amount, // Lowest sale price the rate applies to
+    pub bps:              u16,    // Commission rate in basis points of the sale price
+}
+
+/// A change of the commission tiers, and optionally of the commission
+/// recipients, which can be applied once it is effective.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommissionChange {
+    pub commission_tiers:      Vec<CommissionTier>, // New commission tiers
+    pub commission_recipients: Option<Vec<CommissionShare>>, // New commission recipients, `None` keeps the current ones
+    pub effective_at:          Timestamp, // Time from which the change can be applied
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AskEventData {
+    pub auction_id:       u32,
+    pub asker:            AccountAddress,
+    pub ask:              Amount, // The new lowest ask
+}
+
+/// Why CCD was returned to a bidder.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RefundReason {
+    /// The bid was outbid by another bidder.
+    Outbid,
+    /// The owner cancelled the auction. The amount includes the fee.
+    Cancelled,
+    /// The owner rejected the winning bid.
+    Rejected,
+    /// The bid came in after the end and was returned when the auction was
+    /// settled.
+    LateBid,
+    /// The winning bid was above the price charged for the auction.
+    Overpaid,
+    /// The bidder reclaimed the bid of an auction that was never finalized.
+    Reclaimed,
+    /// The bidder claimed refunds kept after failed transfers.
+    Claimed,
+    /// The bidder retracted the bid. The amount excludes the penalty.
+    Retracted,
+    /// The bidder withdrew their backup bid.
+    BackupWithdrawn,
+    /// The backup bid was returned as the auction was settled.
+    BackupReleased,
+}
+
+/// CCD returned to a bidder. Only logged when the CCD leave the contract: a
+/// refund whose transfer fails is kept claimable and logged once claimed,
+/// without an auction as claims can add up refunds of several auctions.
+#[derive(Debug, PartialEq, Eq, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RefundEventData {
+    pub auction_id:       Option<u32>,
+    pub account:          AccountAddress,
+    pub amount:           Amount,
+    pub reason:           RefundReason,
+}
+
+/// Tag for the `Register` event.
+pub const REGISTER_EVENT_TAG: u8 = 0;
+/// Tag for the `Finalize` event.
+pub const FINALIZE_EVENT_TAG: u8 = 1;
+/// Tag for the `Close` event.
+pub const CLOSE_EVENT_TAG: u8 = 2;
+/// Tag for the `Bid` event.
+pub const BID_EVENT_TAG: u8 = 3;
+/// Tag for the `Rescue` event.
+pub const RESCUE_EVENT_TAG: u8 = 4;
+/// Tag for the `Reclaim` event.
+pub const RECLAIM_EVENT_TAG: u8 = 5;
+/// Tag for the `DeliveryPending` event.
+pub const DELIVERY_PENDING_EVENT_TAG: u8 = 6;
+/// Tag for the `Cancel` event.
+pub const CANCEL_EVENT_TAG: u8 = 7;
+/// Tag for the `Approval` event.
+pub const APPROVAL_EVENT_TAG: u8 = 8;
+/// Tag for the `CommissionChangeScheduled` event.
+pub const COMMISSION_CHANGE_SCHEDULED_EVENT_TAG: u8 = 9;
+/// Tag for the `CommissionChangeCancelled` event.
+pub const COMMISSION_CHANGE_CANCELLED_EVENT_TAG: u8 = 10;
+/// Tag for the `CommissionChangeApplied` event.
+pub const COMMISSION_CHANGE_APPLIED_EVENT_TAG: u8 = 11;
+/// Tag for the `Ask` event.
+pub const ASK_EVENT_TAG: u8 = 12;
+/// Tag for the `Refund` event.
+pub const REFUND_EVENT_TAG: u8 = 13;
+/// Tag for the `Retract` event.
+pub const RETRACT_EVENT_TAG: u8 = 14;
+/// Tag for the `OfferMade` event.
+pub const OFFER_MADE_EVENT_TAG: u8 = 15;
+/// Tag for the `OfferCancelled` event.
+pub const OFFER_CANCELLED_EVENT_TAG: u8 = 16;
+/// Tag for the `OfferAccepted` event.
+pub const OFFER_ACCEPTED_EVENT_TAG: u8 = 17;
+/// Tag for the `OfferExpired` event.
+pub const OFFER_EXPIRED_EVENT_TAG: u8 = 18;
+/// Tag for the `ItemUpdated` event.
+pub const ITEM_UPDATED_EVENT_TAG: u8 = 19;
+/// Tag for the `PayoutFallback` event.
+pub const PAYOUT_FALLBACK_EVENT_TAG: u8 = 20;
+/// Tag for the `BackupBid` event.
+pub const BACKUP_BID_EVENT_TAG: u8 = 21;
+/// Tag for the `BackupPromoted` event.
+pub const BACKUP_PROMOTED_EVENT_TAG: u8 = 22;
+/// Tag for the `BalanceCredited` event.
+pub const BALANCE_CREDITED_EVENT_TAG: u8 = 23;
+/// Tag for the `BalanceDebited` event.
+pub const BALANCE_DEBITED_EVENT_TAG: u8 = 24;
+/// Tag for the `ItemListed` event.
+pub const ITEM_LISTED_EVENT_TAG: u8 = 25;
+/// Tag for the `ItemBid` event.
+pub const ITEM_BID_EVENT_TAG: u8 = 26;
+/// Tag for the `ItemSold` event.
+pub const ITEM_SOLD_EVENT_TAG: u8 = 27;
+/// Tag for the `ItemUnlisted` event.
+pub const ITEM_UNLISTED_EVENT_TAG: u8 = 28;
+/// Tag for the `ItemReturned` event.
+pub const ITEM_RETURNED_EVENT_TAG: u8 = 29;
+/// Tag for the `CommissionDeposited` event.
+pub const COMMISSION_DEPOSITED_EVENT_TAG: u8 = 30;
+/// Tag for the `CommissionAccrued` event.
+pub const COMMISSION_ACCRUED_EVENT_TAG: u8 = 31;
+/// Tag for the `TokenBid` event.
+pub const TOKEN_BID_EVENT_TAG: u8 = 32;
+/// Tag for the `TokenRefund` event.
+pub const TOKEN_REFUND_EVENT_TAG: u8 = 33;
+/// Tag for the `TokenPayout` event.
+pub const TOKEN_PAYOUT_EVENT_TAG: u8 = 34;
+/// Tag for the `ReturnDeferred` event.
+pub const RETURN_DEFERRED_EVENT_TAG: u8 = 35;
+/// Tag for the `PayoutDeferred` event.
+pub const PAYOUT_DEFERRED_EVENT_TAG: u8 = 36;
+/// Tag for the `TokensDeferred` event.
+pub const TOKENS_DEFERRED_EVENT_TAG: u8 = 37;
+
+/// Events logged by the contract. Each event is serialized as its tag byte
+/// followed by its data, so existing layouts stay stable when new events are
+/// added. Tags count up from 0, and stay below the tags 251 to 255 reserved
+/// for CIS-2 events.
+///
+/// | Tag | Event                       | Data                   |
+/// |-----|-----------------------------|------------------------|
+/// | 0   | `Register`                  | `AuctionEventData`     |
+/// | 1   | `Finalize`                  | `FinalizeEventData`    |
+/// | 2   | `Close`                     | `AuctionEventData`     |
+/// | 3   | `Bid`                       | `BidEventData`         |
+/// | 4   | `Rescue`                    | `RescueEventData`      |
+/// | 5   | `Reclaim`                   | `ReclaimEventData`     |
+/// | 6   | `DeliveryPending`           | `AuctionEventData`     |
+/// | 7   | `Cancel`                    | `CancelEventData`      |
+/// | 8   | `Approval`                  | `ApprovalEventData`    |
+/// | 9   | `CommissionChangeScheduled` | `CommissionChange`     |
+/// | 10  | `CommissionChangeCancelled` | `CommissionChange`     |
+/// | 11  | `CommissionChangeApplied`   | `CommissionChange`     |
+/// | 12  | `Ask`                       | `AskEventData`         |
+/// | 13  | `Refund`                    | `RefundEventData`      |
+/// | 14  | `Retract`                   | `RetractEventData`     |
+/// | 15  | `OfferMade`                 | `OfferEventData`       |
+/// | 16  | `OfferCancelled`            | `OfferEventData`       |
+/// | 17  | `OfferAccepted`             | `OfferEventData`       |
+/// | 18  | `OfferExpired`              | `OfferEventData`       |
+/// | 19  | `ItemUpdated`               | `ItemUpdatedEventData` |
+/// | 20  | `PayoutFallback`            | `PayoutFallbackEventData` |
+/// | 21  | `BackupBid`                 | `BackupBidEventData`   |
+/// | 22  | `BackupPromoted`            | `BackupBidEventData`   |
+/// | 23  | `BalanceCredited`           | `BalanceEventData`     |
+/// | 24  | `BalanceDebited`            | `BalanceEventData`     |
+/// | 25  | `ItemListed`                | `ItemEventData`        |
+/// | 26  | `ItemBid`                   | `ItemEventData`        |
+/// | 27  | `ItemSold`                  | `ItemEventData`        |
+/// | 28  | `ItemUnlisted`              | `ItemEventData`        |
+/// | 29  | `ItemReturned`              | `ItemEventData`        |
+/// | 30  | `CommissionDeposited`       | `CommissionSinkEventData` |
+/// | 31  | `CommissionAccrued`         | `CommissionSinkEventData` |
+/// | 32  | `TokenBid`                  | `TokenBidEventData`    |
+/// | 33  | `TokenRefund`               | `TokenRefundEventData` |
+/// | 34  | `TokenPayout`               | `TokenPayoutEventData` |
+/// | 35  | `ReturnDeferred`            | `ReturnDeferredEventData` |
+/// | 36  | `PayoutDeferred`            | `PayoutDeferredEventData` |
+/// | 37  | `TokensDeferred`            | `TokensDeferredEventData` |
+///
+/// The `Item*` events are the marketplace vocabulary shared with other
+/// marketplace contracts, logged next to the events specific to auctions.
+/// Their data is laid out as the auction ID (4 bytes, little endian), the
+/// collection (token contract index and subindex, 8 bytes each), the token ID
+/// (1 length byte and the ID byte), the token amount (LEB128), the price in
+/// µCCD (8 bytes) and the actor (0 and an account address of 32 bytes, or 1
+/// and a contract address of 16 bytes):
+///
+/// ```
+/// # use auction::*;
+/// # use concordium_cis2::{TokenAmountU64, TokenIdU8};
+/// # use concordium_std::*;
+/// let event = AuctionEvent::ItemListed(ItemEventData {
+///     auction_id: 7,
+///     collection: ContractAddress::new(1, 0),
+///     token_id:   TokenIdU8(2),
+///     amount:     TokenAmountU64(3),
+///     price:      Amount::from_micro_ccd(4),
+///     actor:      Address::Contract(ContractAddress::new(5, 0)),
+/// });
+/// let bytes = to_bytes(&event);
+/// assert_eq!(bytes, [
+///     &[ITEM_LISTED_EVENT_TAG][..],
+///     &[7, 0, 0, 0],
+///     &[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+///     &[1, 2],
+///     &[3],
+///     &[4, 0, 0, 0, 0, 0, 0, 0],
+///     &[1, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+/// ]
+/// .concat());
+/// assert_eq!(from_bytes::<AuctionEvent>(&bytes), Ok(event));
+/// ```
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AuctionEvent {
     Register(AuctionEventData),
+    Finalize(FinalizeEventData),
+    Close(AuctionEventData),
+    Bid(BidEventData),
+    Rescue(RescueEventData),
+    Reclaim(ReclaimEventData),
+    DeliveryPending(AuctionEventData),
+    Cancel(CancelEventData),
+    Approval(ApprovalEventData),
+    CommissionChangeScheduled(CommissionChange),
+    CommissionChangeCancelled(CommissionChange),
+    CommissionChangeApplied(CommissionChange),
+    Ask(AskEventData),
+    Refund(RefundEventData),
+    Retract(RetractEventData),
+    OfferMade(OfferEventData),
+    /// The offerer cancelled the offer, or another offer for the same item
+    /// was accepted.
+    OfferCancelled(OfferEventData),
+    OfferAccepted(OfferEventData),
+    OfferExpired(OfferEventData),
+    ItemUpdated(ItemUpdatedEventData),
+    PayoutFallback(PayoutFallbackEventData),
+    BackupBid(BackupBidEventData),
+    BackupPromoted(BackupBidEventData),
+    /// CCD were deposited to a balance, or an outbid bid funded from it was
+    /// credited back.
+    BalanceCredited(BalanceEventData),
+    /// CCD were withdrawn from a balance, or a bid was funded from it.
+    BalanceDebited(BalanceEventData),
+    /// Tokens were listed for sale by auction or for a fixed price. The
+    /// price is the initial price or the fixed price, and the actor is the
+    /// owner.
+    ItemListed(ItemEventData),
+    /// A bid became the highest bid. The price is the bid, and the actor is
+    /// the bidder.
+    ItemBid(ItemEventData),
+    /// The tokens were delivered to the buyer. The price is the price
+    /// charged, and the actor is the buyer.
+    ItemSold(ItemEventData),
+    /// The owner cancelled or relisted the listing, or it closed without
+    /// tokens to return. The price is zero, and the actor is the owner.
+    ItemUnlisted(ItemEventData),
+    /// The tokens were returned to the owner without a sale. The price is
+    /// zero, and the actor is the owner.
+    ItemReturned(ItemEventData),
+    /// The share of the commission of a contract commission sink was sent to
+    /// its entrypoint. Only commission paid to accounts is part of the
+    /// payouts of the `Finalize` and `Retract` events.
+    CommissionDeposited(CommissionSinkEventData),
+    /// A contract commission sink rejected its share of the commission, which
+    /// it can claim with `claim_proceeds` instead.
+    CommissionAccrued(CommissionSinkEventData),
+    /// A bid in the alternative bid token of an auction became the highest
+    /// bid.
+    TokenBid(TokenBidEventData),
+    /// A bid in the alternative bid token was returned in the token. The
+    /// `Refund` event is only logged for CCD.
+    TokenRefund(TokenRefundEventData),
+    /// The winning bid in the alternative bid token was paid out in the
+    /// token. The `Finalize` event of such an auction has the value of the
+    /// bid as its price, and no CCD payouts.
+    TokenPayout(TokenPayoutEventData),
+    /// The tokens of an auction finalized without a sale could not be
+    /// returned to the owner, and stay escrowed until the owner reclaims them
+    /// with `reclaim_unsold_remainder`. No `ItemReturned` event is logged
+    /// until then.
+    ReturnDeferred(ReturnDeferredEventData),
+    /// A commission share, the owner's proceeds or the finalize reward could
+    /// not be transferred to an account, and was added to the refund the
+    /// account can claim. Accounts claim every kind of payout with
+    /// `claim_refund`; `claim_proceeds` is only for contracts. The `Finalize`
+    /// event only lists the commission payouts that were transferred.
+    PayoutDeferred(PayoutDeferredEventData),
+    /// Tokens of a bid in the alternative bid token, or of its payout, could
+    /// not be transferred, and were kept for the recipient to claim with
+    /// `claim_tokens`. No `TokenRefund` event is logged for such a refund.
+    TokensDeferred(TokensDeferredEventData),
+}
+
+impl Serial for AuctionEvent {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        match self {
+            AuctionEvent::Register(data) => {
+                out.write_u8(REGISTER_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Finalize(data) => {
+                out.write_u8(FINALIZE_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Close(data) => {
+                out.write_u8(CLOSE_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Bid(data) => {
+                out.write_u8(BID_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Rescue(data) => {
+                out.write_u8(RESCUE_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Reclaim(data) => {
+                out.write_u8(RECLAIM_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::DeliveryPending(data) => {
+                out.write_u8(DELIVERY_PENDING_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Cancel(data) => {
+                out.write_u8(CANCEL_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Approval(data) => {
+                out.write_u8(APPROVAL_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::CommissionChangeScheduled(data) => {
+                out.write_u8(COMMISSION_CHANGE_SCHEDULED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::CommissionChangeCancelled(data) => {
+                out.write_u8(COMMISSION_CHANGE_CANCELLED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::CommissionChangeApplied(data) => {
+                out.write_u8(COMMISSION_CHANGE_APPLIED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Ask(data) => {
+                out.write_u8(ASK_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Refund(data) => {
+                out.write_u8(REFUND_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::Retract(data) => {
+                out.write_u8(RETRACT_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::OfferMade(data) => {
+                out.write_u8(OFFER_MADE_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::OfferCancelled(data) => {
+                out.write_u8(OFFER_CANCELLED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::OfferAccepted(data) => {
+                out.write_u8(OFFER_ACCEPTED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::OfferExpired(data) => {
+                out.write_u8(OFFER_EXPIRED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::ItemUpdated(data) => {
+                out.write_u8(ITEM_UPDATED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::PayoutFallback(data) => {
+                out.write_u8(PAYOUT_FALLBACK_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::BackupBid(data) => {
+                out.write_u8(BACKUP_BID_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::BackupPromoted(data) => {
+                out.write_u8(BACKUP_PROMOTED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::BalanceCredited(data) => {
+                out.write_u8(BALANCE_CREDITED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::BalanceDebited(data) => {
+                out.write_u8(BALANCE_DEBITED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::ItemListed(data) => {
+                out.write_u8(ITEM_LISTED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::ItemBid(data) => {
+                out.write_u8(ITEM_BID_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::ItemSold(data) => {
+                out.write_u8(ITEM_SOLD_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::ItemUnlisted(data) => {
+                out.write_u8(ITEM_UNLISTED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::ItemReturned(data) => {
+                out.write_u8(ITEM_RETURNED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::CommissionDeposited(data) => {
+                out.write_u8(COMMISSION_DEPOSITED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::CommissionAccrued(data) => {
+                out.write_u8(COMMISSION_ACCRUED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::TokenBid(data) => {
+                out.write_u8(TOKEN_BID_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::TokenRefund(data) => {
+                out.write_u8(TOKEN_REFUND_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::TokenPayout(data) => {
+                out.write_u8(TOKEN_PAYOUT_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::ReturnDeferred(data) => {
+                out.write_u8(RETURN_DEFERRED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::PayoutDeferred(data) => {
+                out.write_u8(PAYOUT_DEFERRED_EVENT_TAG)?;
+                data.serial(out)
+            }
+            AuctionEvent::TokensDeferred(data) => {
+                out.write_u8(TOKENS_DEFERRED_EVENT_TAG)?;
+                data.serial(out)
+            }
+        }
+    }
+}
+
+impl Deserial for AuctionEvent {
+    fn deserial<R: Read>(source: &mut R) -> ParseResult<Self> {
+        let tag = source.read_u8()?;
+        match tag {
+            REGISTER_EVENT_TAG => AuctionEventData::deserial(source).map(AuctionEvent::Register),
+            FINALIZE_EVENT_TAG => FinalizeEventData::deserial(source).map(AuctionEvent::Finalize),
+            CLOSE_EVENT_TAG => AuctionEventData::deserial(source).map(AuctionEvent::Close),
+            BID_EVENT_TAG => BidEventData::deserial(source).map(AuctionEvent::Bid),
+            RESCUE_EVENT_TAG => RescueEventData::deserial(source).map(AuctionEvent::Rescue),
+            RECLAIM_EVENT_TAG => ReclaimEventData::deserial(source).map(AuctionEvent::Reclaim),
+            DELIVERY_PENDING_EVENT_TAG => AuctionEventData::deserial(source).map(AuctionEvent::DeliveryPending),
+            CANCEL_EVENT_TAG => CancelEventData::deserial(source).map(AuctionEvent::Cancel),
+            APPROVAL_EVENT_TAG => ApprovalEventData::deserial(source).map(AuctionEvent::Approval),
+            COMMISSION_CHANGE_SCHEDULED_EVENT_TAG => {
+                CommissionChange::deserial(source).map(AuctionEvent::CommissionChangeScheduled)
+            }
+            COMMISSION_CHANGE_CANCELLED_EVENT_TAG => {
+                CommissionChange::deserial(source).map(AuctionEvent::CommissionChangeCancelled)
+            }
+            COMMISSION_CHANGE_APPLIED_EVENT_TAG => CommissionChange::deserial(source).map(AuctionEvent::CommissionChangeApplied),
+            ASK_EVENT_TAG => AskEventData::deserial(source).map(AuctionEvent::Ask),
+            REFUND_EVENT_TAG => RefundEventData::deserial(source).map(AuctionEvent::Refund),
+            RETRACT_EVENT_TAG => RetractEventData::deserial(source).map(AuctionEvent::Retract),
+            OFFER_MADE_EVENT_TAG => OfferEventData::deserial(source).map(AuctionEvent::OfferMade),
+            OFFER_CANCELLED_EVENT_TAG => OfferEventData::deserial(source).map(AuctionEvent::OfferCancelled),
+            OFFER_ACCEPTED_EVENT_TAG => OfferEventData::deserial(source).map(AuctionEvent::OfferAccepted),
+            OFFER_EXPIRED_EVENT_TAG => OfferEventData::deserial(source).map(AuctionEvent::OfferExpired),
+            ITEM_UPDATED_EVENT_TAG => ItemUpdatedEventData::deserial(source).map(AuctionEvent::ItemUpdated),
+            PAYOUT_FALLBACK_EVENT_TAG => PayoutFallbackEventData::deserial(source).map(AuctionEvent::PayoutFallback),
+            BACKUP_BID_EVENT_TAG => BackupBidEventData::deserial(source).map(AuctionEvent::BackupBid),
+            BACKUP_PROMOTED_EVENT_TAG => BackupBidEventData::deserial(source).map(AuctionEvent::BackupPromoted),
+            BALANCE_CREDITED_EVENT_TAG => BalanceEventData::deserial(source).map(AuctionEvent::BalanceCredited),
+            BALANCE_DEBITED_EVENT_TAG => BalanceEventData::deserial(source).map(AuctionEvent::BalanceDebited),
+            ITEM_LISTED_EVENT_TAG => ItemEventData::deserial(source).map(AuctionEvent::ItemListed),
+            ITEM_BID_EVENT_TAG => ItemEventData::deserial(source).map(AuctionEvent::ItemBid),
+            ITEM_SOLD_EVENT_TAG => ItemEventData::deserial(source).map(AuctionEvent::ItemSold),
+            ITEM_UNLISTED_EVENT_TAG => ItemEventData::deserial(source).map(AuctionEvent::ItemUnlisted),
+            ITEM_RETURNED_EVENT_TAG => ItemEventData::deserial(source).map(AuctionEvent::ItemReturned),
+            COMMISSION_DEPOSITED_EVENT_TAG => {
+                CommissionSinkEventData::deserial(source).map(AuctionEvent::CommissionDeposited)
+            }
+            COMMISSION_ACCRUED_EVENT_TAG => CommissionSinkEventData::deserial(source).map(AuctionEvent::CommissionAccrued),
+            TOKEN_BID_EVENT_TAG => TokenBidEventData::deserial(source).map(AuctionEvent::TokenBid),
+            TOKEN_REFUND_EVENT_TAG => TokenRefundEventData::deserial(source).map(AuctionEvent::TokenRefund),
+            TOKEN_PAYOUT_EVENT_TAG => TokenPayoutEventData::deserial(source).map(AuctionEvent::TokenPayout),
+            RETURN_DEFERRED_EVENT_TAG => ReturnDeferredEventData::deserial(source).map(AuctionEvent::ReturnDeferred),
+            PAYOUT_DEFERRED_EVENT_TAG => PayoutDeferredEventData::deserial(source).map(AuctionEvent::PayoutDeferred),
+            TOKENS_DEFERRED_EVENT_TAG => TokensDeferredEventData::deserial(source).map(AuctionEvent::TokensDeferred),
+            _ => Err(ParseError::default()),
+        }
+    }
+}
+
+/// The schema of the fields of an event data struct.
+fn event_fields<T: schema::SchemaType>() -> schema::Fields {
+    match T::get_type() {
+        schema::Type::Struct(fields) => fields,
+        _ => schema::Fields::None,
+    }
+}
+
+impl schema::SchemaType for AuctionEvent {
+    fn get_type() -> schema::Type {
+        let mut event_map = collections::BTreeMap::new();
+        event_map.insert(REGISTER_EVENT_TAG, (String::from("Register"), event_fields::<AuctionEventData>()));
+        event_map.insert(FINALIZE_EVENT_TAG, (String::from("Finalize"), event_fields::<FinalizeEventData>()));
+        event_map.insert(CLOSE_EVENT_TAG, (String::from("Close"), event_fields::<AuctionEventData>()));
+        event_map.insert(BID_EVENT_TAG, (String::from("Bid"), event_fields::<BidEventData>()));
+        event_map.insert(RESCUE_EVENT_TAG, (String::from("Rescue"), event_fields::<RescueEventData>()));
+        event_map.insert(RECLAIM_EVENT_TAG, (String::from("Reclaim"), event_fields::<ReclaimEventData>()));
+        event_map.insert(DELIVERY_PENDING_EVENT_TAG, (String::from("DeliveryPending"), event_fields::<AuctionEventData>()));
+        event_map.insert(CANCEL_EVENT_TAG, (String::from("Cancel"), event_fields::<CancelEventData>()));
+        event_map.insert(APPROVAL_EVENT_TAG, (String::from("Approval"), event_fields::<ApprovalEventData>()));
+        event_map.insert(
+            COMMISSION_CHANGE_SCHEDULED_EVENT_TAG,
+            (String::from("CommissionChangeScheduled"), event_fields::<CommissionChange>()),
+        );
+        event_map.insert(
+            COMMISSION_CHANGE_CANCELLED_EVENT_TAG,
+            (String::from("CommissionChangeCancelled"), event_fields::<CommissionChange>()),
+        );
+        event_map.insert(
+            COMMISSION_CHANGE_APPLIED_EVENT_TAG,
+            (String::from("CommissionChangeApplied"), event_fields::<CommissionChange>()),
+        );
+        event_map.insert(ASK_EVENT_TAG, (String::from("Ask"), event_fields::<AskEventData>()));
+        event_map.insert(REFUND_EVENT_TAG, (String::from("Refund"), event_fields::<RefundEventData>()));
+        event_map.insert(RETRACT_EVENT_TAG, (String::from("Retract"), event_fields::<RetractEventData>()));
+        event_map.insert(OFFER_MADE_EVENT_TAG, (String::from("OfferMade"), event_fields::<OfferEventData>()));
+        event_map.insert(OFFER_CANCELLED_EVENT_TAG, (String::from("OfferCancelled"), event_fields::<OfferEventData>()));
+        event_map.insert(OFFER_ACCEPTED_EVENT_TAG, (String::from("OfferAccepted"), event_fields::<OfferEventData>()));
+        event_map.insert(OFFER_EXPIRED_EVENT_TAG, (String::from("OfferExpired"), event_fields::<OfferEventData>()));
+        event_map.insert(ITEM_UPDATED_EVENT_TAG, (String::from("ItemUpdated"), event_fields::<ItemUpdatedEventData>()));
+        event_map.insert(PAYOUT_FALLBACK_EVENT_TAG, (String::from("PayoutFallback"), event_fields::<PayoutFallbackEventData>()));
+        event_map.insert(BACKUP_BID_EVENT_TAG, (String::from("BackupBid"), event_fields::<BackupBidEventData>()));
+        event_map.insert(BACKUP_PROMOTED_EVENT_TAG, (String::from("BackupPromoted"), event_fields::<BackupBidEventData>()));
+        event_map.insert(BALANCE_CREDITED_EVENT_TAG, (String::from("BalanceCredited"), event_fields::<BalanceEventData>()));
+        event_map.insert(BALANCE_DEBITED_EVENT_TAG, (String::from("BalanceDebited"), event_fields::<BalanceEventData>()));
+        event_map.insert(ITEM_LISTED_EVENT_TAG, (String::from("ItemListed"), event_fields::<ItemEventData>()));
+        event_map.insert(ITEM_BID_EVENT_TAG, (String::from("ItemBid"), event_fields::<ItemEventData>()));
+        event_map.insert(ITEM_SOLD_EVENT_TAG, (String::from("ItemSold"), event_fields::<ItemEventData>()));
+        event_map.insert(ITEM_UNLISTED_EVENT_TAG, (String::from("ItemUnlisted"), event_fields::<ItemEventData>()));
+        event_map.insert(ITEM_RETURNED_EVENT_TAG, (String::from("ItemReturned"), event_fields::<ItemEventData>()));
+        event_map.insert(
+            COMMISSION_DEPOSITED_EVENT_TAG,
+            (String::from("CommissionDeposited"), event_fields::<CommissionSinkEventData>()),
+        );
+        event_map.insert(
+            COMMISSION_ACCRUED_EVENT_TAG,
+            (String::from("CommissionAccrued"), event_fields::<CommissionSinkEventData>()),
+        );
+        event_map.insert(TOKEN_BID_EVENT_TAG, (String::from("TokenBid"), event_fields::<TokenBidEventData>()));
+        event_map.insert(TOKEN_REFUND_EVENT_TAG, (String::from("TokenRefund"), event_fields::<TokenRefundEventData>()));
+        event_map.insert(TOKEN_PAYOUT_EVENT_TAG, (String::from("TokenPayout"), event_fields::<TokenPayoutEventData>()));
+        event_map.insert(
+            RETURN_DEFERRED_EVENT_TAG,
+            (String::from("ReturnDeferred"), event_fields::<ReturnDeferredEventData>()),
+        );
+        event_map.insert(
+            PAYOUT_DEFERRED_EVENT_TAG,
+            (String::from("PayoutDeferred"), event_fields::<PayoutDeferredEventData>()),
+        );
+        event_map.insert(
+            TOKENS_DEFERRED_EVENT_TAG,
+            (String::from("TokensDeferred"), event_fields::<TokensDeferredEventData>()),
+        );
+        schema::Type::TaggedEnum(event_map)
+    }
+}
+
+/// The minimum amount by which a bid must exceed the current highest bid.
+#[derive(Debug, Serialize, SchemaType, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Raise {
+    /// A fixed amount.
+    Flat(Amount),
+    /// Basis points of the current highest bid, rounded up to the next µCCD.
+    Percent(u16),
+}
+
+impl Raise {
+    /// The minimum raise over `highest_bid`.
+    fn minimum_for(&self, highest_bid: Amount) -> Amount {
+        match self {
+            Raise::Flat(amount) => *amount,
+            Raise::Percent(bps) => basis_points_of(highest_bid, *bps),
+        }
+    }
+}
+
+/// `bps` basis points of `amount`, rounded up to the next µCCD.
+fn basis_points_of(amount: Amount, bps: u16) -> Amount {
+    let whole = u128::from(TOTAL_BASIS_POINTS);
+    let part = (u128::from(amount.micro_ccd) * u128::from(bps)).div_ceil(whole);
+    Amount::from_micro_ccd(u64::try_from(part).unwrap_or(u64::MAX))
+}
+
+/// The kind of an auction.
+#[derive(Debug, Serialize, SchemaType, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AuctionKind {
+    /// The owner sells the tokens to the highest bidder.
+    Standard,
+    /// The owner buys the tokens from the lowest asker, paying at most the
+    /// escrowed budget. The highest bid and bidder of such an auction are the
+    /// lowest ask and asker.
+    Reverse {
+        budget: Amount,
+    },
+    /// The owner sells the tokens to the highest bidder for the second
+    /// highest bid, or the initial price if there was only one bid.
+    SecondPrice {
+        second_bid: Amount, // The highest outbid highest bid, zero while there is at most one bid; see `count_backup_bid`
+    },
+    /// The owner sells the tokens for a fixed price to the first buyer, with
+    /// no bids and no end.
+    FixedPrice {
+        price: Amount,
+    },
+}
+
+impl AuctionKind {
+    /// Whether the auction takes asks instead of bids.
+    fn is_reverse(&self) -> bool { matches!(self, AuctionKind::Reverse { .. }) }
+
+    /// Whether the auction is a fixed-price listing, bought with `purchase`.
+    fn is_fixed_price(&self) -> bool { matches!(self, AuctionKind::FixedPrice { .. }) }
+}
+
+/// A CIS-2 token an auction accepts bids in besides CCD, at a fixed ratio
+/// the owner declares when listing: one unit of the token is worth
+/// `ratio_numerator / ratio_denominator` µCCD. Bids in the token and in CCD
+/// are compared by this value, and the highest bid of the auction is the
+/// value of a bid in the token, while the tokens themselves are held and
+/// refunded or paid out as they are.
+#[derive(Debug, Serialize, SchemaType, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AltBidToken {
+    pub contract:          ContractAddress, // CIS-2 token contract address
+    pub token_id:          TokenIdU8,       // CIS-2 token ID
+    pub ratio_numerator:   u64,
+    pub ratio_denominator: u64,
+}
+
+impl AltBidToken {
+    /// The value of `tokens` in CCD, rounded down to the µCCD, or `None` if
+    /// it does not fit into an amount. Listing an auction ensures that the
+    /// ratio is not zero.
+    pub fn value_of(&self, tokens: TokenAmountU64) -> Option<Amount> {
+        let micro_ccd = u128::from(tokens.0) * u128::from(self.ratio_numerator) / u128::from(self.ratio_denominator);
+        u64::try_from(micro_ccd).ok().map(Amount::from_micro_ccd)
+    }
 }
 
 /// Auction struct representing a single auction.
 #[derive(Debug, Serialize, SchemaType, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Auction {
-    auction_state: AuctionState,
-    highest_bidder: Option<AccountAddress>,
-    initial_price: u64,
-    highest_bid: Amount,
-    item: String,
-    end: Timestamp,
-    owner: AccountAddress,
-    token_contract: ContractAddress, // CIS-2 token contract address
-    token_id: TokenIdU8,               // CIS-2 token ID
-    token_amount: TokenAmountU64,               // Amount of tokens
+    pub auction_state: AuctionState,
+    pub highest_bidder: Option<AccountAddress>,
+    pub beneficiary: Option<AccountAddress>, // Receives the tokens instead of the highest bidder
+    pub initial_price: u64,
+    pub highest_bid: Amount,
+    pub item: String,
+    pub end: Timestamp,
+    pub owner: Address,
+    pub token_contract: ContractAddress, // CIS-2 token contract address
+    pub token_id: TokenIdU8,               // CIS-2 token ID
+    pub token_amount: TokenAmountU64,               // Amount of tokens
+    pub settlement_exclusivity: Option<Duration>,   // Time after `end` in which only the owner or admin may finalize
+    pub minimum_raise: Raise,                       // How much a bid must exceed the current highest bid by
+    pub requires_owner_approval: bool,              // The owner accepts or rejects the winning bid after `end`
+    pub finalize_reward: Amount,                    // Paid to whoever finalizes the auction if it has a winner
+    pub commission_tiers: Vec<CommissionTier>,      // Commission tiers when the auction was listed
+    pub kind: AuctionKind,
+    pub escrow_confirmed: bool,                      // The contract received the tokens, or for reverse auctions the budget
+    pub bid_count: u32,                             // Number of bids placed, including top-ups
+    pub unique_bidders: u32,                        // Number of distinct bidders, counted up to `MAX_COUNTED_BIDDERS`
+    pub payout_token: Option<(ContractAddress, TokenIdU8)>, // Token the owner's share is swapped for, instead of CCD
+    pub accepts_backup_bids: bool,                  // Bids not beating the highest bid can stand as backup bids
+    pub alt_bid_token: Option<AltBidToken>,         // CIS-2 token accepted for bids besides CCD
+    pub token_bid: Option<TokenAmountU64>,          // Tokens of `alt_bid_token` held for the highest bid, if it was placed in them
+    pub amount_sold: TokenAmountU64,                // All of `token_amount` once delivered to the winner, else zero: sales are all-or-nothing
+    pub amount_remaining: TokenAmountU64,           // All of `token_amount` while escrowed for the auction, else zero
+}
+
+/// A bid standing in for the highest bid of an auction accepting backup
+/// bids, with its CCD held by the contract.
+#[derive(Debug, Serialize, SchemaType, Clone, Copy, PartialEq, Eq)]
+pub struct BackupBid {
+    pub bidder:      AccountAddress,
+    pub beneficiary: Option<AccountAddress>, // Receives the tokens instead of the bidder
+    pub amount:      Amount,
+}
+
+/// A binding offer for the tokens of a listing or an auction without bids,
+/// with the offered CCD held by the contract.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub listing_id: u32,
+    pub offerer:    AccountAddress,
+    pub amount:     Amount,
+    pub expiry:     Timestamp, // Last time at which the owner can accept the offer
+}
+
+/// The record of how a sold auction was settled, kept for clients polling
+/// `view_settlement`.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+pub struct SettlementRecord {
+    pub finalizer:        Address,   // Sender of the call that settled the auction
+    pub settled_at:       Timestamp,
+    pub seller_amount:    Amount,    // Paid to the owner, or to the asker of a reverse auction, zero for a bid in the alternative bid token
+    pub commission:       Amount,    // Paid to the commission recipients, zero for a bid in the alternative bid token
+    pub finalize_reward:  Amount,    // Paid to the finalizer
+    pub tokens_delivered: bool,      // Whether the tokens reached the winner; no CCD is paid out until they do
+}
+
+/// The settlement of an auction, as returned by `view_settlement`.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+pub enum SettlementStatus {
+    NotSettled,
+    Settled(SettlementRecord),
+}
+
+/// What `finalize` would do with an auction, as previewed by
+/// `view_settlement_preview`.
+#[derive(Debug, Serialize, SchemaType, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewStatus {
+    /// The tokens would be delivered to the winner, and the bid paid out.
+    WouldSettleToWinner,
+    /// The auction has no bids, and the tokens would be returned to the owner.
+    WouldReturnUnsold,
+    /// The auction has not ended yet.
+    NotYetEnded,
+    /// The auction is no longer open, so `finalize` would fail.
+    AlreadyFinalized,
+}
+
+/// The payouts `finalize` would make for an auction right now, as returned
+/// by `view_settlement_preview`. All amounts are zero unless the auction
+/// would settle to the winner.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+pub struct SettlementPreview {
+    pub status:             PreviewStatus,
+    pub winner:             Option<AccountAddress>, // Receives the tokens, or none if they stay with or return to the owner
+    pub price:              Amount,         // Charged to the winner, or the value of a winning bid in the alternative bid token
+    pub commission:         Amount,
+    pub commission_payouts: Vec<(CommissionSink, Amount)>, // Commission per recipient, including contract sinks
+    pub finalize_reward:    Amount,         // Paid to the sender of the preview, if it is an account
+    pub owner_amount:       Amount,         // Paid to the owner
+    pub refund:             Amount,         // Rest of the highest bid returned to the winner
+    pub owner_tokens:       TokenAmountU64, // Paid to the owner for a winning bid in the alternative bid token
+    pub commission_tokens:  TokenAmountU64, // Commission of a winning bid in the alternative bid token
+}
+
+impl SettlementPreview {
+    /// A preview without payouts.
+    fn without_payouts(status: PreviewStatus) -> Self {
+        SettlementPreview {
+            status,
+            winner: None,
+            price: Amount::zero(),
+            commission: Amount::zero(),
+            commission_payouts: Vec::new(),
+            finalize_reward: Amount::zero(),
+            owner_amount: Amount::zero(),
+            refund: Amount::zero(),
+            owner_tokens: TokenAmountU64(0),
+            commission_tokens: TokenAmountU64(0),
+        }
+    }
+}
+
+/// The state of the smart contract.
+#[derive(Serial, DeserialWithState)]
+#[concordium(state_parameter = "S")]
+pub struct State<S = ExternStateApi> {
+    auctions: StateMap<u32, Auction, S>, // Auctions by ID, so that an update only loads the auction it touches
+    auction_count: u32, // Number of auctions ever created, and the ID of the next one
+    commission_recipients: Vec<CommissionShare>, // Recipients sharing the commission
+    commission_tiers: Vec<CommissionTier>, // Commission tiers for new auctions
+    commission_change_delay: Duration, // Minimum time between scheduling and applying a commission change
+    pending_commission_change: Option<CommissionChange>, // Scheduled commission change
+    admin: AccountAddress,
+    auto_settle_late_bids: bool, // Settle an ended auction when a late bid arrives
+    reclaim_grace_period: Duration, // Time after `end` after which an unfinalized auction can be reclaimed
+    cancellation_fee_bps: u16, // Fee for cancelling an auction with bids, in basis points of the highest bid
+    owner_approval_window: Duration, // Time after `end` in which the owner decides on the winning bid, if required
+    retraction_penalty_bps: u16, // Penalty for retracting a bid, in basis points of the bid
+    retraction_cutoff: Duration, // Time before `end` from which bids can no longer be retracted
+    pending_refunds: StateMap<AccountAddress, Amount, S>, // Refunds that could not be sent and can be claimed
+    bids_by_bidder: StateMap<AccountAddress, StateSet<u32, S>, S>, // Auctions in which an account is the highest bidder
+    auctions_by_token_contract: StateMap<ContractAddress, StateSet<u32, S>, S>, // Live auctions by token contract
+    escrowed: StateMap<(ContractAddress, TokenIdU8), TokenAmountU64, S>, // Tokens committed to live auctions, including bids in alternative bid tokens
+    escrow_receipts: StateMap<(ContractAddress, TokenIdU8, Address), TokenAmountU64, S>, // Tokens received from owners and not yet committed to an auction
+    awaiting_escrow: Option<ContractAddress>, // Token contract of the escrow transfer `create_auction` is making
+    cis0_exempt: StateSet<ContractAddress, S>, // Token contracts listed without checking CIS-2 support
+    swap_contract: Option<ContractAddress>, // Contract swapping the owners' CCD for their payout tokens
+    active_auctions: StateMap<Address, u32, S>, // Number of auctions not settled yet, by owner
+    max_active_auctions: u32, // Maximum number of auctions not settled yet an owner can have
+    max_auction_duration: Duration, // Maximum time from creation to the end of an auction
+    delivery_window: Duration, // Time after `end` in which only the owner can refund an undelivered reverse auction
+    pending_returns: StateSet<u32, S>, // Unsold auctions whose remaining tokens the owner is yet to reclaim
+    bidders_by_auction: StateMap<u32, StateSet<AccountAddress, S>, S>, // Accounts that have bid on an auction not settled yet
+    offers: StateMap<u64, Offer, S>, // Open offers by ID
+    offers_by_listing: StateMap<u32, StateSet<u64, S>, S>, // IDs of the open offers of a listing or auction
+    next_offer_id: u64, // ID of the next offer
+    offers_total: Amount, // Sum of the open offers
+    stats: Stats, // Lifetime statistics
+    standing_bids_total: Amount, // Sum of the highest bids in CCD of unsettled auctions, including those pending delivery
+    reverse_budgets_total: Amount, // Sum of the budgets of unsettled reverse auctions
+    pending_refunds_total: Amount, // Sum of the pending refunds
+    pending_proceeds: StateMap<ContractAddress, Amount, S>, // Proceeds of contract owners, claimed with `claim_proceeds`
+    pending_proceeds_total: Amount, // Sum of the pending proceeds
+    pending_token_claims: StateMap<(ContractAddress, TokenIdU8, Address), TokenAmountU64, S>, // Tokens of failed token refunds and payouts, claimed with `claim_tokens`
+    settlements: StateMap<u32, SettlementRecord, S>, // Settlement records of sold auctions
+    backup_bids: StateMap<u32, BackupBid, S>, // Backup bids of unsettled auctions
+    backup_bids_total: Amount, // Sum of the backup bids
+    balances: StateMap<AccountAddress, Amount, S>, // CCD deposited by accounts to bid from
+    balances_total: Amount, // Sum of the balances
+    balance_funded_bids: StateSet<u32, S>, // Auctions whose highest bid is credited back to the balance of the bidder when outbid
+}
+
+/// Whether a set in the state has no values. `StateSet::is_empty` only looks
+/// up the key of the set itself, which is never stored, so it is true for any
+/// set.
+fn set_is_empty<T: DeserialWithState<S>, S: HasStateApi>(set: &StateSet<T, S>) -> bool { set.iter().next().is_none() }
+
+impl State {
+    /// A copy of the auction with the given ID.
+    fn auction(&self, auction_id: u32) -> Result<Auction, BidError> {
+        let auction = self.auctions.get(&auction_id).ok_or(BidError::AuctionNotFound)?;
+        Ok(Auction::clone(&auction))
+    }
+
+    /// Whether an auction with the given ID exists.
+    fn has_auction(&self, auction_id: u32) -> bool {
+        auction_id < self.auction_count
+    }
+
+    /// Stores a new auction under the next ID and returns the ID.
+    fn add_auction(&mut self, auction: Auction) -> Result<u32, BidError> {
+        let auction_id = self.auction_count;
+        self.auction_count = auction_id.checked_add(1).ok_or(BidError::ArithmeticOverflow)?;
+        let _ = self.auctions.insert(auction_id, auction);
+        Ok(auction_id)
+    }
+
+    /// Stores the changed auction with the given ID.
+    fn store_auction(&mut self, auction_id: u32, auction: Auction) {
+        let _ = self.auctions.insert(auction_id, auction);
+    }
+
+    /// Copies of at most `take` auctions in ID order, starting at `from`.
+    fn auction_range(&self, from: u32, take: u32) -> Vec<(u32, Auction)> {
+        let to = from.saturating_add(take).min(self.auction_count);
+        (from..to).filter_map(|auction_id| self.auction(auction_id).ok().map(|auction| (auction_id, auction))).collect()
+    }
+
+    /// Records `bidder` as the highest bidder of the auction in the reverse index.
+    fn track_highest_bid(&mut self, state_builder: &mut StateBuilder<ExternStateApi>, bidder: AccountAddress, auction_id: u32) {
+        self.bids_by_bidder.entry(bidder).or_insert_with(|| state_builder.new_set()).insert(auction_id);
+    }
+
+    /// Removes the auction from the reverse index entry of `bidder`.
+    fn untrack_highest_bid(&mut self, bidder: &AccountAddress, auction_id: u32) {
+        let now_empty = match self.bids_by_bidder.get_mut(bidder) {
+            Some(mut auction_ids) => {
+                auction_ids.remove(&auction_id);
+                set_is_empty(&auction_ids)
+            }
+            None => false,
+        };
+        if now_empty {
+            self.bids_by_bidder.remove(bidder);
+        }
+    }
+
+    /// Adds a live auction to the index of its token contract.
+    fn index_auction(&mut self, state_builder: &mut StateBuilder<ExternStateApi>, token_contract: ContractAddress, auction_id: u32) {
+        self.auctions_by_token_contract.entry(token_contract).or_insert_with(|| state_builder.new_set()).insert(auction_id);
+    }
+
+    /// Removes an auction from the index of its token contract.
+    fn unindex_auction(&mut self, token_contract: &ContractAddress, auction_id: u32) {
+        let now_empty = match self.auctions_by_token_contract.get_mut(token_contract) {
+            Some(mut auction_ids) => {
+                auction_ids.remove(&auction_id);
+                set_is_empty(&auction_ids)
+            }
+            None => false,
+        };
+        if now_empty {
+            self.auctions_by_token_contract.remove(token_contract);
+        }
+    }
+
+    /// The amount of a token committed to live auctions.
+    fn escrowed_amount(&self, token_contract: ContractAddress, token_id: TokenIdU8) -> TokenAmountU64 {
+        self.escrowed.get(&(token_contract, token_id)).map_or(TokenAmountU64(0), |amount| *amount)
+    }
+
+    /// Records tokens escrowed for a new auction.
+    fn add_escrow(
+        &mut self,
+        token_contract: ContractAddress,
+        token_id: TokenIdU8,
+        amount: TokenAmountU64,
+    ) -> Result<(), BidError> {
+        let escrowed = self.escrowed_amount(token_contract, token_id);
+        let escrowed = escrowed.0.checked_add(amount.0).ok_or(BidError::ArithmeticOverflow)?;
+        let _ = self.escrowed.insert((token_contract, token_id), TokenAmountU64(escrowed));
+        Ok(())
+    }
+
+    /// Records tokens leaving the escrow of a settled auction.
+    fn release_escrow(&mut self, token_contract: ContractAddress, token_id: TokenIdU8, amount: TokenAmountU64) {
+        let remaining = self.escrowed_amount(token_contract, token_id).0.saturating_sub(amount.0);
+        if remaining == 0 {
+            self.escrowed.remove(&(token_contract, token_id));
+        } else {
+            let _ = self.escrowed.insert((token_contract, token_id), TokenAmountU64(remaining));
+        }
+    }
+
+    /// Releases the tokens still escrowed for an auction, counting `sold` of
+    /// them as delivered to the winner and the rest as returned to the owner.
+    /// An auction sells all of its tokens or none, so `sold` is either zero or
+    /// all of the remaining tokens.
+    fn release_auction_escrow(&mut self, auction_id: u32, sold: TokenAmountU64) -> Result<(), BidError> {
+        let mut auction = self.auction(auction_id)?;
+        let remaining = auction.amount_remaining;
+        ensure!(sold.0 <= remaining.0, BidError::EscrowInvariantViolated);
+        auction.amount_sold = TokenAmountU64(auction.amount_sold.0.checked_add(sold.0).ok_or(BidError::ArithmeticOverflow)?);
+        auction.amount_remaining = TokenAmountU64(0);
+        let (token_contract, token_id) = (auction.token_contract, auction.token_id);
+        self.store_auction(auction_id, auction);
+        self.release_escrow(token_contract, token_id, remaining);
+        Ok(())
+    }
+
+    /// Keeps `amount` tokens of an unsold auction escrowed, after returning
+    /// them to the owner failed, for the owner to reclaim.
+    fn defer_return(&mut self, auction_id: u32, amount: TokenAmountU64) -> Result<(), BidError> {
+        let mut auction = self.auction(auction_id)?;
+        self.add_escrow(auction.token_contract, auction.token_id, amount)?;
+        auction.amount_remaining = amount;
+        self.store_auction(auction_id, auction);
+        self.pending_returns.insert(auction_id);
+        Ok(())
+    }
+
+    /// Records tokens received from `owner` by an escrow transfer.
+    fn credit_escrow_receipt(
+        &mut self,
+        token_contract: ContractAddress,
+        token_id: TokenIdU8,
+        owner: Address,
+        amount: TokenAmountU64,
+    ) -> Result<(), BidError> {
+        let mut received = self.escrow_receipts.entry((token_contract, token_id, owner)).or_insert(TokenAmountU64(0));
+        received.0 = received.0.checked_add(amount.0).ok_or(BidError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Commits `amount` of the tokens received from `owner` to an auction.
+    /// Returns false, leaving the receipts as they are, if fewer tokens were
+    /// received.
+    fn take_escrow_receipt(
+        &mut self,
+        token_contract: ContractAddress,
+        token_id: TokenIdU8,
+        owner: Address,
+        amount: TokenAmountU64,
+    ) -> bool {
+        let key = (token_contract, token_id, owner);
+        let received = self.escrow_receipts.get(&key).map_or(0, |received| received.0);
+        if received < amount.0 {
+            return false;
+        }
+        if received == amount.0 {
+            self.escrow_receipts.remove(&key);
+        } else {
+            let _ = self.escrow_receipts.insert(key, TokenAmountU64(received - amount.0));
+        }
+        true
+    }
+
+    /// The number of auctions of `owner` not settled yet.
+    fn active_auction_count(&self, owner: &Address) -> u32 {
+        self.active_auctions.get(owner).map_or(0, |count| *count)
+    }
+
+    /// Sets the state of an auction, leaving the active count of its owner
+    /// once it is no longer `NotSoldYet`.
+    fn set_auction_state(&mut self, auction_id: u32, auction_state: AuctionState) {
+        let Ok(mut auction) = self.auction(auction_id) else {
+            return;
+        };
+        let settles = auction.auction_state == AuctionState::NotSoldYet && auction_state != AuctionState::NotSoldYet;
+        auction.auction_state = auction_state;
+        let owner = auction.owner;
+        let token_contract = auction.token_contract;
+        self.store_auction(auction_id, auction);
+        if settles {
+            // A backup bid left on an auction closed other than by settlement
+            // is kept claimable like a failed refund
+            if let Some(backup) = self.take_backup_bid(auction_id) {
+                self.credit_refund(backup.bidder, backup.amount);
+            }
+            self.bidders_by_auction.remove(&auction_id);
+            self.balance_funded_bids.remove(&auction_id);
+            self.unindex_auction(&token_contract, auction_id);
+            match self.active_auction_count(&owner) {
+                0 | 1 => {
+                    self.active_auctions.remove(&owner);
+                }
+                count => {
+                    let _ = self.active_auctions.insert(owner, count - 1);
+                }
+            }
+        }
+    }
+
+    /// Counts a bid of `bidder` on the auction, and counts `bidder` as a new
+    /// bidder unless they have bid on it before or the count has reached
+    /// `MAX_COUNTED_BIDDERS`.
+    fn record_bid(&mut self, state_builder: &mut StateBuilder<ExternStateApi>, auction_id: u32, bidder: AccountAddress) {
+        let Ok(mut auction) = self.auction(auction_id) else {
+            return;
+        };
+        auction.bid_count = auction.bid_count.saturating_add(1);
+        if auction.unique_bidders < MAX_COUNTED_BIDDERS {
+            let is_new = self.bidders_by_auction.entry(auction_id).or_insert_with(|| state_builder.new_set()).insert(bidder);
+            if is_new {
+                auction.unique_bidders += 1;
+            }
+        }
+        self.store_auction(auction_id, auction);
+    }
+
+    /// Stores a new offer and returns its ID.
+    fn add_offer(&mut self, state_builder: &mut StateBuilder<ExternStateApi>, offer: Offer) -> Result<u64, BidError> {
+        let offer_id = self.next_offer_id;
+        self.next_offer_id = offer_id.checked_add(1).ok_or(BidError::ArithmeticOverflow)?;
+        self.offers_total = self.offers_total.checked_add(offer.amount).ok_or(BidError::ArithmeticOverflow)?;
+        self.offers_by_listing.entry(offer.listing_id).or_insert_with(|| state_builder.new_set()).insert(offer_id);
+        let _ = self.offers.insert(offer_id, offer);
+        Ok(offer_id)
+    }
+
+    /// Removes and returns an open offer.
+    fn remove_offer(&mut self, offer_id: u64) -> Option<Offer> {
+        let offer = self.offers.remove_and_get(&offer_id)?;
+        self.offers_total -= offer.amount;
+        let now_empty = match self.offers_by_listing.get_mut(&offer.listing_id) {
+            Some(mut offer_ids) => {
+                offer_ids.remove(&offer_id);
+                set_is_empty(&offer_ids)
+            }
+            None => false,
+        };
+        if now_empty {
+            self.offers_by_listing.remove(&offer.listing_id);
+        }
+        Some(offer)
+    }
+
+    /// The IDs of the open offers of a listing or auction.
+    fn offer_ids(&self, listing_id: u32) -> Vec<u64> {
+        self.offers_by_listing.get(&listing_id).map_or(Vec::new(), |offer_ids| offer_ids.iter().map(|id| *id).collect())
+    }
+
+    /// Adds `amount` to the refund that `account` can claim.
+    fn credit_refund(&mut self, account: AccountAddress, amount: Amount) {
+        *self.pending_refunds.entry(account).or_insert_with(Amount::zero) += amount;
+        self.pending_refunds_total += amount;
+    }
+
+    /// Removes and returns the backup bid of an auction.
+    fn take_backup_bid(&mut self, auction_id: u32) -> Option<BackupBid> {
+        let backup = self.backup_bids.remove_and_get(&auction_id)?;
+        self.backup_bids_total -= backup.amount;
+        Some(backup)
+    }
+
+    /// Makes the backup bid of an auction, if it has one, its highest bid
+    /// after the highest bid was retracted or rejected, and returns it.
+    fn promote_backup_bid(&mut self, state_builder: &mut StateBuilder<ExternStateApi>, auction_id: u32) -> Option<BackupBid> {
+        let mut auction = self.auction(auction_id).ok()?;
+        let backup = self.take_backup_bid(auction_id)?;
+        auction.highest_bidder = Some(backup.bidder);
+        auction.beneficiary = backup.beneficiary;
+        auction.highest_bid = backup.amount;
+        if let AuctionKind::SecondPrice { second_bid } = &mut auction.kind {
+            *second_bid = Amount::zero();
+        }
+        self.store_auction(auction_id, auction);
+        self.track_highest_bid(state_builder, backup.bidder, auction_id);
+        self.standing_bids_total += backup.amount;
+        Some(backup)
+    }
+
+    /// Removes and returns the refund that `account` can claim.
+    fn take_refund(&mut self, account: &AccountAddress) -> Option<Amount> {
+        let refund = self.pending_refunds.remove_and_get(account)?;
+        self.pending_refunds_total -= refund;
+        Some(refund)
+    }
+
+    /// Adds `amount` to the proceeds that the contract `owner` can claim.
+    fn credit_proceeds(&mut self, owner: ContractAddress, amount: Amount) {
+        *self.pending_proceeds.entry(owner).or_insert_with(Amount::zero) += amount;
+        self.pending_proceeds_total += amount;
+    }
+
+    /// Keeps `amount` tokens that could not be transferred to `recipient`
+    /// escrowed for it to claim.
+    fn credit_token_claim(
+        &mut self,
+        token_contract: ContractAddress,
+        token_id: TokenIdU8,
+        recipient: Address,
+        amount: TokenAmountU64,
+    ) -> Result<(), BidError> {
+        self.add_escrow(token_contract, token_id, amount)?;
+        let mut claim = self.pending_token_claims.entry((token_contract, token_id, recipient)).or_insert(TokenAmountU64(0));
+        claim.0 = claim.0.checked_add(amount.0).ok_or(BidError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Removes and returns the tokens that `recipient` can claim, releasing
+    /// them from the escrow.
+    fn take_token_claim(&mut self, token_contract: ContractAddress, token_id: TokenIdU8, recipient: Address) -> Option<TokenAmountU64> {
+        let claim = self.pending_token_claims.remove_and_get(&(token_contract, token_id, recipient))?;
+        self.release_escrow(token_contract, token_id, claim);
+        Some(claim)
+    }
+
+    /// Removes and returns the proceeds that the contract `owner` can claim.
+    fn take_proceeds(&mut self, owner: &ContractAddress) -> Option<Amount> {
+        let proceeds = self.pending_proceeds.remove_and_get(owner)?;
+        self.pending_proceeds_total -= proceeds;
+        Some(proceeds)
+    }
+}
+
+/// Entrypoint receiving the escrow transfers of `create_auction`.
+const ON_RECEIVING_CIS2: EntrypointName = EntrypointName::new_unchecked("onReceivingCIS2");
+
+/// Entrypoint of the swap contract exchanging CCD for tokens.
+const SWAP: EntrypointName = EntrypointName::new_unchecked("swap");
+
+/// Type of the parameter of the `swap` entrypoint of the swap contract. The
+/// attached CCD are exchanged for tokens sent to `recipient`.
+#[derive(Serialize, SchemaType)]
+pub struct SwapParameter {
+    pub token_contract: ContractAddress,
+    pub token_id:       TokenIdU8,
+    pub recipient:      Address,
+}
+
+/// Client for the swap contract set by the admin.
+struct SwapClient {
+    contract: ContractAddress,
+}
+
+impl SwapClient {
+    fn new(contract: ContractAddress) -> Self { Self { contract } }
+
+    /// Swaps `amount` CCD for tokens of `token` sent to `recipient`. Any
+    /// failure of the swap contract is reported as `SwapFailed`, and leaves
+    /// the CCD with this contract.
+    fn swap(
+        &self,
+        host: &mut Host<State>,
+        amount: Amount,
+        (token_contract, token_id): (ContractAddress, TokenIdU8),
+        recipient: Address,
+    ) -> Result<(), BidError> {
+        let parameter = SwapParameter {
+            token_contract,
+            token_id,
+            recipient,
+        };
+        host.invoke_contract(&self.contract, &parameter, SWAP, amount).map_err(|_| BidError::SwapFailed)?;
+        Ok(())
+    }
+}
+
+/// Version of the contract, from the version of the crate it was built from.
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Maximum number of entries returned by a paginated view.
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// Maximum number of auctions that can be requested from `get_auctions`.
+pub const MAX_GET_AUCTIONS: usize = 50;
+
+/// Maximum number of auctions `view_auctions` returns, to keep its return
+/// value within limits.
+pub const MAX_VIEW_AUCTIONS: usize = 1000;
+
+/// Maximum finalize reward an auction can offer.
+pub const MAX_FINALIZE_REWARD: Amount = Amount::from_ccd(100);
+
+/// Maximum share of the highest bid paid as the finalize reward, in basis
+/// points. A higher reward is capped to this share.
+pub const MAX_FINALIZE_REWARD_BPS: u16 = 100;
+
+/// Maximum length of the item description of an auction, in bytes.
+pub const MAX_ITEM_LENGTH: usize = 512;
+
+/// Maximum number of bytes of data that can be attached to a bid.
+pub const MAX_BID_DATA_SIZE: usize = 256;
+
+/// Maximum number of distinct bidders counted per auction. Bidders beyond it
+/// are not stored, so the count stops there.
+pub const MAX_COUNTED_BIDDERS: u32 = 1000;
+
+/// Type of the parameter of paginated views.
+#[derive(Serialize, SchemaType)]
+pub struct PageParameter {
+    pub skip: u32,  // Number of entries to skip
+    pub take: u32,  // Maximum number of entries to return, capped at `MAX_PAGE_SIZE`
+}
+
+/// Type of the parameter of `get_auctions_range`.
+#[derive(Serialize, SchemaType)]
+pub struct AuctionRangeParameter {
+    pub from: u32,  // First auction ID of the range
+    pub to:   u32,  // Auction ID after the range
+}
+
+/// A range of auctions and the number of auctions ever created.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct AuctionRange {
+    pub auction_count: u32,                // Number of auctions ever created, so clients know where the IDs end
+    pub auctions:      Vec<(u32, Auction)>, // Existing auctions in the range, with their IDs
+}
+
+/// Type of the parameter of `view_auctions_by_token_contract`.
+#[derive(Serialize, SchemaType)]
+pub struct TokenContractAuctionsParameter {
+    pub token_contract: ContractAddress,
+    pub token_id:       Option<TokenIdU8>, // Only return auctions of this token
+    pub page:           PageParameter,     // Page of the matching auctions
+}
+
+/// Tokens of one token ID the contract should be holding.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct EscrowedTokens {
+    pub token_contract: ContractAddress,
+    pub token_id:       TokenIdU8,
+    pub amount:         TokenAmountU64,
+}
+
+/// Tokens kept for a recipient to claim with `claim_tokens`.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct TokenClaim {
+    pub token_contract: ContractAddress,
+    pub token_id:       TokenIdU8,
+    pub recipient:      Address,
+    pub amount:         TokenAmountU64,
+}
+
+/// What the contract should be holding, for comparison with its balances.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct EscrowSummary {
+    pub standing_bids: Amount,    // Sum of the highest bids of unsettled auctions
+    pub pending_refunds: Amount,  // Sum of the refunds accounts can claim
+    pub reverse_budgets: Amount,  // Sum of the budgets of unsettled reverse auctions
+    pub open_offers: Amount,      // Sum of the open offers
+    pub pending_proceeds: Amount, // Sum of the proceeds contract owners can claim
+    pub backup_bids: Amount,      // Sum of the backup bids of unsettled auctions
+    pub balances: Amount,         // Sum of the balances accounts deposited to bid from
+    pub total_ccd: Amount,        // CCD the contract should be holding
+    pub tokens: Vec<EscrowedTokens>, // Tokens escrowed for live auctions, unsold remainders and token claims
+    pub unsold_remainders: Vec<(u32, TokenAmountU64)>, // Unsold auctions with tokens their owner is yet to reclaim
+    pub token_claims: Vec<TokenClaim>, // Tokens kept for recipients to claim with `claim_tokens`, also part of `tokens`
+}
+
+/// What the contract holds for a single account.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct AccountPosition {
+    pub pending_refund: Amount,            // Refund the account can claim
+    pub highest_bids: Vec<(u32, Amount)>,  // Auctions the account is leading, with its bid
+}
+
+/// Type of the parameter of `view_state`, with a page for each collection.
+#[derive(Serialize, SchemaType)]
+pub struct StateViewParameter {
+    pub auctions: PageParameter, // Page of the auctions, by ID
+    pub refunds:  PageParameter, // Page of the pending refunds
+}
+
+/// Snapshot of the contract state returned by `view_state`. Each version is
+/// serialized with its own tag byte, so consumers can tell versions apart and
+/// new fields go into a new version instead of changing an existing layout.
+#[derive(Debug, Serialize, SchemaType)]
+pub enum StateView {
+    V1(StateViewV1),
+}
+
+/// Version 1 of the `view_state` snapshot.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct StateViewV1 {
+    pub admin: AccountAddress,
+    pub commission_recipients: Vec<CommissionShare>,
+    pub commission_tiers: Vec<CommissionTier>,
+    pub commission_change_delay: Duration,
+    pub pending_commission_change: Option<CommissionChange>,
+    pub auto_settle_late_bids: bool,
+    pub reclaim_grace_period: Duration,
+    pub cancellation_fee_bps: u16,
+    pub owner_approval_window: Duration,
+    pub stats: Stats,
+    pub auction_count: u32,                            // Number of auctions ever created
+    pub auctions: Vec<(u32, Auction)>,                 // Requested page of the auctions, with their IDs
+    pub pending_refunds: Vec<(AccountAddress, Amount)>, // Requested page of the pending refunds
+}
+
+/// Configuration of the contract returned by `view_config`, for frontends to
+/// read instead of hard-coding it. Versioned like `StateView`: new settings
+/// go into a new version, so existing decoders keep working.
+#[derive(Debug, Serialize, SchemaType)]
+pub enum ConfigView {
+    V1(ConfigViewV1),
+}
+
+/// Version 1 of the `view_config` configuration.
+#[derive(Debug, Serialize, SchemaType)]
+pub struct ConfigViewV1 {
+    pub version: String,                                // `CONTRACT_VERSION` of the running contract
+    pub admin: AccountAddress,
+    pub commission_recipients: Vec<CommissionShare>,
+    pub commission_tiers: Vec<CommissionTier>,
+    pub commission_change_delay: Duration,
+    pub pending_commission_change: Option<CommissionChange>,
+    pub auto_settle_late_bids: bool,
+    pub reclaim_grace_period: Duration,
+    pub cancellation_fee_bps: u16,
+    pub owner_approval_window: Duration,
+    pub retraction_penalty_bps: u16,
+    pub retraction_cutoff: Duration,
+    pub max_active_auctions: u32,
+    pub max_auction_duration: Duration,
+    pub delivery_window: Duration,
+    pub swap_contract: Option<ContractAddress>,
+}
+
+/// Lifetime statistics of the contract, kept in its state so that they carry
+/// over into any migration of the state.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub auctions_created: u64,  // Auctions created or listed, not counting relistings
+    pub auctions_sold: u64,     // Auctions whose tokens were delivered to a winner
+    pub volume_settled: Amount, // Sum of the winning bids of sold auctions
+    pub commission_paid: Amount, // Sum of the commission paid out for sold auctions
+}
+
+impl Stats {
+    /// Records a sold auction, saturating instead of overflowing.
+    fn record_sale(&mut self, price: Amount, commission: Amount) {
+        let saturating_add = |total: Amount, amount: Amount| total.checked_add(amount).unwrap_or(Amount::from_micro_ccd(u64::MAX));
+        self.auctions_sold = self.auctions_sold.saturating_add(1);
+        self.volume_settled = saturating_add(self.volume_settled, price);
+        self.commission_paid = saturating_add(self.commission_paid, commission);
+    }
+}
+
+/// Basis points making up the whole of an amount.
+pub const TOTAL_BASIS_POINTS: u16 = 10_000;
+
+/// Where a share of the commission is paid. A contract sink, such as a
+/// treasury, is invoked at its entrypoint with the commission attached and
+/// the ID of the auction as the parameter.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CommissionSink {
+    Account(AccountAddress),
+    Contract {
+        address:    ContractAddress,
+        entrypoint: OwnedEntrypointName,
+    },
+}
+
+/// A recipient of a share of the commission.
+#[derive(Debug, Serialize, SchemaType, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommissionShare {
+    pub recipient: CommissionSink,
+    pub share_bps: u16, // Share of the commission in basis points
+}
+
+/// Ensures the shares are non-empty, non-zero and add up to the whole commission.
+fn validate_commission_shares(shares: &[CommissionShare]) -> Result<(), BidError> {
+    ensure!(!shares.is_empty(), BidError::InvalidCommissionShares);
+    ensure!(shares.iter().all(|share| share.share_bps > 0), BidError::InvalidCommissionShares);
+    let total: u32 = shares.iter().map(|share| u32::from(share.share_bps)).sum();
+    ensure_eq!(total, u32::from(TOTAL_BASIS_POINTS), BidError::InvalidCommissionShares);
+    Ok(())
+}
+
+/// Maximum number of commission tiers.
+pub const MAX_COMMISSION_TIERS: usize = 10;
+
+/// Ensures the tiers are non-empty, start at a threshold of zero so that
+/// every price has a rate, have strictly increasing thresholds and rates of
+/// at most the whole price.
+fn validate_commission_tiers(tiers: &[CommissionTier]) -> Result<(), BidError> {
+    ensure!(tiers.len() <= MAX_COMMISSION_TIERS, BidError::InvalidCommissionRate);
+    ensure!(tiers.first().is_some_and(|tier| tier.threshold == Amount::zero()), BidError::InvalidCommissionRate);
+    ensure!(tiers.windows(2).all(|pair| pair[0].threshold < pair[1].threshold), BidError::InvalidCommissionRate);
+    ensure!(tiers.iter().all(|tier| tier.bps <= TOTAL_BASIS_POINTS), BidError::InvalidCommissionRate);
+    Ok(())
+}
+
+/// The commission rate for a sale price: that of the tier with the highest
+/// threshold not above the price, applied to the whole price. A price just
+/// above a threshold can therefore pay less commission than one just below.
+fn commission_rate(tiers: &[CommissionTier], price: Amount) -> u16 {
+    tiers.iter().rev().find(|tier| tier.threshold <= price).map_or(0, |tier| tier.bps)
+}
+
+/// The commission on a sale price.
+fn commission_for(tiers: &[CommissionTier], price: Amount) -> Amount {
+    commission_of(price, commission_rate(tiers, price))
+}
+
+/// Splits `total` according to the shares. The rounding remainder goes to
+/// the first recipient so the payouts always add up to `total`.
+fn split_shares(total: u64, shares: &[CommissionShare]) -> Vec<(CommissionSink, u64)> {
+    let mut payouts: Vec<(CommissionSink, u64)> = shares
+        .iter()
+        .map(|share| {
+            let part = u128::from(total) * u128::from(share.share_bps) / u128::from(TOTAL_BASIS_POINTS);
+            (share.recipient.clone(), part as u64)
+        })
+        .collect();
+    let distributed: u64 = payouts.iter().map(|(_, payout)| *payout).sum();
+    if let Some((_, first)) = payouts.first_mut() {
+        *first += total - distributed;
+    }
+    payouts
+}
+
+/// Splits the commission according to the shares.
+fn split_commission(commission: Amount, shares: &[CommissionShare]) -> Vec<(CommissionSink, Amount)> {
+    split_shares(commission.micro_ccd, shares)
+        .into_iter()
+        .map(|(recipient, micro_ccd)| (recipient, Amount::from_micro_ccd(micro_ccd)))
+        .collect()
+}
+
+/// Splits a commission taken in tokens according to the shares.
+fn split_token_commission(commission: TokenAmountU64, shares: &[CommissionShare]) -> Vec<(CommissionSink, TokenAmountU64)> {
+    split_shares(commission.0, shares).into_iter().map(|(recipient, tokens)| (recipient, TokenAmountU64(tokens))).collect()
+}
+
+/// Type of the parameter to initialize the contract.
+#[derive(Serialize, SchemaType)]
+pub struct InitParameter {
+    /// Recipients of the commission, accounts or contract sinks, with shares
+    /// adding up to 10000 basis points.
+    pub commission_recipients: Vec<CommissionShare>,
+    /// Commission tiers, sorted by strictly increasing thresholds starting at
+    /// zero, with rates of at most 10000 basis points. The rate of the tier
+    /// with the highest threshold not above the sale price applies to the
+    /// whole price.
+    pub commission_tiers: Vec<CommissionTier>,
+    /// Minimum time between scheduling a change of the commission tiers and
+    /// the change becoming effective.
+    pub commission_change_delay: Duration,
+    /// If set, a bid arriving after the end of an auction that has not been
+    /// finalized yet settles the auction and refunds the late bidder, instead
+    /// of leaving the settlement to a separate `finalize` call.
+    pub auto_settle_late_bids: bool,
+    /// Time after the end of an auction after which, if it still has not been
+    /// finalized, the highest bidder and the owner can reclaim their bid and
+    /// tokens with `reclaim_expired`.
+    pub reclaim_grace_period: Duration,
+    /// Fee the owner pays to the highest bidder for cancelling an auction
+    /// with bids, in basis points of the highest bid. At most 10000.
+    pub cancellation_fee_bps: u16,
+    /// Time after the end of an auction requiring owner approval in which the
+    /// owner accepts or rejects the winning bid. Afterwards the auction can
+    /// be finalized by anyone, which settles it.
+    pub owner_approval_window: Duration,
+    /// Penalty a bidder pays to the commission recipients for retracting the
+    /// highest bid, in basis points of the bid. At most 10000.
+    pub retraction_penalty_bps: u16,
+    /// Time before the end of an auction from which the highest bid can no
+    /// longer be retracted.
+    pub retraction_cutoff: Duration,
+    /// Maximum number of auctions and listings an account can have that are
+    /// not settled, cancelled or closed yet.
+    pub max_active_auctions: u32,
+    /// Maximum time from the creation of an auction to its end. Auctions
+    /// ending later are rejected, so no auction is open practically forever.
+    pub max_auction_duration: Duration,
+    /// Time after the end of a reverse auction in which only its owner can
+    /// stop waiting for the lowest asker to deliver and have the budget
+    /// refunded with `finalize`. Afterwards anyone can.
+    pub delivery_window: Duration,
+}
+
+/// Type of the parameter to create a new auction.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewAuctionParameter {
+    pub item: String,
+    pub end: Timestamp,
+    pub initial_price: u64,
+    pub token_contract: ContractAddress, // CIS-2 token contract address
+    pub token_id: TokenIdU8,              // CIS-2 token ID
+    pub token_amount: TokenAmountU64,              // Amount of tokens
+    pub settlement_exclusivity: Option<Duration>,  // Time after `end` in which only the owner or admin may finalize
+    pub minimum_raise: Raise,                      // How much a bid must exceed the current highest bid by
+    pub requires_owner_approval: bool,             // The owner accepts or rejects the winning bid after `end`
+    pub finalize_reward: Amount,                   // Paid to whoever finalizes the auction if it has a winner, at most `MAX_FINALIZE_REWARD`
+    pub second_price: bool,                        // The winner pays the second highest bid
+    pub payout_token: Option<(ContractAddress, TokenIdU8)>, // Token the owner's share is swapped for, instead of CCD
+    pub accepts_backup_bids: bool,                 // Bids not beating the highest bid can stand as backup bids
+    pub alt_bid_token: Option<AltBidToken>,        // CIS-2 token accepted for bids besides CCD
+}
+
+/// Type of the parameter to list tokens for auction by transferring them to
+/// the contract, passed as the data of the transfer. The tokens and the owner
+/// of the auction are those of the transfer.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListingParameter {
+    pub item: String,
+    pub end: Timestamp,
+    pub initial_price: u64,
+    pub settlement_exclusivity: Option<Duration>,  // Time after `end` in which only the owner or admin may finalize
+    pub minimum_raise: Raise,                      // How much a bid must exceed the current highest bid by
+    pub requires_owner_approval: bool,             // The owner accepts or rejects the winning bid after `end`
+    pub finalize_reward: Amount,                   // Paid to whoever finalizes the auction if it has a winner, at most `MAX_FINALIZE_REWARD`
+    pub second_price: bool,                        // The winner pays the second highest bid
+    pub payout_token: Option<(ContractAddress, TokenIdU8)>, // Token the owner's share is swapped for, instead of CCD
+    pub accepts_backup_bids: bool,                 // Bids not beating the highest bid can stand as backup bids
+    pub alt_bid_token: Option<AltBidToken>,        // CIS-2 token accepted for bids besides CCD
+}
+
+/// Type of the data of CIS-2 transfers to the contract.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransferData {
+    /// List the transferred tokens for auction.
+    List(ListingParameter),
+    /// Deliver the transferred tokens for the lowest ask of the ended reverse
+    /// auction with the given ID.
+    DeliverAsk(u32),
+    /// Bid the transferred tokens on the auction with the given ID, which
+    /// accepts them as its alternative bid token.
+    Bid(u32),
+}
+
+/// Type of the parameter to create a reverse auction. The attached CCD are
+/// the budget.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReverseAuctionParameter {
+    pub item: String,
+    pub end: Timestamp,
+    pub token_contract: ContractAddress, // CIS-2 token contract address of the wanted tokens
+    pub token_id: TokenIdU8,             // CIS-2 token ID of the wanted tokens
+    pub token_amount: TokenAmountU64,    // Amount of wanted tokens
+}
+
+/// Type of the parameter to list tokens for a fixed price.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FixedPriceParameter {
+    pub item: String,
+    pub price: Amount,
+    pub token_contract: ContractAddress, // CIS-2 token contract address
+    pub token_id: TokenIdU8,             // CIS-2 token ID
+    pub token_amount: TokenAmountU64,    // Amount of tokens
+}
+
+/// Type of the parameter to make an offer. The attached CCD are the offer.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MakeOfferParameter {
+    pub listing_id: u32,       // ID of the listing or auction
+    pub expiry:     Timestamp, // Last time at which the owner can accept the offer
+}
+
+/// Type of the parameter to accept an offer.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AcceptOfferParameter {
+    pub listing_id: u32,
+    pub offer_id:   u64,
+}
+
+/// Type of the parameter to ask a price in a reverse auction.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AskParameter {
+    pub auction_id: u32,  // ID of the reverse auction
+    pub ask: Amount,      // Price for which the sender delivers the tokens
+}
+
+/// Type of the parameter to place a bid.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BidParameter {
+    pub auction_id: u32,  // ID of the auction to bid on
+    pub beneficiary: Option<AccountAddress>,  // Account to receive the tokens instead of the sender
+    pub data: AdditionalData,  // Opaque data logged with the bid but not stored, at most `MAX_BID_DATA_SIZE` bytes
+}
+
+/// Type of the parameter to place a bid funded from the sender's balance.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BalanceBidParameter {
+    pub bid: BidParameter,
+    pub amount: Amount,  // Taken from the balance for the bid, or added to the sender's highest bid
+}
+
+/// Type of the parameter of functions acting on a single auction.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuctionIdParameter {
+    pub auction_id: u32,  // ID of the auction
+}
+
+/// Type of the parameter to relist an unsold auction.
+#[derive(Serialize, SchemaType)]
+pub struct RelistParameter {
+    pub auction_id: u32,                // ID of the auction to relist
+    pub end: Timestamp,                 // End of the new auction
+    pub initial_price: Option<u64>,     // New initial price, or keep the old one
+}
+
+/// Type of the parameter to change the item description of an auction.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdateItemParameter {
+    pub auction_id: u32,                // ID of the auction to change
+    pub item: String,                   // New item description
+}
+
+/// Type of the parameter to rescue tokens not held for any auction.
+#[derive(Serialize, SchemaType)]
+pub struct RescueParameter {
+    pub token_contract: ContractAddress, // CIS-2 token contract address
+    pub token_id: TokenIdU8,             // CIS-2 token ID
+    pub amount: TokenAmountU64,          // Amount of tokens to rescue
+    pub to: Receiver,                    // Where to send the tokens
+}
+
+/// Errors of the contract entrypoints.
+#[derive(Debug, PartialEq, Eq, Clone, Reject, Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BidError {
+    OnlyAccount,
+    BidBelowCurrentBid,
+    BidBelowMinimumRaise,
+    BidTooLate,
+    AuctionAlreadyFinalized,
+    AuctionNotFound,
+    #[from(ParseError)]
+    ParameterParsingError,
+    AuctionStillActive,
+    #[from(TransferError)]
+    TransferFailed,
+    OnlyNotOwner,
+    OnlyOwner,
+    AuctionHasBids,
+    FinalizeNotYetPermissionless,
+    NothingToClaim,
+    TooManyAuctionIds,
+    OnlyAdmin,
+    InvalidCommissionShares,
+    ZeroTopUp,
+    RescueExceedsUnescrowed,
+    GracePeriodNotOver,
+    BidDataTooLarge,
+    NotPendingDelivery,
+    AuctionEnded,
+    WrongCancellationFee,
+    InvalidCancellationFee,
+    AwaitingOwnerApproval,
+    OwnerApprovalNotRequired,
+    ApprovalWindowOver,
+    InvalidFinalizeReward,
+    InvalidItemDescription,
+    InvalidCommissionRate,
+    CommissionChangeTooSoon,
+    NoPendingCommissionChange,
+    CommissionChangeNotDue,
+    WrongAuctionKind,
+    ZeroBudget,
+    AskAboveBudget,
+    AskNotBelowCurrentAsk,
+    AwaitingDelivery,
+    OnlyLowestAsker,
+    WrongTokens,
+    TokenContractIsSelf,
+    EscrowNotReceived,
+    InvalidRetractionPenalty,
+    RetractionTooLate,
+    OnlyHighestBidder,
+    EscrowNotConfirmed,
+    TokenContractNotCis2,
+    FixedPriceListing,
+    PaymentBelowPrice,
+    ZeroOffer,
+    OfferNotFound,
+    OnlyOfferer,
+    OfferExpired,
+    OfferStillValid,
+    TooManyActiveAuctions,
+    SwapFailed,
+    /// The owner holds fewer tokens than the auction is for.
+    InsufficientTokenBalance {
+        balance:  TokenAmountU64,
+        required: TokenAmountU64,
+    },
+    /// The owner has not made the contract an operator of their tokens.
+    MissingOperatorApproval,
+    TooManyAuctionsToView,
+    /// The bid equals the current highest bid, or the current backup bid,
+    /// which was placed earlier.
+    BidTiesCurrentBid,
+    NotBackupBidder,
+    /// The balance of the sender is below the amount to bid or withdraw.
+    InsufficientBalance,
+    /// An event could not be logged, as the log is full or the event is too
+    /// large.
+    #[from(LogError)]
+    LogFailed,
+    /// The tokens transferred as a bid are not the alternative bid token of
+    /// the auction.
+    WrongBidToken,
+    /// The highest bidder topped up their bid in another asset than the bid
+    /// was placed in.
+    BidAssetMismatch,
+    /// The alternative bid token has a zero ratio, or the auction takes
+    /// second price or backup bids, which bids in the token do not support.
+    InvalidAltBidToken,
+    /// The value of the bid in the alternative bid token does not fit into
+    /// an amount of CCD.
+    BidValueOverflow,
+    /// Bids in the alternative bid token cannot be retracted.
+    TokenBidNotRetractable,
+    /// The auction ends further after its creation than the maximum auction
+    /// duration allows.
+    AuctionTooLong,
+    /// A total of amounts, token amounts or times does not fit into its type.
+    ArithmeticOverflow,
+    /// The receive hook for CIS-2 tokens was called by an account instead of
+    /// a token contract.
+    OnlyContract,
+    /// More tokens of an auction were counted as sold than were still
+    /// escrowed for it.
+    EscrowInvariantViolated,
+    /// An account called `claim_proceeds`. Proceeds and commission owed to
+    /// accounts are claimed with `claim_refund`.
+    UseClaimRefund,
+}
+
+/// `create_auction` function to add a new auction to the state. Returns the
+/// ID of the new auction. The owner can be an account or a contract, which
+/// claims its proceeds with `claim_proceeds`.
+#[receive(contract = "auction", name = "create_auction", parameter = "NewAuctionParameter", return_value = "u32", enable_logger, mutable, error = "BidError")]
+pub fn create_auction(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<u32, BidError> {
+    let parameter: NewAuctionParameter = ctx.parameter_cursor().get()?;
+
+    let owner = ctx.sender();
+    escrow_tokens(ctx, host, owner, parameter.token_contract, parameter.token_id, parameter.token_amount)?;
+    register_auction(host, logger, owner, parameter, ctx.metadata().slot_time())
+}
+
+/// Transfers the tokens of a new auction from `owner` to the contract. Fails
+/// with `EscrowNotReceived` unless `onReceivingCIS2` saw the tokens arrive
+/// from the owner, so that a token contract reporting success without calling
+/// it rolls the transfer back instead of leaving the tokens with the contract.
+fn escrow_tokens(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    owner: Address,
+    token_contract: ContractAddress,
+    token_id: TokenIdU8,
+    token_amount: TokenAmountU64,
+) -> Result<(), BidError> {
+    ensure!(token_contract != ctx.self_address(), BidError::TokenContractIsSelf);
+    ensure_supports_cis2(host, token_contract)?;
+    check_escrow_preflight(ctx, host, owner, token_contract, token_id, token_amount)?;
+
+    // Transfer CIS-2 tokens from the auction creator to the contract
+    let transfer = Transfer {
+        token_id,
+        amount: token_amount,
+        from: owner,
+        to: Receiver::from_contract(ctx.self_address(), ON_RECEIVING_CIS2.to_owned()),
+        data: AdditionalData::empty(),
+    };
+
+    // Let `onReceivingCIS2` accept the transfer without listing data
+    host.state_mut().awaiting_escrow = Some(token_contract);
+    let client = Cis2Client::new(token_contract);
+    let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    host.state_mut().awaiting_escrow = None;
+    ensure!(result.is_ok(), BidError::EscrowNotReceived);
+
+    let received = host.state_mut().take_escrow_receipt(token_contract, token_id, owner, token_amount);
+    ensure!(received, BidError::EscrowNotReceived);
+    Ok(())
+}
+
+/// Function to handle receiving CIS-2 tokens. The escrow transfer made by
+/// `create_auction` is accepted and recorded as received from the owner, for
+/// `create_auction` to commit to the new auction; any other transfer must carry
+/// `TransferData` saying what the tokens are for: listing them for auction by
+/// the sender of the tokens, delivering them for the lowest ask of a reverse
+/// auction, or bidding them on an auction accepting them. Transfers without
+/// valid data are rejected, so the tokens are not kept.
+#[receive(contract = "auction", name = "onReceivingCIS2", enable_logger, mutable, error = "BidError")]
+pub fn on_receiving_cis2(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    // Get information about received tokens
+    let params: OnReceivingCis2Params<ContractTokenId, ContractTokenAmount> =
+        ctx.parameter_cursor().get()?;
+
+    // Get the token contract that sent the tokens
+    let token_contract = match ctx.sender() {
+        Address::Contract(contract) => contract,
+        Address::Account(_) => bail!(BidError::OnlyContract),
+    };
+
+    if params.data.as_ref().is_empty() && host.state().awaiting_escrow == Some(token_contract) {
+        return host.state_mut().credit_escrow_receipt(token_contract, params.token_id, params.from, params.amount);
+    }
+
+    let data: TransferData = from_bytes(params.data.as_ref())?;
+    let listing = match data {
+        TransferData::List(listing) => listing,
+        TransferData::DeliverAsk(auction_id) => return deliver_ask(ctx, host, logger, auction_id, token_contract, params),
+        TransferData::Bid(auction_id) => return place_token_bid(ctx, host, logger, auction_id, token_contract, params),
+    };
+    let owner = params.from;
+
+    let parameter = NewAuctionParameter {
+        item: listing.item,
+        end: listing.end,
+        initial_price: listing.initial_price,
+        token_contract,
+        token_id: params.token_id,
+        token_amount: params.amount,
+        settlement_exclusivity: listing.settlement_exclusivity,
+        minimum_raise: listing.minimum_raise,
+        requires_owner_approval: listing.requires_owner_approval,
+        finalize_reward: listing.finalize_reward,
+        second_price: listing.second_price,
+        payout_token: listing.payout_token,
+        accepts_backup_bids: listing.accepts_backup_bids,
+        alt_bid_token: listing.alt_bid_token,
+    };
+    register_auction(host, logger, owner, parameter, ctx.metadata().slot_time())?;
+    Ok(())
+}
+
+/// Ensures the token contract reports supporting CIS-2 itself through its
+/// CIS-0 `supports` entrypoint, unless the admin exempted it. Support by
+/// other contracts is not enough, as the tokens are transferred on the
+/// contract itself.
+fn ensure_supports_cis2(host: &Host<State>, token_contract: ContractAddress) -> Result<(), BidError> {
+    if host.state().cis0_exempt.contains(&token_contract) {
+        return Ok(());
+    }
+    let client = Cis2Client::new(token_contract);
+    let result: Result<SupportResult, Cis2ClientError<()>> = client.supports_cis2(host);
+    ensure!(matches!(result, Ok(SupportResult::Support)), BidError::TokenContractNotCis2);
+    Ok(())
+}
+
+/// Checks that the owner holds the tokens and has made the contract an
+/// operator, so that a failing escrow transfer is reported with the reason.
+/// The check is advisory: queries the token contract cannot answer are
+/// skipped, and the escrow transfer is still checked, as the balance can
+/// change before it is made.
+fn check_escrow_preflight(
+    ctx: &impl HasReceiveContext,
+    host: &Host<State>,
+    owner: Address,
+    token_contract: ContractAddress,
+    token_id: TokenIdU8,
+    token_amount: TokenAmountU64,
+) -> Result<(), BidError> {
+    let client = Cis2Client::new(token_contract);
+    let balance: Result<TokenAmountU64, Cis2ClientError<()>> = client.balance_of(host, token_id, owner);
+    if let Ok(balance) = balance {
+        ensure!(balance >= token_amount, BidError::InsufficientTokenBalance {
+            balance,
+            required: token_amount,
+        });
+    }
+    let is_operator: Result<bool, Cis2ClientError<()>> =
+        client.operator_of(host, owner, Address::Contract(ctx.self_address()));
+    ensure!(!matches!(is_operator, Ok(false)), BidError::MissingOperatorApproval);
+    Ok(())
+}
+
+/// Ensures an item description is short enough to be kept in the state. It
+/// is valid UTF-8 already, as parameters with invalid strings do not parse.
+fn validate_item(item: &str) -> Result<(), BidError> {
+    ensure!(item.len() <= MAX_ITEM_LENGTH, BidError::InvalidItemDescription);
+    Ok(())
+}
+
+/// Ensures an auction created at `now` ends at most `max_duration` later, and
+/// that its settlement exclusivity window after the end does not overflow a
+/// timestamp, which would keep the auction exclusive to its owner forever.
+pub fn validate_end(
+    end: Timestamp,
+    settlement_exclusivity: Option<Duration>,
+    now: Timestamp,
+    max_duration: Duration,
+) -> Result<(), BidError> {
+    // A maximum reaching past the last timestamp does not limit the end
+    ensure!(now.checked_add(max_duration).is_none_or(|latest| end <= latest), BidError::AuctionTooLong);
+    if let Some(window) = settlement_exclusivity {
+        ensure!(end.checked_add(window).is_some(), BidError::ArithmeticOverflow);
+    }
+    Ok(())
+}
+
+/// Settles an ended reverse auction whose lowest asker delivered the wanted
+/// tokens: the tokens are forwarded to the owner, the ask is paid to the
+/// asker minus the commission and the rest of the budget is refunded to the
+/// owner.
+fn deliver_ask(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    token_contract: ContractAddress,
+    params: OnReceivingCis2Params<ContractTokenId, ContractTokenAmount>,
+) -> Result<(), BidError> {
+    let auction = host.state().auction(auction_id)?;
+    let budget = match auction.kind {
+        AuctionKind::Reverse { budget } => budget,
+        _ => bail!(BidError::WrongAuctionKind),
+    };
+
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    ensure!(auction_phase(&auction, ctx.metadata().slot_time()) == Phase::Ended, BidError::AuctionStillActive);
+    let asker = match auction.highest_bidder {
+        Some(asker) if params.from.matches_account(&asker) => asker,
+        _ => bail!(BidError::OnlyLowestAsker),
+    };
+    ensure!(
+        token_contract == auction.token_contract
+            && params.token_id == auction.token_id
+            && params.amount == auction.token_amount,
+        BidError::WrongTokens
+    );
+
+    let ask = auction.highest_bid;
+    let commission_amount = commission_for(&auction.commission_tiers, ask);
+    let commission_payouts = split_commission(commission_amount, &host.state().commission_recipients);
+
+    let state = host.state_mut();
+    state.set_auction_state(auction_id, AuctionState::Sold {
+        winner: asker,
+        price: ask,
+        settled_at: ctx.metadata().slot_time(),
+    });
+    state.reverse_budgets_total -= budget;
+    state.stats.record_sale(ask, commission_amount);
+
+    // Forward the delivered tokens to the owner
+    let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+        token_id: auction.token_id,
+        amount: auction.token_amount,
+        from: Address::Contract(ctx.self_address()),
+        to: owner_receiver(auction.owner),
+        data: AdditionalData::empty(),
+    };
+    let client = Cis2Client::new(auction.token_contract);
+    let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    result.map_err(|_| BidError::TransferFailed)?;
+
+    let commission_payouts = pay_commission(host, logger, auction_id, commission_payouts)?;
+    pay_or_defer(host, logger, auction_id, asker, ask - commission_amount, PayoutKind::Proceeds)?;
+    if budget > ask {
+        pay_owner(host, logger, auction_id, auction.owner, budget - ask, PayoutKind::Budget)?;
+    }
+    let _ = host.state_mut().settlements.insert(auction_id, SettlementRecord {
+        finalizer: Address::Account(asker),
+        settled_at: ctx.metadata().slot_time(),
+        seller_amount: ask - commission_amount,
+        commission: commission_amount,
+        finalize_reward: Amount::zero(),
+        tokens_delivered: true,
+    });
+
+    logger
+        .log(&AuctionEvent::Finalize(FinalizeEventData {
+            auction_id,
+            finalizer: Address::Account(asker),
+            commission_payouts,
+            finalize_reward: Amount::zero(),
+            price: ask,
+            winner: Some(asker),
+            settled_at: ctx.metadata().slot_time(),
+        }))?;
+    Ok(())
+}
+
+/// `create_reverse_auction` function to start a reverse auction, in which the
+/// sender buys the wanted tokens from whoever asks the lowest price. The
+/// attached CCD are held as the budget, which is the highest price that can
+/// be asked. Returns the ID of the new auction.
+#[receive(
+    contract = "auction",
+    name = "create_reverse_auction",
+    parameter = "ReverseAuctionParameter",
+    return_value = "u32",
+    payable,
+    enable_logger,
+    mutable,
+    error = "BidError"
+)]
+pub fn create_reverse_auction(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut impl HasLogger,
+) -> Result<u32, BidError> {
+    let parameter: ReverseAuctionParameter = ctx.parameter_cursor().get()?;
+
+    let owner = ctx.sender();
+    ensure!(amount > Amount::zero(), BidError::ZeroBudget);
+    ensure!(parameter.token_contract != ctx.self_address(), BidError::TokenContractIsSelf);
+
+    let auction = Auction {
+        auction_state: AuctionState::NotSoldYet,
+        highest_bidder: None,
+        beneficiary: None,
+        initial_price: 0,
+        highest_bid: Amount::zero(),
+        item: parameter.item,
+        end: parameter.end,
+        owner,
+        token_contract: parameter.token_contract,
+        token_id: parameter.token_id,
+        token_amount: parameter.token_amount,
+        settlement_exclusivity: None,
+        minimum_raise: Raise::Flat(Amount::zero()),
+        requires_owner_approval: false,
+        finalize_reward: Amount::zero(),
+        commission_tiers: host.state().commission_tiers.clone(),
+        kind: AuctionKind::Reverse {
+            budget: amount,
+        },
+        escrow_confirmed: true,
+        bid_count: 0,
+        unique_bidders: 0,
+        payout_token: None,
+        accepts_backup_bids: false,
+        alt_bid_token: None,
+        token_bid: None,
+        amount_sold: TokenAmountU64(0),
+        amount_remaining: TokenAmountU64(0),
+    };
+    push_auction(host, logger, auction, ctx.metadata().slot_time())
+}
+
+/// `create_listing` function to list tokens for a fixed price. The tokens are
+/// escrowed like for an auction, and the listing never ends: it stays open
+/// until it is bought with `purchase` or cancelled by the owner with
+/// `cancel_with_fee`, which is free as listings have no bids. Returns the ID
+/// of the listing, which shares the IDs of auctions.
+#[receive(
+    contract = "auction",
+    name = "create_listing",
+    parameter = "FixedPriceParameter",
+    return_value = "u32",
+    enable_logger,
+    mutable,
+    error = "BidError"
+)]
+pub fn create_listing(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<u32, BidError> {
+    let parameter: FixedPriceParameter = ctx.parameter_cursor().get()?;
+
+    let owner = ctx.sender();
+    escrow_tokens(ctx, host, owner, parameter.token_contract, parameter.token_id, parameter.token_amount)?;
+
+    let auction = Auction {
+        auction_state: AuctionState::NotSoldYet,
+        highest_bidder: None,
+        beneficiary: None,
+        initial_price: parameter.price.micro_ccd,
+        highest_bid: Amount::zero(),
+        item: parameter.item,
+        end: Timestamp::from_timestamp_millis(u64::MAX),
+        owner,
+        token_contract: parameter.token_contract,
+        token_id: parameter.token_id,
+        token_amount: parameter.token_amount,
+        settlement_exclusivity: None,
+        minimum_raise: Raise::Flat(Amount::zero()),
+        requires_owner_approval: false,
+        finalize_reward: Amount::zero(),
+        commission_tiers: host.state().commission_tiers.clone(),
+        kind: AuctionKind::FixedPrice {
+            price: parameter.price,
+        },
+        escrow_confirmed: true,
+        bid_count: 0,
+        unique_bidders: 0,
+        payout_token: None,
+        accepts_backup_bids: false,
+        alt_bid_token: None,
+        token_bid: None,
+        amount_sold: TokenAmountU64(0),
+        amount_remaining: TokenAmountU64(0),
+    };
+    push_auction(host, logger, auction, ctx.metadata().slot_time())
+}
+
+/// `update_auction_item` function to let the owner correct the item
+/// description of an auction until the first bid is placed. The new
+/// description is validated like on creation.
+#[receive(
+    contract = "auction",
+    name = "update_auction_item",
+    parameter = "UpdateItemParameter",
+    enable_logger,
+    crypto_primitives,
+    mutable,
+    error = "BidError"
+)]
+pub fn update_auction_item(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    crypto_primitives: &impl HasCryptoPrimitives,
+) -> Result<(), BidError> {
+    let parameter: UpdateItemParameter = ctx.parameter_cursor().get()?;
+    let mut auction = host.state().auction(parameter.auction_id)?;
+
+    ensure!(ctx.sender() == auction.owner, BidError::OnlyOwner);
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    ensure!(auction.highest_bidder.is_none(), BidError::AuctionHasBids);
+    validate_item(&parameter.item)?;
+
+    let item_hash = crypto_primitives.hash_sha2_256(parameter.item.as_bytes()).0;
+    auction.item = parameter.item;
+    host.state_mut().store_auction(parameter.auction_id, auction);
+    logger
+        .log(&AuctionEvent::ItemUpdated(ItemUpdatedEventData {
+            auction_id: parameter.auction_id,
+            item_hash,
+        }))
+        .map_err(BidError::from)
+}
+
+/// `purchase` function to buy the tokens of a fixed-price listing. At least
+/// the price must be attached, and any overpayment is refunded. The listing
+/// is settled right away like an auction won by the buyer: the tokens go to
+/// the buyer and the owner is paid the price minus the commission.
+#[receive(contract = "auction", name = "purchase", parameter = "AuctionIdParameter", payable, enable_logger, mutable, error = "BidError")]
+pub fn purchase(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let buyer = match ctx.sender() {
+        Address::Account(account_address) => account_address,
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+    };
+
+    let state = host.state_mut();
+    let mut auction = state.auction(parameter.auction_id)?;
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    let price = match auction.kind {
+        AuctionKind::FixedPrice { price } => price,
+        _ => bail!(BidError::WrongAuctionKind),
+    };
+    ensure!(auction.escrow_confirmed, BidError::EscrowNotConfirmed);
+    ensure!(!auction.owner.matches_account(&buyer), BidError::OnlyNotOwner);
+    ensure!(amount >= price, BidError::PaymentBelowPrice);
+
+    // Treat the payment as the winning bid, so the overpayment is refunded
+    auction.highest_bidder = Some(buyer);
+    auction.highest_bid = amount;
+    state.store_auction(parameter.auction_id, auction);
+    state.standing_bids_total += amount;
+    settle_auction(ctx, host, logger, parameter.auction_id)
+}
+
+/// `make_offer` function to make a binding offer for the tokens of a
+/// fixed-price listing or of a standard auction without bids, which the
+/// owner can accept until the expiry. The attached CCD are held until the
+/// offer is accepted, cancelled or expired. Returns the ID of the offer.
+#[receive(
+    contract = "auction",
+    name = "make_offer",
+    parameter = "MakeOfferParameter",
+    return_value = "u64",
+    payable,
+    enable_logger,
+    mutable,
+    error = "BidError"
+)]
+pub fn make_offer(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut impl HasLogger,
+) -> Result<u64, BidError> {
+    let parameter: MakeOfferParameter = ctx.parameter_cursor().get()?;
+    let offerer = match ctx.sender() {
+        Address::Account(account_address) => account_address,
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+    };
+    let auction = host.state().auction(parameter.listing_id)?;
+    ensure_offerable(&auction)?;
+    ensure!(!auction.owner.matches_account(&offerer), BidError::OnlyNotOwner);
+    ensure!(amount > Amount::zero(), BidError::ZeroOffer);
+    ensure!(parameter.expiry >= ctx.metadata().slot_time(), BidError::OfferExpired);
+
+    let offer = Offer {
+        listing_id: parameter.listing_id,
+        offerer,
+        amount,
+        expiry: parameter.expiry,
+    };
+    let (state, state_builder) = host.state_and_builder();
+    let offer_id = state.add_offer(state_builder, offer)?;
+    log_offer(logger, offer_id, parameter.listing_id, offerer, amount, AuctionEvent::OfferMade)?;
+    Ok(offer_id)
+}
+
+/// `cancel_offer` function to let the offerer withdraw an open offer.
+#[receive(contract = "auction", name = "cancel_offer", parameter = "u64", enable_logger, mutable, error = "BidError")]
+pub fn cancel_offer(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let offer_id: u64 = ctx.parameter_cursor().get()?;
+    let offer = host.state().offers.get(&offer_id).ok_or(BidError::OfferNotFound)?.clone();
+    ensure!(ctx.sender().matches_account(&offer.offerer), BidError::OnlyOfferer);
+
+    refund_offer(host, logger, offer_id, AuctionEvent::OfferCancelled)
+}
+
+/// `expire_offer` function to let anyone refund an offer that can no longer
+/// be accepted, because it expired or its item is no longer on sale.
+#[receive(contract = "auction", name = "expire_offer", parameter = "u64", enable_logger, mutable, error = "BidError")]
+pub fn expire_offer(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let offer_id: u64 = ctx.parameter_cursor().get()?;
+    let offer = host.state().offers.get(&offer_id).ok_or(BidError::OfferNotFound)?.clone();
+    let expired = ctx.metadata().slot_time() > offer.expiry;
+    let auction = host.state().auction(offer.listing_id);
+    let off_sale = auction.map_or(true, |auction| ensure_offerable(&auction).is_err());
+    ensure!(expired || off_sale, BidError::OfferStillValid);
+
+    refund_offer(host, logger, offer_id, AuctionEvent::OfferExpired)
+}
+
+/// `accept_offer` function to let the owner sell the tokens to a valid
+/// offer. The sale is settled like an auction won with the offer, and all
+/// other open offers for the item are refunded.
+#[receive(contract = "auction", name = "accept_offer", parameter = "AcceptOfferParameter", enable_logger, mutable, error = "BidError")]
+pub fn accept_offer(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AcceptOfferParameter = ctx.parameter_cursor().get()?;
+    let state = host.state_mut();
+    let offer = state.offers.get(&parameter.offer_id).ok_or(BidError::OfferNotFound)?.clone();
+    ensure_eq!(offer.listing_id, parameter.listing_id, BidError::OfferNotFound);
+    ensure!(ctx.metadata().slot_time() <= offer.expiry, BidError::OfferExpired);
+    let mut auction = state.auction(parameter.listing_id)?;
+    ensure!(ctx.sender() == auction.owner, BidError::OnlyOwner);
+    ensure_offerable(&auction)?;
+
+    // The offer becomes the winning bid, and the price of a listing
+    auction.highest_bidder = Some(offer.offerer);
+    auction.highest_bid = offer.amount;
+    if let AuctionKind::FixedPrice { price } = &mut auction.kind {
+        *price = offer.amount;
+    }
+    state.store_auction(parameter.listing_id, auction);
+    state.remove_offer(parameter.offer_id);
+    state.standing_bids_total += offer.amount;
+    log_offer(logger, parameter.offer_id, parameter.listing_id, offer.offerer, offer.amount, AuctionEvent::OfferAccepted)?;
+
+    for offer_id in host.state().offer_ids(parameter.listing_id) {
+        refund_offer(host, logger, offer_id, AuctionEvent::OfferCancelled)?;
+    }
+    settle_auction(ctx, host, logger, parameter.listing_id)
+}
+
+/// View function to return the open offers of a listing or auction.
+#[receive(contract = "auction", name = "view_offers", parameter = "AuctionIdParameter", return_value = "Vec<(u64, Offer)>", error = "BidError")]
+pub fn view_offers(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<Vec<(u64, Offer)>, BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    let offers = state
+        .offer_ids(parameter.auction_id)
+        .into_iter()
+        .filter_map(|offer_id| state.offers.get(&offer_id).map(|offer| (offer_id, offer.clone())))
+        .collect();
+    Ok(offers)
+}
+
+/// Ensures offers can be made for and accepted on the auction: it is a
+/// fixed-price listing or a standard auction without bids, whose tokens the
+/// contract holds and which is not settled yet.
+fn ensure_offerable(auction: &Auction) -> Result<(), BidError> {
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    ensure!(matches!(auction.kind, AuctionKind::Standard | AuctionKind::FixedPrice { .. }), BidError::WrongAuctionKind);
+    ensure!(auction.highest_bidder.is_none(), BidError::AuctionHasBids);
+    ensure!(auction.escrow_confirmed, BidError::EscrowNotConfirmed);
+    Ok(())
+}
+
+/// Removes an open offer, refunds it, or keeps it claimable if the transfer
+/// fails, and logs `event`.
+fn refund_offer(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    offer_id: u64,
+    event: fn(OfferEventData) -> AuctionEvent,
+) -> Result<(), BidError> {
+    let offer = host.state_mut().remove_offer(offer_id).ok_or(BidError::OfferNotFound)?;
+    if host.invoke_transfer(&offer.offerer, offer.amount).is_err() {
+        host.state_mut().credit_refund(offer.offerer, offer.amount);
+    }
+    log_offer(logger, offer_id, offer.listing_id, offer.offerer, offer.amount, event)
+}
+
+fn log_offer(
+    logger: &mut impl HasLogger,
+    offer_id: u64,
+    listing_id: u32,
+    offerer: AccountAddress,
+    amount: Amount,
+    event: fn(OfferEventData) -> AuctionEvent,
+) -> Result<(), BidError> {
+    logger
+        .log(&event(OfferEventData {
+            offer_id,
+            listing_id,
+            offerer,
+            amount,
+        }))
+        .map_err(BidError::from)
+}
+
+/// `submit_ask` function to offer the wanted tokens of a reverse auction for
+/// a price below the current lowest ask and at most the budget. No CCD are
+/// attached; the lowest asker delivers the tokens after the end.
+#[receive(contract = "auction", name = "submit_ask", parameter = "AskParameter", enable_logger, mutable, error = "BidError")]
+pub fn submit_ask(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AskParameter = ctx.parameter_cursor().get()?;
+    let asker = match ctx.sender() {
+        Address::Account(account_address) => account_address,
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+    };
+
+    let slot_time = ctx.metadata().slot_time();
+    let mut auction = host.state().auction(parameter.auction_id)?;
+    let budget = match auction.kind {
+        AuctionKind::Reverse { budget } => budget,
+        _ => bail!(BidError::WrongAuctionKind),
+    };
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    ensure!(auction_phase(&auction, slot_time) == Phase::Bidding, BidError::BidTooLate);
+    ensure!(!auction.owner.matches_account(&asker), BidError::OnlyNotOwner);
+    ensure!(parameter.ask <= budget, BidError::AskAboveBudget);
+    if auction.highest_bidder.is_some() {
+        ensure!(parameter.ask < auction.highest_bid, BidError::AskNotBelowCurrentAsk);
+    }
+
+    auction.highest_bidder = Some(asker);
+    auction.highest_bid = parameter.ask;
+    host.state_mut().store_auction(parameter.auction_id, auction);
+
+    logger
+        .log(&AuctionEvent::Ask(AskEventData {
+            auction_id: parameter.auction_id,
+            asker,
+            ask: parameter.ask,
+        }))?;
+    Ok(())
+}
+
+/// Registers a new auction of `owner` for tokens sent to the contract at
+/// `slot_time` and logs the `Register` event. Returns the ID of the new
+/// auction.
+fn register_auction(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    owner: Address,
+    parameter: NewAuctionParameter,
+    slot_time: Timestamp,
+) -> Result<u32, BidError> {
+    let auction = Auction {
+        auction_state: AuctionState::NotSoldYet,
+        highest_bidder: None,
+        beneficiary: None,
+        initial_price: parameter.initial_price,
+        highest_bid: Amount::zero(),
+        item: parameter.item,
+        end: parameter.end,
+        owner,
+        token_contract: parameter.token_contract,
+        token_id: parameter.token_id,
+        token_amount: parameter.token_amount,
+        settlement_exclusivity: parameter.settlement_exclusivity,
+        minimum_raise: parameter.minimum_raise,
+        requires_owner_approval: parameter.requires_owner_approval,
+        finalize_reward: parameter.finalize_reward,
+        commission_tiers: host.state().commission_tiers.clone(),
+        kind: match parameter.second_price {
+            true => AuctionKind::SecondPrice {
+                second_bid: Amount::zero(),
+            },
+            false => AuctionKind::Standard,
+        },
+        escrow_confirmed: true,
+        bid_count: 0,
+        unique_bidders: 0,
+        payout_token: parameter.payout_token,
+        accepts_backup_bids: parameter.accepts_backup_bids,
+        alt_bid_token: parameter.alt_bid_token,
+        token_bid: None,
+        amount_sold: TokenAmountU64(0),
+        amount_remaining: TokenAmountU64(0),
+    };
+    // Bids in the alternative bid token always win at the full bid, and do
+    // not stand as backup bids
+    if let Some(alt_bid_token) = parameter.alt_bid_token {
+        ensure!(
+            alt_bid_token.ratio_numerator > 0
+                && alt_bid_token.ratio_denominator > 0
+                && !parameter.second_price
+                && !parameter.accepts_backup_bids,
+            BidError::InvalidAltBidToken
+        );
+    }
+    push_auction(host, logger, auction, slot_time)
+}
+
+/// Validates a new auction created at `slot_time`, adds it to the state
+/// together with the tokens or the budget it holds, and logs the `Register`
+/// event. Returns the ID of the new auction.
+fn push_auction(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    mut auction: Auction,
+    slot_time: Timestamp,
+) -> Result<u32, BidError> {
+    validate_item(&auction.item)?;
+    ensure!(auction.finalize_reward <= MAX_FINALIZE_REWARD, BidError::InvalidFinalizeReward);
+    // Fixed-price listings never end
+    if !auction.kind.is_fixed_price() {
+        validate_end(auction.end, auction.settlement_exclusivity, slot_time, host.state().max_auction_duration)?;
+    }
+
+    // Add the new auction to the state
+    let (state, state_builder) = host.state_and_builder();
+    let active = state.active_auction_count(&auction.owner);
+    ensure!(active < state.max_active_auctions, BidError::TooManyActiveAuctions);
+    let _ = state.active_auctions.insert(auction.owner, active + 1);
+    match auction.kind {
+        AuctionKind::Reverse { budget } => state.reverse_budgets_total += budget,
+        _ if auction.escrow_confirmed => {
+            state.add_escrow(auction.token_contract, auction.token_id, auction.token_amount)?;
+            auction.amount_remaining = auction.token_amount;
+        }
+        _ => {}
+    }
+    let listed = auction.clone();
+    let id = state.add_auction(auction)?;
+    state.index_auction(state_builder, listed.token_contract, id);
+    state.stats.auctions_created = state.stats.auctions_created.saturating_add(1);
+
+    // Return the ID of the newly created auction
+    logger.log(&AuctionEvent::Register(AuctionEventData { auction_id: id }))?;
+    log_item_event(logger, AuctionEvent::ItemListed, id, &listed, listing_price(&listed), listed.owner)?;
+    Ok(id)
+}
+
+/// `bid` function to place a bid on a specific auction.
+#[receive(contract = "auction", name = "bid", parameter = "BidParameter", payable, mutable, enable_logger, error = "BidError")]
+pub fn auction_bid(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,  // Use &mut Host<State> for state-modifying functions
+    amount: Amount,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: BidParameter = ctx.parameter_cursor().get()?;
+    place_bid(ctx, host, logger, parameter, amount, false)
+}
+
+/// `bid_from_balance` function to place a bid funded from the sender's
+/// balance instead of attached CCD. As for `bid`, a top-up adds the amount to
+/// the sender's highest bid. When a bid funded from the balance, even in
+/// part, is outbid, it is credited back to the balance instead of being
+/// transferred; other refunds are transferred like for any bid.
+#[receive(
+    contract = "auction",
+    name = "bid_from_balance",
+    parameter = "BalanceBidParameter",
+    mutable,
+    enable_logger,
+    error = "BidError"
+)]
+pub fn bid_from_balance(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: BalanceBidParameter = ctx.parameter_cursor().get()?;
+    let bidder = match ctx.sender() {
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+        Address::Account(account_address) => account_address,
+    };
+    debit_balance(host, logger, bidder, parameter.amount)?;
+    place_bid(ctx, host, logger, parameter.bid, parameter.amount, true)
+}
+
+/// How a valid bid is placed, as decided by `validate_bid`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BidPlacement {
+    /// The bid becomes the highest bid. For a top-up of the highest bidder,
+    /// `bid_total` includes the bid the contract already holds.
+    Highest {
+        bid_total: Amount,
+        is_top_up: bool,
+    },
+    /// The bid does not beat the highest bid, and stands as the backup bid of
+    /// the auction instead.
+    Backup,
+}
+
+/// Checks a bid of `amount` by `sender` for `beneficiary` on `auction` at
+/// time `now`, given the current backup bid of the auction, without touching
+/// the state. Returns how the bid is placed, or why it is rejected.
+pub fn validate_bid(
+    auction: &Auction,
+    now: Timestamp,
+    sender: AccountAddress,
+    beneficiary: AccountAddress,
+    amount: Amount,
+    backup: Option<&BackupBid>,
+) -> Result<BidPlacement, BidError> {
+    // Ensure the auction has not been finalized yet
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    // Reverse auctions take asks instead of bids
+    ensure!(!auction.kind.is_reverse(), BidError::WrongAuctionKind);
+    // Fixed-price listings are bought instead
+    ensure!(!auction.kind.is_fixed_price(), BidError::FixedPriceListing);
+    ensure!(auction.escrow_confirmed, BidError::EscrowNotConfirmed);
+    // Ensure the auction has not ended yet
+    ensure!(auction_phase(auction, now) == Phase::Bidding, BidError::BidTooLate);
+
+    // The owner can neither bid nor have the tokens bid for
+    ensure!(
+        !auction.owner.matches_account(&sender) && !auction.owner.matches_account(&beneficiary),
+        BidError::OnlyNotOwner
+    );
+
+    // The current highest bidder tops up their bid: the amount is added to
+    // the bid the contract already holds instead of replacing it.
+    let is_top_up = auction.highest_bidder == Some(sender);
+    let bid_total = if is_top_up {
+        ensure!(amount > Amount::zero(), BidError::ZeroTopUp);
+        auction.highest_bid.checked_add(amount).ok_or(BidError::ArithmeticOverflow)?
+    } else {
+        amount
+    };
+
+    // Check if the current highest bid is zero
+    if auction.highest_bid == Amount::zero() {
+        // Ensure the bid is greater than the initial price
+        ensure!(bid_total.micro_ccd > auction.initial_price, BidError::BidBelowCurrentBid);
+    } else {
+        // Ensure that the new bid is not below the current highest bid
+        ensure!(bid_total >= auction.highest_bid, BidError::BidBelowCurrentBid);
+        // A bid that ties the highest bid, or exceeds it by less than the
+        // minimum raise, does not replace it. It can stand as the backup bid
+        // if the auction accepts them, and exceeds the current backup bid,
+        // which stands on a tie as it was placed earlier.
+        let minimum_bid = auction.highest_bid.checked_add(auction.minimum_raise.minimum_for(auction.highest_bid));
+        let beats_highest = bid_total > auction.highest_bid && minimum_bid.is_some_and(|minimum_bid| bid_total >= minimum_bid);
+        if !beats_highest && auction.accepts_backup_bids && !is_top_up {
+            if let Some(backup) = backup {
+                ensure!(amount >= backup.amount, BidError::BidBelowCurrentBid);
+                ensure!(amount > backup.amount, BidError::BidTiesCurrentBid);
+            }
+            return Ok(BidPlacement::Backup);
+        }
+        ensure!(bid_total > auction.highest_bid, BidError::BidTiesCurrentBid);
+        ensure!(beats_highest, BidError::BidBelowMinimumRaise);
+    }
+    Ok(BidPlacement::Highest {
+        bid_total,
+        is_top_up,
+    })
+}
+
+/// Places a bid of `amount`, attached to the call or, if `from_balance` is
+/// set, already debited from the sender's balance. The bid is checked with
+/// `validate_bid` and the state is updated in full before any CCD are sent,
+/// so that every transfer happens against the final state of the bid.
+fn place_bid(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    parameter: BidParameter,
+    amount: Amount,
+    from_balance: bool,
+) -> Result<(), BidError> {
+    ensure!(parameter.data.as_ref().len() <= MAX_BID_DATA_SIZE, BidError::BidDataTooLarge);
+    let state = host.state();
+    let auto_settle = state.auto_settle_late_bids;
+    let admin = state.admin;
+    let approval_window = state.owner_approval_window;
+    // Only the auction the bid is for is loaded from the state
+    let mut auction = state.auction(parameter.auction_id)?;
+    let backup = state.backup_bids.get(&parameter.auction_id).map(|backup| *backup);
+
+    // Ensure that only accounts can place a bid
+    let sender_address = match ctx.sender() {
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+        Address::Account(account_address) => account_address,
+    };
+    let beneficiary = parameter.beneficiary.unwrap_or(sender_address);
+
+    let slot_time = ctx.metadata().slot_time();
+    let placement = match validate_bid(&auction, slot_time, sender_address, beneficiary, amount, backup.as_ref()) {
+        // Rejecting the bid would also roll back the settlement, so when
+        // auto-settlement is enabled the late bid is accepted as a no-op: the
+        // auction is settled and the full bid amount is sent back.
+        Err(BidError::BidTooLate)
+            if auto_settle
+                && may_finalize(&auction, admin, ctx.sender(), slot_time)
+                && !awaiting_owner_approval(&auction, approval_window, slot_time) =>
+        {
+            if needs_owner_approval(&auction) {
+                log_approval(logger, parameter.auction_id, ApprovalOutcome::SettledByDefault)?;
+            }
+            settle_auction(ctx, host, logger, parameter.auction_id)?;
+            if from_balance {
+                return credit_balance(host, logger, sender_address, amount);
+            }
+            host.invoke_transfer(&sender_address, amount)?;
+            return log_refund(logger, Some(parameter.auction_id), sender_address, amount, RefundReason::LateBid);
+        }
+        result => result?,
+    };
+    let (bid_total, is_top_up) = match placement {
+        BidPlacement::Highest { bid_total, is_top_up } => (bid_total, is_top_up),
+        BidPlacement::Backup => {
+            return place_backup_bid(host, logger, parameter.auction_id, sender_address, parameter.beneficiary, amount, backup);
+        }
+    };
+    // A bid in the alternative bid token is topped up with the token only
+    ensure!(!is_top_up || auction.token_bid.is_none(), BidError::BidAssetMismatch);
+
+    // Effects: update the auction with the new highest bid and highest
+    // bidder. The latest bid, including a top-up, decides who receives the
+    // tokens. A top-up has no previous bidder to refund.
+    let previous = auction.clone();
+    let prev_highest_bidder = auction.highest_bidder.take().filter(|_| !is_top_up);
+    auction.highest_bid = bid_total;
+    auction.highest_bidder = Some(sender_address);
+    auction.beneficiary = parameter.beneficiary;
+    auction.token_bid = None;
+    if let (AuctionKind::SecondPrice { second_bid }, Some(_)) = (&mut auction.kind, prev_highest_bidder) {
+        *second_bid = previous.highest_bid;
+    }
+
+    let (state, state_builder) = host.state_and_builder();
+    state.store_auction(parameter.auction_id, auction.clone());
+    if let Some(prev_bidder) = prev_highest_bidder {
+        state.untrack_highest_bid(&prev_bidder, parameter.auction_id);
+    }
+    let prev_from_balance = state.balance_funded_bids.contains(&parameter.auction_id);
+    if from_balance || (is_top_up && prev_from_balance) {
+        state.balance_funded_bids.insert(parameter.auction_id);
+    } else {
+        state.balance_funded_bids.remove(&parameter.auction_id);
+    }
+    // A backup bidder taking the lead no longer needs their backup bid
+    let own_backup = backup.filter(|backup| backup.bidder == sender_address);
+    if own_backup.is_some() {
+        state.take_backup_bid(parameter.auction_id);
+    }
+    state.track_highest_bid(state_builder, sender_address, parameter.auction_id);
+    state.record_bid(state_builder, parameter.auction_id, sender_address);
+    state.standing_bids_total = state.standing_bids_total + bid_total - held_ccd(&previous);
+
+    logger
+        .log(&AuctionEvent::Bid(BidEventData {
+            auction_id: parameter.auction_id,
+            bidder: sender_address,
+            beneficiary,
+            amount: bid_total,
+            data: parameter.data.as_ref().to_vec(),
+        }))?;
+    log_item_event(logger, AuctionEvent::ItemBid, parameter.auction_id, &auction, bid_total, Address::Account(sender_address))?;
+
+    // Interactions: refund the previous highest bid
+    if prev_highest_bidder.is_some() {
+        refund_outbid(ctx, host, logger, parameter.auction_id, &previous, prev_from_balance)?;
+    }
+    if let Some(backup) = own_backup {
+        refund_backup_bid(host, logger, parameter.auction_id, backup, RefundReason::BackupReleased)?;
+    }
+
+    Ok(())
+}
+
+/// Places a bid of tokens transferred with `TransferData::Bid` on an auction
+/// accepting them as its alternative bid token. The bid is compared with
+/// bids in CCD by the value of the tokens and checked with `validate_bid`
+/// like a CCD bid. The highest bidder tops up a bid in the token with more
+/// of the token, which is valued together with the tokens already held. As
+/// for `bid`, the state is updated in full before the outbid highest bid is
+/// refunded in the asset it was placed in. Rejecting the bid rejects the
+/// transfer, so the tokens stay with the bidder.
+fn place_token_bid(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    token_contract: ContractAddress,
+    params: OnReceivingCis2Params<ContractTokenId, ContractTokenAmount>,
+) -> Result<(), BidError> {
+    let mut auction = host.state().auction(auction_id)?;
+    let alt_bid_token = auction
+        .alt_bid_token
+        .filter(|alt_bid_token| alt_bid_token.contract == token_contract && alt_bid_token.token_id == params.token_id)
+        .ok_or(BidError::WrongBidToken)?;
+    let bidder = match params.from {
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+        Address::Account(account_address) => account_address,
+    };
+
+    // A bid in CCD is topped up with CCD only
+    let is_top_up = auction.highest_bidder == Some(bidder);
+    let tokens = match (is_top_up, auction.token_bid) {
+        (true, None) => bail!(BidError::BidAssetMismatch),
+        (true, Some(held)) => TokenAmountU64(held.0.checked_add(params.amount.0).ok_or(BidError::BidValueOverflow)?),
+        (false, _) => params.amount,
+    };
+    let value = alt_bid_token.value_of(tokens).ok_or(BidError::BidValueOverflow)?;
+    // `validate_bid` adds a top-up to the highest bid, which is the value of
+    // the tokens already held
+    let amount = match is_top_up {
+        true => value - auction.highest_bid,
+        false => value,
+    };
+    let slot_time = ctx.metadata().slot_time();
+    match validate_bid(&auction, slot_time, bidder, bidder, amount, None)? {
+        BidPlacement::Highest { .. } => (),
+        // Auctions with an alternative bid token take no backup bids
+        BidPlacement::Backup => bail!(BidError::InvalidAltBidToken),
+    }
+
+    // Effects: update the auction with the new highest bid, and hold the
+    // transferred tokens for it
+    let previous = auction.clone();
+    let prev_highest_bidder = auction.highest_bidder.take().filter(|_| !is_top_up);
+    auction.highest_bid = value;
+    auction.highest_bidder = Some(bidder);
+    auction.beneficiary = None;
+    auction.token_bid = Some(tokens);
+
+    let (state, state_builder) = host.state_and_builder();
+    state.store_auction(auction_id, auction.clone());
+    if let Some(prev_bidder) = prev_highest_bidder {
+        state.untrack_highest_bid(&prev_bidder, auction_id);
+    }
+    let prev_from_balance = state.balance_funded_bids.remove(&auction_id);
+    state.add_escrow(alt_bid_token.contract, alt_bid_token.token_id, params.amount)?;
+    state.track_highest_bid(state_builder, bidder, auction_id);
+    state.record_bid(state_builder, auction_id, bidder);
+    state.standing_bids_total -= held_ccd(&previous);
+
+    logger
+        .log(&AuctionEvent::Bid(BidEventData {
+            auction_id,
+            bidder,
+            beneficiary: bidder,
+            amount: value,
+            data: Vec::new(),
+        }))?;
+    logger
+        .log(&AuctionEvent::TokenBid(TokenBidEventData {
+            auction_id,
+            bidder,
+            tokens,
+            value,
+        }))?;
+    log_item_event(logger, AuctionEvent::ItemBid, auction_id, &auction, value, Address::Account(bidder))?;
+
+    // Interactions: refund the previous highest bid
+    if prev_highest_bidder.is_some() {
+        refund_outbid(ctx, host, logger, auction_id, &previous, prev_from_balance)?;
+    }
+    Ok(())
+}
+
+/// The CCD the contract holds for the highest bid of `auction`, which are
+/// none if it was placed in the alternative bid token.
+fn held_ccd(auction: &Auction) -> Amount {
+    match auction.token_bid {
+        Some(_) => Amount::zero(),
+        None => auction.highest_bid,
+    }
+}
+
+/// Refunds the highest bid `previous` had before it was outbid, in the asset
+/// it was placed in: tokens of the alternative bid token are transferred
+/// back, a CCD bid funded from a balance, even in part, is credited back to
+/// it, and other CCD bids are transferred, or kept claimable if the transfer
+/// fails.
+fn refund_outbid(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    previous: &Auction,
+    from_balance: bool,
+) -> Result<(), BidError> {
+    let Some(bidder) = previous.highest_bidder else {
+        return Ok(());
+    };
+    if previous.token_bid.is_some() {
+        return refund_bid_tokens(ctx, host, logger, auction_id, previous, RefundReason::Outbid);
+    }
+    let bid = previous.highest_bid;
+    if from_balance {
+        return credit_balance(host, logger, bidder, bid);
+    }
+    if host.invoke_transfer(&bidder, bid).is_err() {
+        host.state_mut().credit_refund(bidder, bid);
+        return Ok(());
+    }
+    log_refund(logger, Some(auction_id), bidder, bid, RefundReason::Outbid)
+}
+
+/// Returns the tokens of the highest bid of `auction` to the highest bidder,
+/// if the bid was placed in the alternative bid token. Tokens that cannot be
+/// returned are kept for the bidder to claim with `claim_tokens`, like CCD
+/// refunds, so a bidder refusing them cannot block the call.
+fn refund_bid_tokens(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    auction: &Auction,
+    reason: RefundReason,
+) -> Result<(), BidError> {
+    let (Some(alt_bid_token), Some(tokens), Some(bidder)) = (auction.alt_bid_token, auction.token_bid, auction.highest_bidder)
+    else {
+        return Ok(());
+    };
+    host.state_mut().release_escrow(alt_bid_token.contract, alt_bid_token.token_id, tokens);
+    if !pay_tokens_or_defer(ctx, host, logger, auction_id, &alt_bid_token, Receiver::from_account(bidder), tokens)? {
+        return Ok(());
+    }
+    logger
+        .log(&AuctionEvent::TokenRefund(TokenRefundEventData {
+            auction_id,
+            account: bidder,
+            tokens,
+            reason,
+        }))?;
+    Ok(())
+}
+
+/// Transfers tokens of an alternative bid token held by the contract.
+fn transfer_bid_tokens(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    alt_bid_token: &AltBidToken,
+    to: Receiver,
+    tokens: TokenAmountU64,
+) -> Result<(), BidError> {
+    let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+        token_id: alt_bid_token.token_id,
+        amount: tokens,
+        from: Address::Contract(ctx.self_address()),
+        to,
+        data: AdditionalData::empty(),
+    };
+    let client = Cis2Client::new(alt_bid_token.contract);
+    let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    result.map_err(|_| BidError::TransferFailed)?;
+    Ok(())
+}
+
+/// Transfers tokens of an alternative bid token released from the escrow to
+/// `to`, or keeps them escrowed for the recipient to claim with
+/// `claim_tokens` and logs the `TokensDeferred` event if the transfer fails.
+/// Returns whether the tokens were transferred.
+fn pay_tokens_or_defer(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    alt_bid_token: &AltBidToken,
+    to: Receiver,
+    tokens: TokenAmountU64,
+) -> Result<bool, BidError> {
+    let recipient = to.address();
+    if transfer_bid_tokens(ctx, host, alt_bid_token, to, tokens).is_ok() {
+        return Ok(true);
+    }
+    host.state_mut().credit_token_claim(alt_bid_token.contract, alt_bid_token.token_id, recipient, tokens)?;
+    logger.log(&AuctionEvent::TokensDeferred(TokensDeferredEventData {
+        auction_id,
+        recipient,
+        token_contract: alt_bid_token.contract,
+        token_id: alt_bid_token.token_id,
+        amount: tokens,
+    }))?;
+    Ok(false)
+}
+
+/// Type of the parameter of `claim_tokens`.
+#[derive(Serialize, SchemaType)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClaimTokensParameter {
+    pub token_contract: ContractAddress,
+    pub token_id:       TokenIdU8,
+    pub entrypoint:     OwnedEntrypointName, // Entrypoint a contract sender receives the tokens at, ignored for accounts
+}
+
+/// `claim_tokens` function to withdraw the tokens of an alternative bid token
+/// kept for the sender after a refund or payout to it failed.
+#[receive(contract = "auction", name = "claim_tokens", parameter = "ClaimTokensParameter", mutable, error = "BidError")]
+pub fn claim_tokens(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), BidError> {
+    let parameter: ClaimTokensParameter = ctx.parameter_cursor().get()?;
+    let sender = ctx.sender();
+    let tokens = host
+        .state_mut()
+        .take_token_claim(parameter.token_contract, parameter.token_id, sender)
+        .ok_or(BidError::NothingToClaim)?;
+
+    let to = match sender {
+        Address::Account(account) => Receiver::from_account(account),
+        Address::Contract(contract) => Receiver::from_contract(contract, parameter.entrypoint),
+    };
+    let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+        token_id: parameter.token_id,
+        amount: tokens,
+        from: Address::Contract(ctx.self_address()),
+        to,
+        data: AdditionalData::empty(),
+    };
+    let client = Cis2Client::new(parameter.token_contract);
+    let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    result.map_err(|_| BidError::TransferFailed)?;
+    Ok(())
+}
+
+/// Records a bid that does not replace the highest bid of an auction
+/// accepting backup bids as its backup bid, after `validate_bid` checked that
+/// it exceeds the `previous` backup bid, if any, which is refunded. There is
+/// at most one backup bid per auction. For second price auctions the backup
+/// bid counts towards the second highest bid only if it still stands when
+/// the auction settles, so a withdrawn or replaced backup bid sets no price.
+fn place_backup_bid(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    bidder: AccountAddress,
+    beneficiary: Option<AccountAddress>,
+    amount: Amount,
+    previous: Option<BackupBid>,
+) -> Result<(), BidError> {
+    let (state, state_builder) = host.state_and_builder();
+    if previous.is_some() {
+        state.take_backup_bid(auction_id);
+    }
+    let _ = state.backup_bids.insert(auction_id, BackupBid {
+        bidder,
+        beneficiary,
+        amount,
+    });
+    state.backup_bids_total += amount;
+    state.record_bid(state_builder, auction_id, bidder);
+
+    logger
+        .log(&AuctionEvent::BackupBid(BackupBidEventData {
+            auction_id,
+            bidder,
+            amount,
+        }))?;
+    match previous {
+        Some(previous) => refund_backup_bid(host, logger, auction_id, previous, RefundReason::Outbid),
+        None => Ok(()),
+    }
+}
+
+/// Refunds a backup bid that was taken off its auction, or keeps it
+/// claimable if the transfer fails.
+fn refund_backup_bid(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    backup: BackupBid,
+    reason: RefundReason,
+) -> Result<(), BidError> {
+    if host.invoke_transfer(&backup.bidder, backup.amount).is_err() {
+        host.state_mut().credit_refund(backup.bidder, backup.amount);
+        return Ok(());
+    }
+    log_refund(logger, Some(auction_id), backup.bidder, backup.amount, reason)
+}
+
+/// Counts the backup bid standing when a second price auction settles
+/// towards its second highest bid, which otherwise only covers the highest
+/// bids that were outbid. A backup bid short of the minimum raise can exceed
+/// the highest bid, so the second highest bid is capped at the highest bid.
+fn count_backup_bid(auction: &mut Auction, backup: &BackupBid) {
+    if let AuctionKind::SecondPrice { second_bid } = &mut auction.kind {
+        *second_bid = (*second_bid).max(backup.amount).min(auction.highest_bid);
+    }
+}
+
+/// Logs that the backup bid of an auction became its highest bid.
+fn log_backup_promoted(logger: &mut impl HasLogger, auction_id: u32, backup: &BackupBid) -> Result<(), BidError> {
+    logger
+        .log(&AuctionEvent::BackupPromoted(BackupBidEventData {
+            auction_id,
+            bidder: backup.bidder,
+            amount: backup.amount,
+        }))
+        .map_err(BidError::from)
+}
+
+/// `withdraw_backup_bid` function to let the backup bidder of an auction take
+/// back their backup bid at any time.
+#[receive(contract = "auction", name = "withdraw_backup_bid", parameter = "AuctionIdParameter", enable_logger, mutable, error = "BidError")]
+pub fn withdraw_backup_bid(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    ensure!(host.state().has_auction(parameter.auction_id), BidError::AuctionNotFound);
+    let is_backup_bidder = match host.state().backup_bids.get(&parameter.auction_id) {
+        Some(backup) => ctx.sender().matches_account(&backup.bidder),
+        None => false,
+    };
+    ensure!(is_backup_bidder, BidError::NotBackupBidder);
+
+    let backup = host.state_mut().take_backup_bid(parameter.auction_id).ok_or(BidError::NotBackupBidder)?;
+    refund_backup_bid(host, logger, parameter.auction_id, backup, RefundReason::BackupWithdrawn)
+}
+
+/// View function to return the backup bid of an auction, if it has one.
+#[receive(contract = "auction", name = "view_backup_bid", parameter = "AuctionIdParameter", return_value = "Option<BackupBid>", error = "BidError")]
+pub fn view_backup_bid(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<Option<BackupBid>, BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    ensure!(state.has_auction(parameter.auction_id), BidError::AuctionNotFound);
+    let backup = state.backup_bids.get(&parameter.auction_id).map(|backup| *backup);
+    Ok(backup)
+}
+
+/// `claim_refund` function to withdraw the refunds owed to the sender,
+/// including the payouts whose transfer to the sender failed, such as the
+/// commission shares of a commission recipient account.
+#[receive(contract = "auction", name = "claim_refund", enable_logger, mutable, error = "BidError")]
+pub fn claim_refund(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let account = match ctx.sender() {
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+        Address::Account(account_address) => account_address,
+    };
+
+    let refund = host.state_mut().take_refund(&account).ok_or(BidError::NothingToClaim)?;
+    host.invoke_transfer(&account, refund)?;
+    log_refund(logger, None, account, refund, RefundReason::Claimed)
+}
+
+/// Credits CCD held by the contract to the balance of `account`.
+fn credit_balance(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    account: AccountAddress,
+    amount: Amount,
+) -> Result<(), BidError> {
+    let state = host.state_mut();
+    let balance = {
+        let mut balance = state.balances.entry(account).or_insert_with(Amount::zero);
+        *balance += amount;
+        *balance
+    };
+    state.balances_total += amount;
+    log_balance(logger, AuctionEvent::BalanceCredited, account, amount, balance)
+}
+
+/// Debits the balance of `account`, failing if it is too low.
+fn debit_balance(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    account: AccountAddress,
+    amount: Amount,
+) -> Result<(), BidError> {
+    let state = host.state_mut();
+    let available = state.balances.get(&account).map_or(Amount::zero(), |balance| *balance);
+    ensure!(amount <= available, BidError::InsufficientBalance);
+    let balance = available - amount;
+    if balance == Amount::zero() {
+        state.balances.remove(&account);
+    } else {
+        let _ = state.balances.insert(account, balance);
+    }
+    state.balances_total -= amount;
+    log_balance(logger, AuctionEvent::BalanceDebited, account, amount, balance)
+}
+
+fn log_balance(
+    logger: &mut impl HasLogger,
+    event: fn(BalanceEventData) -> AuctionEvent,
+    account: AccountAddress,
+    amount: Amount,
+    balance: Amount,
+) -> Result<(), BidError> {
+    logger
+        .log(&event(BalanceEventData {
+            account,
+            amount,
+            balance,
+        }))
+        .map_err(BidError::from)
+}
+
+/// `deposit` function to add the attached CCD to the sender's balance, from
+/// which it can bid with `bid_from_balance`.
+#[receive(contract = "auction", name = "deposit", payable, enable_logger, mutable, error = "BidError")]
+pub fn deposit(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let account = match ctx.sender() {
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+        Address::Account(account_address) => account_address,
+    };
+    credit_balance(host, logger, account, amount)
+}
+
+/// `withdraw` function to transfer the given amount from the sender's
+/// balance to the sender.
+#[receive(contract = "auction", name = "withdraw", parameter = "Amount", enable_logger, mutable, error = "BidError")]
+pub fn withdraw(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let amount: Amount = ctx.parameter_cursor().get()?;
+    let account = match ctx.sender() {
+        Address::Contract(_) => bail!(BidError::OnlyAccount),
+        Address::Account(account_address) => account_address,
+    };
+    debit_balance(host, logger, account, amount)?;
+    host.invoke_transfer(&account, amount)?;
+    Ok(())
+}
+
+/// View function to return the balance of an account.
+#[receive(contract = "auction", name = "view_balance", parameter = "AccountAddress", return_value = "Amount", error = "BidError")]
+pub fn view_balance(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<Amount, BidError> {
+    let account: AccountAddress = ctx.parameter_cursor().get()?;
+    Ok(host.state().balances.get(&account).map_or(Amount::zero(), |balance| *balance))
+}
+
+/// `claim_proceeds` function to let a contract owning auctions, or a contract
+/// commission sink that rejected its share, withdraw its proceeds. The CCD
+/// are sent by invoking the given entrypoint of the sender, which has to be
+/// payable. Accounts are rejected with `UseClaimRefund`, as the payouts owed
+/// to them are kept as refunds.
+#[receive(contract = "auction", name = "claim_proceeds", parameter = "OwnedEntrypointName", mutable, error = "BidError")]
+pub fn claim_proceeds(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), BidError> {
+    let entrypoint: OwnedEntrypointName = ctx.parameter_cursor().get()?;
+    let owner = match ctx.sender() {
+        Address::Account(_) => bail!(BidError::UseClaimRefund),
+        Address::Contract(contract_address) => contract_address,
+    };
+
+    let proceeds = host.state_mut().take_proceeds(&owner).ok_or(BidError::NothingToClaim)?;
+    host.invoke_contract(&owner, &(), entrypoint.as_entrypoint_name(), proceeds).map_err(|_| BidError::TransferFailed)?;
+    Ok(())
+}
+
+/// View function to return the proceeds a contract owner can claim.
+#[receive(contract = "auction", name = "view_proceeds", parameter = "ContractAddress", return_value = "Amount", error = "BidError")]
+pub fn view_proceeds(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<Amount, BidError> {
+    let owner: ContractAddress = ctx.parameter_cursor().get()?;
+    Ok(host.state().pending_proceeds.get(&owner).map_or(Amount::zero(), |proceeds| *proceeds))
+}
+
+/// View function to return a page of the refunds that accounts can claim.
+#[receive(contract = "auction", name = "view_refunds", parameter = "PageParameter", return_value = "Vec<(AccountAddress, Amount)>", error = "BidError")]
+pub fn view_refunds(
+    ctx: &impl HasReceiveContext,
+    host: &Host<State>,
+) -> Result<Vec<(AccountAddress, Amount)>, BidError> {
+    let parameter: PageParameter = ctx.parameter_cursor().get()?;
+
+    let refunds = host
+        .state()
+        .pending_refunds
+        .iter()
+        .skip(parameter.skip as usize)
+        .take(parameter.take.min(MAX_PAGE_SIZE) as usize)
+        .map(|(account, amount)| (*account, *amount))
+        .collect();
+    Ok(refunds)
+}
+
+/// View function to return the CCD and tokens the contract should be holding.
+/// The totals are maintained as the auctions change, so this does not scan
+/// the auctions.
+#[receive(contract = "auction", name = "view_escrow_summary", return_value = "EscrowSummary", error = "BidError")]
+pub fn view_escrow_summary(_ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<EscrowSummary, BidError> {
+    let state = host.state();
+    let tokens = state
+        .escrowed
+        .iter()
+        .map(|(token, amount)| EscrowedTokens {
+            token_contract: token.0,
+            token_id:       token.1,
+            amount:         *amount,
+        })
+        .collect();
+    let unsold_remainders = state
+        .pending_returns
+        .iter()
+        .filter_map(|auction_id| state.auction(*auction_id).ok().map(|auction| (*auction_id, auction.amount_remaining)))
+        .collect();
+    let token_claims = state
+        .pending_token_claims
+        .iter()
+        .map(|(key, amount)| TokenClaim {
+            token_contract: key.0,
+            token_id:       key.1,
+            recipient:      key.2,
+            amount:         *amount,
+        })
+        .collect();
+    let total_ccd = [
+        state.standing_bids_total,
+        state.pending_refunds_total,
+        state.reverse_budgets_total,
+        state.offers_total,
+        state.pending_proceeds_total,
+        state.backup_bids_total,
+        state.balances_total,
+    ]
+    .iter()
+    .try_fold(Amount::zero(), |total, amount| total.checked_add(*amount))
+    .ok_or(BidError::ArithmeticOverflow)?;
+
+    Ok(EscrowSummary {
+        standing_bids: state.standing_bids_total,
+        pending_refunds: state.pending_refunds_total,
+        reverse_budgets: state.reverse_budgets_total,
+        open_offers: state.offers_total,
+        pending_proceeds: state.pending_proceeds_total,
+        backup_bids: state.backup_bids_total,
+        balances: state.balances_total,
+        total_ccd,
+        tokens,
+        unsold_remainders,
+        token_claims,
+    })
+}
+
+/// View function to return the refund an account can claim and the auctions
+/// in which it is currently the highest bidder.
+#[receive(contract = "auction", name = "view_account_position", parameter = "AccountAddress", return_value = "AccountPosition", error = "BidError")]
+pub fn view_account_position(
+    ctx: &impl HasReceiveContext,
+    host: &Host<State>,
+) -> Result<AccountPosition, BidError> {
+    let account: AccountAddress = ctx.parameter_cursor().get()?;
+    let state = host.state();
+
+    let pending_refund = state.pending_refunds.get(&account).map_or(Amount::zero(), |refund| *refund);
+    let highest_bids = match state.bids_by_bidder.get(&account) {
+        Some(auction_ids) => auction_ids
+            .iter()
+            .filter_map(|auction_id| {
+                state.auction(*auction_id).ok().map(|auction| (*auction_id, auction.highest_bid))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(AccountPosition {
+        pending_refund,
+        highest_bids,
+    })
+}
+
+/// View function to return the lifetime statistics of the contract.
+#[receive(contract = "auction", name = "view_stats", return_value = "Stats", error = "BidError")]
+pub fn view_stats(_ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<Stats, BidError> {
+    Ok(host.state().stats.clone())
+}
+
+/// View function to return a snapshot of the configuration and a page of each
+/// collection in the state, for indexers starting from scratch.
+#[receive(contract = "auction", name = "view_state", parameter = "StateViewParameter", return_value = "StateView", error = "BidError")]
+pub fn view_state(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<StateView, BidError> {
+    let parameter: StateViewParameter = ctx.parameter_cursor().get()?;
+    let state = host.state();
+
+    let auctions = state.auction_range(parameter.auctions.skip, parameter.auctions.take.min(MAX_PAGE_SIZE));
+    let pending_refunds = state
+        .pending_refunds
+        .iter()
+        .skip(parameter.refunds.skip as usize)
+        .take(parameter.refunds.take.min(MAX_PAGE_SIZE) as usize)
+        .map(|(account, amount)| (*account, *amount))
+        .collect();
+
+    Ok(StateView::V1(StateViewV1 {
+        admin: state.admin,
+        commission_recipients: state.commission_recipients.clone(),
+        commission_tiers: state.commission_tiers.clone(),
+        commission_change_delay: state.commission_change_delay,
+        pending_commission_change: state.pending_commission_change.clone(),
+        auto_settle_late_bids: state.auto_settle_late_bids,
+        reclaim_grace_period: state.reclaim_grace_period,
+        cancellation_fee_bps: state.cancellation_fee_bps,
+        owner_approval_window: state.owner_approval_window,
+        stats: state.stats.clone(),
+        auction_count: state.auction_count,
+        auctions,
+        pending_refunds,
+    }))
+}
+
+/// View function to return the configuration of the contract and its
+/// version.
+#[receive(contract = "auction", name = "view_config", return_value = "ConfigView", error = "BidError")]
+pub fn view_config(_ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<ConfigView, BidError> {
+    let state = host.state();
+    Ok(ConfigView::V1(ConfigViewV1 {
+        version: String::from(CONTRACT_VERSION),
+        admin: state.admin,
+        commission_recipients: state.commission_recipients.clone(),
+        commission_tiers: state.commission_tiers.clone(),
+        commission_change_delay: state.commission_change_delay,
+        pending_commission_change: state.pending_commission_change.clone(),
+        auto_settle_late_bids: state.auto_settle_late_bids,
+        reclaim_grace_period: state.reclaim_grace_period,
+        cancellation_fee_bps: state.cancellation_fee_bps,
+        owner_approval_window: state.owner_approval_window,
+        retraction_penalty_bps: state.retraction_penalty_bps,
+        retraction_cutoff: state.retraction_cutoff,
+        max_active_auctions: state.max_active_auctions,
+        max_auction_duration: state.max_auction_duration,
+        delivery_window: state.delivery_window,
+        swap_contract: state.swap_contract,
+    }))
+}
+
+/// The auctions serialized like a `Vec<Auction>` in ID order, loaded one at
+/// a time instead of copied into a vector.
+pub struct AuctionsView<'a>(&'a State);
+
+impl Serial for AuctionsView<'_> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.0.auction_count.serial(out)?;
+        for auction_id in 0..self.0.auction_count {
+            if let Some(auction) = self.0.auctions.get(&auction_id) {
+                auction.serial(out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// View function to return all auctions in ID order. Once there are more than
+/// `MAX_VIEW_AUCTIONS` auctions, it fails with `TooManyAuctionsToView`, and
+/// the paged `view_state` or `get_auctions` have to be used instead.
+#[receive(contract = "auction", name = "view_auctions", return_value = "Vec<Auction>", error = "BidError")]
+pub fn view_auctions<'a>(_ctx: &impl HasReceiveContext, host: &'a Host<State>) -> Result<AuctionsView<'a>, BidError> {
+    let state = host.state();
+    ensure!(state.auction_count as usize <= MAX_VIEW_AUCTIONS, BidError::TooManyAuctionsToView);
+    Ok(AuctionsView(state))
+}
+
+/// `get_auction` function to fetch a specific auction by its ID as a view function.
+#[receive(contract = "auction", name = "get_auction", parameter = "AuctionIdParameter", return_value = "Auction", error = "BidError")]
+pub fn get_auction(
+    ctx: &impl HasReceiveContext,
+    host: &Host<State>,
+) -> Result<Auction, BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+
+    // Get a copy of the auction, and ensure it exists
+    host.state().auction(parameter.auction_id)
+}
+
+/// The bid statistics of an auction.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct AuctionStats {
+    pub bid_count:      u32, // Number of bids placed, including top-ups
+    pub unique_bidders: u32, // Number of distinct bidders, counted up to `MAX_COUNTED_BIDDERS`
+}
+
+/// View function to return how an auction was settled. Auctions that have
+/// not been sold, including those still running and those closed without a
+/// sale, are reported as not settled rather than failing, so that clients can
+/// poll this safely. An auction pending delivery is reported as settled with
+/// `tokens_delivered` unset until a retry delivers the tokens.
+#[receive(contract = "auction", name = "view_settlement", parameter = "AuctionIdParameter", return_value = "SettlementStatus", error = "BidError")]
+pub fn view_settlement(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<SettlementStatus, BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    ensure!(state.has_auction(parameter.auction_id), BidError::AuctionNotFound);
+    let status = match state.settlements.get(&parameter.auction_id) {
+        Some(record) => SettlementStatus::Settled(record.clone()),
+        None => SettlementStatus::NotSettled,
+    };
+    Ok(status)
+}
+
+/// View function to preview what `finalize` would pay out for an auction if
+/// the sender called it now, using the same payout split as `finalize`.
+/// Where `finalize` would fail for another reason than the auction not
+/// having ended or being finalized already, such as the settlement
+/// exclusivity or owner approval window, the view fails with the same error.
+/// Reverse auctions, which `finalize` settles by refunding the budget, are
+/// not previewed.
+#[receive(
+    contract = "auction",
+    name = "view_settlement_preview",
+    parameter = "AuctionIdParameter",
+    return_value = "SettlementPreview",
+    error = "BidError"
+)]
+pub fn view_settlement_preview(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<SettlementPreview, BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let state = host.state();
+    let mut auction = state.auction(parameter.auction_id)?;
+
+    if auction.auction_state != AuctionState::NotSoldYet {
+        return Ok(SettlementPreview::without_payouts(PreviewStatus::AlreadyFinalized));
+    }
+    ensure!(!auction.kind.is_fixed_price(), BidError::FixedPriceListing);
+    let slot_time = ctx.metadata().slot_time();
+    if auction_phase(&auction, slot_time) == Phase::Bidding {
+        return Ok(SettlementPreview::without_payouts(PreviewStatus::NotYetEnded));
+    }
+    ensure!(!auction.kind.is_reverse(), BidError::WrongAuctionKind);
+    ensure!(may_finalize(&auction, state.admin, ctx.sender(), slot_time), BidError::FinalizeNotYetPermissionless);
+    let bidder = match (auction.escrow_confirmed, auction.highest_bidder) {
+        (true, Some(bidder)) => bidder,
+        _ => return Ok(SettlementPreview::without_payouts(PreviewStatus::WouldReturnUnsold)),
+    };
+    ensure!(
+        !awaiting_owner_approval(&auction, state.owner_approval_window, slot_time),
+        BidError::AwaitingOwnerApproval
+    );
+
+    let winner = Some(auction.beneficiary.unwrap_or(bidder));
+    if let Some(tokens) = auction.token_bid {
+        let (commission_tokens, owner_tokens) = token_payout_split(&auction, tokens);
+        return Ok(SettlementPreview {
+            winner,
+            price: auction.highest_bid,
+            owner_tokens,
+            commission_tokens,
+            ..SettlementPreview::without_payouts(PreviewStatus::WouldSettleToWinner)
+        });
+    }
+    let keeper = match ctx.sender() {
+        Address::Account(account_address) => Some(account_address),
+        Address::Contract(_) => None,
+    };
+    if let Some(backup) = state.backup_bids.get(&parameter.auction_id) {
+        count_backup_bid(&mut auction, &backup);
+    }
+    let split = payout_split(&auction, &state.commission_recipients, keeper)?;
+    Ok(SettlementPreview {
+        status: PreviewStatus::WouldSettleToWinner,
+        winner,
+        price: split.price,
+        commission: split.commission,
+        commission_payouts: split.commission_payouts,
+        finalize_reward: split.finalize_reward,
+        owner_amount: split.owner_amount,
+        refund: split.refund,
+        owner_tokens: TokenAmountU64(0),
+        commission_tokens: TokenAmountU64(0),
+    })
+}
+
+/// View function to return the number of bids and distinct bidders of an
+/// auction. A top-up counts as a bid but not as a new bidder.
+#[receive(contract = "auction", name = "view_auction_stats", parameter = "AuctionIdParameter", return_value = "AuctionStats", error = "BidError")]
+pub fn view_auction_stats(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<AuctionStats, BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let auction = host.state().auction(parameter.auction_id)?;
+    Ok(AuctionStats {
+        bid_count:      auction.bid_count,
+        unique_bidders: auction.unique_bidders,
+    })
+}
+
+/// `get_auctions` function to fetch several auctions by their IDs as a view
+/// function. The auctions are returned in the order of the IDs, with `None`
+/// for IDs that do not exist.
+#[receive(contract = "auction", name = "get_auctions", parameter = "Vec<u32>", return_value = "Vec<Option<Auction>>", error = "BidError")]
+pub fn get_auctions(
+    ctx: &impl HasReceiveContext,
+    host: &Host<State>,
+) -> Result<Vec<Option<Auction>>, BidError> {
+    let auction_ids: Vec<u32> = ctx.parameter_cursor().get()?;
+    ensure!(auction_ids.len() <= MAX_GET_AUCTIONS, BidError::TooManyAuctionIds);
+
+    let state = host.state();
+    Ok(auction_ids.iter().map(|auction_id| state.auction(*auction_id).ok()).collect())
+}
+
+/// View function to return the auctions with IDs from `from` up to but not
+/// including `to`, in ID order and at most `MAX_PAGE_SIZE` of them. A range
+/// beyond the existing auctions is empty.
+#[receive(contract = "auction", name = "get_auctions_range", parameter = "AuctionRangeParameter", return_value = "AuctionRange", error = "BidError")]
+pub fn get_auctions_range(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<AuctionRange, BidError> {
+    let parameter: AuctionRangeParameter = ctx.parameter_cursor().get()?;
+
+    let state = host.state();
+    let take = parameter.to.saturating_sub(parameter.from).min(MAX_PAGE_SIZE);
+    Ok(AuctionRange {
+        auction_count: state.auction_count,
+        auctions:      state.auction_range(parameter.from, take),
+    })
+}
+
+/// View function to return a page of the live auctions of tokens of a
+/// contract, optionally only those of one token, with their IDs. Auctions
+/// leave the index once they are finalized, cancelled or otherwise closed.
+#[receive(
+    contract = "auction",
+    name = "view_auctions_by_token_contract",
+    parameter = "TokenContractAuctionsParameter",
+    return_value = "Vec<(u32, Auction)>",
+    error = "BidError"
+)]
+pub fn view_auctions_by_token_contract(
+    ctx: &impl HasReceiveContext,
+    host: &Host<State>,
+) -> Result<Vec<(u32, Auction)>, BidError> {
+    let parameter: TokenContractAuctionsParameter =
+        ctx.parameter_cursor().get()?;
+
+    let state = host.state();
+    let auction_ids = match state.auctions_by_token_contract.get(&parameter.token_contract) {
+        Some(auction_ids) => auction_ids,
+        None => return Ok(Vec::new()),
+    };
+    let auctions = auction_ids
+        .iter()
+        .filter_map(|auction_id| state.auction(*auction_id).ok().map(|auction| (*auction_id, auction)))
+        .filter(|(_, auction)| parameter.token_id.is_none_or(|token_id| auction.token_id == token_id))
+        .skip(parameter.page.skip as usize)
+        .take(parameter.page.take.min(MAX_PAGE_SIZE) as usize)
+        .collect();
+    Ok(auctions)
+}
+
+/// `finalize` function to finalize a specific auction.
+#[receive(contract = "auction", name = "finalize", parameter = "AuctionIdParameter", enable_logger, mutable, error = "BidError")]
+pub fn auction_finalize(ctx: &impl HasReceiveContext, host: &mut Host<State>, logger: &mut impl HasLogger,) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+
+    let auction = host.state().auction(parameter.auction_id)?;
+
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    ensure!(!auction.kind.is_fixed_price(), BidError::FixedPriceListing);
+
+    let slot_time = ctx.metadata().slot_time();
+    ensure!(auction_phase(&auction, slot_time) == Phase::Ended, BidError::AuctionStillActive);
+    if let AuctionKind::Reverse { budget } = auction.kind {
+        return refund_reverse_auction(ctx, host, logger, parameter.auction_id, budget);
+    }
+    ensure!(
+        may_finalize(&auction, host.state().admin, ctx.sender(), slot_time),
+        BidError::FinalizeNotYetPermissionless
+    );
+    // Without tokens the auction had no bids, and there is nothing to return
+    if !auction.escrow_confirmed {
+        host.state_mut().set_auction_state(parameter.auction_id, AuctionState::Unsold);
+        logger.log(&AuctionEvent::Close(AuctionEventData { auction_id: parameter.auction_id }))?;
+        let auction = host.state().auction(parameter.auction_id)?;
+        return log_item_event(logger, AuctionEvent::ItemUnlisted, parameter.auction_id, &auction, Amount::zero(), auction.owner);
+    }
+    ensure!(
+        !awaiting_owner_approval(&auction, host.state().owner_approval_window, slot_time),
+        BidError::AwaitingOwnerApproval
+    );
+
+    // The owner did not decide on the winning bid in time, so it stands
+    if needs_owner_approval(&auction) {
+        log_approval(logger, parameter.auction_id, ApprovalOutcome::SettledByDefault)?;
+    }
+    settle_auction(ctx, host, logger, parameter.auction_id)
+}
+
+/// Settles an ended reverse auction that was not filled by refunding the
+/// budget to the owner. Once there is an ask, the lowest asker settles the
+/// auction by delivering the tokens; while none arrived, the owner can stop
+/// waiting for them at any time, and anyone else once the delivery window
+/// after the end is over.
+fn refund_reverse_auction(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    budget: Amount,
+) -> Result<(), BidError> {
+    let state = host.state_mut();
+    let auction = state.auction(auction_id)?;
+    if auction.highest_bidder.is_some() {
+        let window_over =
+            auction.end.checked_add(state.delivery_window).is_some_and(|until| ctx.metadata().slot_time() >= until);
+        ensure!(ctx.sender() == auction.owner || window_over, BidError::AwaitingDelivery);
+    }
+    let owner = auction.owner;
+    state.set_auction_state(auction_id, AuctionState::Unsold);
+    state.reverse_budgets_total -= budget;
+
+    pay_owner(host, logger, auction_id, owner, budget, PayoutKind::Budget)?;
+    logger
+        .log(&AuctionEvent::Finalize(FinalizeEventData {
+            auction_id,
+            finalizer: ctx.sender(),
+            commission_payouts: Vec::new(),
+            finalize_reward: Amount::zero(),
+            price: Amount::zero(),
+            winner: None,
+            settled_at: ctx.metadata().slot_time(),
+        }))?;
+    Ok(())
+}
+
+/// Whether the settlement of `auction` depends on the owner approving its
+/// winning bid.
+fn needs_owner_approval(auction: &Auction) -> bool {
+    auction.requires_owner_approval && auction.highest_bidder.is_some()
+}
+
+/// Whether the owner can still accept or reject the winning bid of the ended
+/// `auction` at `slot_time`, which keeps anyone else from settling it.
+fn awaiting_owner_approval(auction: &Auction, window: Duration, slot_time: Timestamp) -> bool {
+    needs_owner_approval(auction) && auction.end.checked_add(window).is_none_or(|until| slot_time < until)
+}
+
+/// Logs the decision on the winning bid of an auction requiring owner approval.
+fn log_approval(logger: &mut impl HasLogger, auction_id: u32, outcome: ApprovalOutcome) -> Result<(), BidError> {
+    logger
+        .log(&AuctionEvent::Approval(ApprovalEventData {
+            auction_id,
+            outcome,
+        }))
+        .map_err(BidError::from)
+}
+
+/// Logs an event of the marketplace vocabulary for the tokens of an
+/// auction. Reverse auctions do not list tokens for sale and log none.
+fn log_item_event(
+    logger: &mut impl HasLogger,
+    event: fn(ItemEventData) -> AuctionEvent,
+    auction_id: u32,
+    auction: &Auction,
+    price: Amount,
+    actor: Address,
+) -> Result<(), BidError> {
+    if auction.kind.is_reverse() {
+        return Ok(());
+    }
+    logger
+        .log(&event(ItemEventData {
+            auction_id,
+            collection: auction.token_contract,
+            token_id: auction.token_id,
+            amount: auction.token_amount,
+            price,
+            actor,
+        }))
+        .map_err(BidError::from)
+}
+
+/// The price tokens are listed for: the fixed price of a listing, or the
+/// initial price of an auction.
+fn listing_price(auction: &Auction) -> Amount {
+    match auction.kind {
+        AuctionKind::FixedPrice { price } => price,
+        _ => Amount::from_micro_ccd(auction.initial_price),
+    }
+}
+
+fn log_refund(
+    logger: &mut impl HasLogger,
+    auction_id: Option<u32>,
+    account: AccountAddress,
+    amount: Amount,
+    reason: RefundReason,
+) -> Result<(), BidError> {
+    logger
+        .log(&AuctionEvent::Refund(RefundEventData {
+            auction_id,
+            account,
+            amount,
+            reason,
+        }))
+        .map_err(BidError::from)
+}
+
+/// Ensures the sender is the owner of the ended `auction` and can still
+/// accept or reject its winning bid. Returns the highest bidder.
+fn ensure_owner_decision(
+    ctx: &impl HasReceiveContext,
+    auction: &Auction,
+    window: Duration,
+) -> Result<AccountAddress, BidError> {
+    ensure!(ctx.sender() == auction.owner, BidError::OnlyOwner);
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    let slot_time = ctx.metadata().slot_time();
+    ensure!(auction_phase(auction, slot_time) == Phase::Ended, BidError::AuctionStillActive);
+    ensure!(auction.requires_owner_approval, BidError::OwnerApprovalNotRequired);
+    let bidder = auction.highest_bidder.ok_or(BidError::NothingToClaim)?;
+    ensure!(awaiting_owner_approval(auction, window, slot_time), BidError::ApprovalWindowOver);
+    Ok(bidder)
+}
+
+/// `accept_winning_bid` function to let the owner of an auction requiring
+/// approval accept the winning bid within the approval window, which settles
+/// the auction.
+#[receive(contract = "auction", name = "accept_winning_bid", parameter = "AuctionIdParameter", enable_logger, mutable, error = "BidError")]
+pub fn accept_winning_bid(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let auction = host.state().auction(parameter.auction_id)?;
+    ensure_owner_decision(ctx, &auction, host.state().owner_approval_window)?;
+
+    log_approval(logger, parameter.auction_id, ApprovalOutcome::Accepted)?;
+    settle_auction(ctx, host, logger, parameter.auction_id)
+}
+
+/// `reject_winning_bid` function to let the owner of an auction requiring
+/// approval reject the winning bid within the approval window: the bid is
+/// refunded and the tokens are returned to the owner. If the auction has a
+/// backup bid, it becomes the winning bid instead, for the owner to accept or
+/// reject within the same window.
+#[receive(contract = "auction", name = "reject_winning_bid", parameter = "AuctionIdParameter", enable_logger, mutable, error = "BidError")]
+pub fn reject_winning_bid(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let auction = host.state().auction(parameter.auction_id)?;
+    let bidder = ensure_owner_decision(ctx, &auction, host.state().owner_approval_window)?;
+
+    let (state, state_builder) = host.state_and_builder();
+    state.untrack_highest_bid(&bidder, parameter.auction_id);
+    state.balance_funded_bids.remove(&parameter.auction_id);
+    state.standing_bids_total -= held_ccd(&auction);
+    let promoted = state.promote_backup_bid(state_builder, parameter.auction_id);
+    if promoted.is_none() {
+        state.set_auction_state(parameter.auction_id, AuctionState::Rejected);
+        state.release_auction_escrow(parameter.auction_id, TokenAmountU64(0))?;
+    }
+    log_approval(logger, parameter.auction_id, ApprovalOutcome::Rejected)?;
+
+    // Refund the bid, or keep it claimable if the transfer fails
+    if auction.token_bid.is_some() {
+        refund_bid_tokens(ctx, host, logger, parameter.auction_id, &auction, RefundReason::Rejected)?;
+    } else if host.invoke_transfer(&bidder, auction.highest_bid).is_err() {
+        host.state_mut().credit_refund(bidder, auction.highest_bid);
+    } else {
+        log_refund(logger, Some(parameter.auction_id), bidder, auction.highest_bid, RefundReason::Rejected)?;
+    }
+    if let Some(backup) = promoted {
+        return log_backup_promoted(logger, parameter.auction_id, &backup);
+    }
+
+    // Return CIS-2 tokens to the owner
+    let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+        token_id: auction.token_id,
+        amount: auction.amount_remaining,
+        from: Address::Contract(ctx.self_address()),
+        to: owner_receiver(auction.owner),
+        data: AdditionalData::empty(),
+    };
+    let client = Cis2Client::new(auction.token_contract);
+    let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    result.map_err(|_| BidError::TransferFailed)?;
+
+    log_item_event(logger, AuctionEvent::ItemReturned, parameter.auction_id, &auction, Amount::zero(), auction.owner)
+}
+
+/// The phase of an auction relative to its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Before `end`: bids and asks are taken, and the owner can cancel.
+    Bidding,
+    /// From `end` on: the auction can be finalized, relisted or decided on
+    /// by the owner, and bids are late.
+    Ended,
+}
+
+/// The phase of `auction` at `now`. The end itself belongs to `Ended`, so
+/// that a bid and a finalize in the same block at `end` cannot both succeed
+/// and change the winner depending on their order.
+fn auction_phase(auction: &Auction, now: Timestamp) -> Phase {
+    match now < auction.end {
+        true => Phase::Bidding,
+        false => Phase::Ended,
+    }
+}
+
+/// Whether `caller` may settle the ended `auction` at `slot_time`. During the
+/// auction's settlement exclusivity window only the owner and the admin may
+/// settle, afterwards anyone may.
+fn may_finalize(auction: &Auction, admin: AccountAddress, caller: Address, slot_time: Timestamp) -> bool {
+    let exclusive_until = match auction.settlement_exclusivity {
+        Some(window) => auction.end.checked_add(window),
+        None => return true,
+    };
+    let permissionless = exclusive_until.is_some_and(|until| slot_time >= until);
+    permissionless || caller == auction.owner || caller.matches_account(&admin)
+}
+
+/// `relist_auction` function to start a new auction for the tokens of an
+/// ended auction without bids, keeping the tokens in escrow. The old auction
+/// is marked as superseded and can neither be bid on nor finalized.
+#[receive(contract = "auction", name = "relist_auction", parameter = "RelistParameter", enable_logger, mutable, error = "BidError")]
+pub fn relist_auction(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: RelistParameter = ctx.parameter_cursor().get()?;
+    let slot_time = ctx.metadata().slot_time();
+
+    let (state, state_builder) = host.state_and_builder();
+    let new_id = state.auction_count;
+    let commission_tiers = state.commission_tiers.clone();
+    let mut auction = state.auction(parameter.auction_id)?;
+
+    ensure!(ctx.sender() == auction.owner, BidError::OnlyOwner);
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    ensure!(auction_phase(&auction, slot_time) == Phase::Ended, BidError::AuctionStillActive);
+    ensure!(auction.highest_bidder.is_none(), BidError::AuctionHasBids);
+    ensure!(!auction.kind.is_reverse(), BidError::WrongAuctionKind);
+    ensure!(auction.escrow_confirmed, BidError::EscrowNotConfirmed);
+    validate_end(parameter.end, auction.settlement_exclusivity, slot_time, state.max_auction_duration)?;
+
+    // The new auction takes over the place of the old one in the active count
+    auction.auction_state = AuctionState::Superseded(new_id);
+    let relisted = Auction {
+        auction_state: AuctionState::NotSoldYet,
+        highest_bidder: None,
+        beneficiary: None,
+        initial_price: parameter.initial_price.unwrap_or(auction.initial_price),
+        highest_bid: Amount::zero(),
+        end: parameter.end,
+        commission_tiers,
+        bid_count: 0,
+        unique_bidders: 0,
+        ..auction.clone()
+    };
+    let token_contract = relisted.token_contract;
+    // The escrowed tokens move to the new auction
+    auction.amount_remaining = TokenAmountU64(0);
+    state.store_auction(parameter.auction_id, auction);
+    state.add_auction(relisted.clone())?;
+    // Bids retracted from the old auction do not count towards the new one
+    state.bidders_by_auction.remove(&parameter.auction_id);
+    state.unindex_auction(&token_contract, parameter.auction_id);
+    state.index_auction(state_builder, token_contract, new_id);
+
+    logger.log(&AuctionEvent::Close(AuctionEventData { auction_id: parameter.auction_id }))?;
+    logger.log(&AuctionEvent::Register(AuctionEventData { auction_id: new_id }))?;
+    log_item_event(logger, AuctionEvent::ItemUnlisted, parameter.auction_id, &relisted, Amount::zero(), relisted.owner)?;
+    log_item_event(logger, AuctionEvent::ItemListed, new_id, &relisted, listing_price(&relisted), relisted.owner)?;
+    Ok(())
+}
+
+/// Type of the parameter to exempt a token contract from the CIS-2 check.
+#[derive(Serialize, SchemaType)]
+pub struct Cis0ExemptionParameter {
+    pub token_contract: ContractAddress,
+    pub exempt:         bool, // Skip the check for the contract, or check it again
+}
+
+/// `set_cis0_exemption` function to let the admin allow listing tokens of a
+/// contract that predates CIS-0 and cannot report supporting CIS-2.
+#[receive(contract = "auction", name = "set_cis0_exemption", parameter = "Cis0ExemptionParameter", mutable, error = "BidError")]
+pub fn set_cis0_exemption(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), BidError> {
+    let parameter: Cis0ExemptionParameter = ctx.parameter_cursor().get()?;
+    ensure!(ctx.sender().matches_account(&host.state().admin), BidError::OnlyAdmin);
+
+    let exempt = &mut host.state_mut().cis0_exempt;
+    if parameter.exempt {
+        exempt.insert(parameter.token_contract);
+    } else {
+        exempt.remove(&parameter.token_contract);
+    }
+    Ok(())
+}
+
+/// `set_swap_contract` function to let the admin set the contract that swaps
+/// the owners' shares for the payout tokens of their auctions, or unset it to
+/// pay all shares in CCD.
+#[receive(contract = "auction", name = "set_swap_contract", parameter = "Option<ContractAddress>", mutable, error = "BidError")]
+pub fn set_swap_contract(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), BidError> {
+    let swap_contract: Option<ContractAddress> = ctx.parameter_cursor().get()?;
+    ensure!(ctx.sender().matches_account(&host.state().admin), BidError::OnlyAdmin);
+
+    host.state_mut().swap_contract = swap_contract;
+    Ok(())
+}
+
+/// View function to return whether a token contract is exempt from the
+/// CIS-2 check.
+#[receive(contract = "auction", name = "view_cis0_exemption", parameter = "ContractAddress", return_value = "bool", error = "BidError")]
+pub fn view_cis0_exemption(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<bool, BidError> {
+    let token_contract: ContractAddress = ctx.parameter_cursor().get()?;
+    Ok(host.state().cis0_exempt.contains(&token_contract))
+}
+
+/// `set_max_active_auctions` function to let the admin change the maximum
+/// number of active auctions per account. Accounts above a lowered maximum
+/// keep their auctions, but cannot create new ones until they are below it.
+#[receive(contract = "auction", name = "set_max_active_auctions", parameter = "u32", mutable, error = "BidError")]
+pub fn set_max_active_auctions(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), BidError> {
+    let max_active_auctions: u32 = ctx.parameter_cursor().get()?;
+    ensure!(ctx.sender().matches_account(&host.state().admin), BidError::OnlyAdmin);
+
+    host.state_mut().max_active_auctions = max_active_auctions;
+    Ok(())
+}
+
+/// `set_max_auction_duration` function to let the admin change the maximum
+/// time from the creation of an auction to its end. Existing auctions keep
+/// their end.
+#[receive(contract = "auction", name = "set_max_auction_duration", parameter = "Duration", mutable, error = "BidError")]
+pub fn set_max_auction_duration(ctx: &impl HasReceiveContext, host: &mut Host<State>) -> Result<(), BidError> {
+    let max_auction_duration: Duration = ctx.parameter_cursor().get()?;
+    ensure!(ctx.sender().matches_account(&host.state().admin), BidError::OnlyAdmin);
+
+    host.state_mut().max_auction_duration = max_auction_duration;
+    Ok(())
+}
+
+/// The active auctions of an account and the maximum it can have.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct ActiveAuctions {
+    pub count: u32, // Auctions and listings of the account not settled yet
+    pub max:   u32, // Maximum number of active auctions per account
+}
+
+/// View function to return the number of active auctions of an account and
+/// the maximum, so the UI can warn before an auction is rejected.
+#[receive(contract = "auction", name = "view_active_auctions", parameter = "Address", return_value = "ActiveAuctions", error = "BidError")]
+pub fn view_active_auctions(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<ActiveAuctions, BidError> {
+    let owner: Address = ctx.parameter_cursor().get()?;
+    Ok(ActiveAuctions {
+        count: host.state().active_auction_count(&owner),
+        max:   host.state().max_active_auctions,
+    })
+}
+
+/// `schedule_commission_change` function to let the admin schedule a change
+/// of the commission tiers for new auctions, and of the recipients of the
+/// commission. The change must be effective no earlier than the commission
+/// change delay from now, and replaces any change scheduled before.
+#[receive(contract = "auction", name = "schedule_commission_change", parameter = "CommissionChange", enable_logger, mutable, error = "BidError")]
+pub fn schedule_commission_change(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let change: CommissionChange = ctx.parameter_cursor().get()?;
+    let state = host.state_mut();
+
+    ensure!(ctx.sender().matches_account(&state.admin), BidError::OnlyAdmin);
+    validate_commission_tiers(&change.commission_tiers)?;
+    if let Some(shares) = &change.commission_recipients {
+        validate_commission_shares(shares)?;
+    }
+    let earliest = ctx.metadata().slot_time().checked_add(state.commission_change_delay);
+    ensure!(earliest.is_some_and(|earliest| change.effective_at >= earliest), BidError::CommissionChangeTooSoon);
+
+    state.pending_commission_change = Some(change.clone());
+    logger.log(&AuctionEvent::CommissionChangeScheduled(change))?;
+    Ok(())
+}
+
+/// `cancel_commission_change` function to let the admin cancel the scheduled
+/// commission change.
+#[receive(contract = "auction", name = "cancel_commission_change", enable_logger, mutable, error = "BidError")]
+pub fn cancel_commission_change(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let state = host.state_mut();
+    ensure!(ctx.sender().matches_account(&state.admin), BidError::OnlyAdmin);
+
+    let change = state.pending_commission_change.take().ok_or(BidError::NoPendingCommissionChange)?;
+    logger.log(&AuctionEvent::CommissionChangeCancelled(change))?;
+    Ok(())
 }
 
-/// The state of the smart contract.
-#[derive(Debug, Serialize, SchemaType, Clone)]
-pub struct State {
-    auctions: Vec<Auction>,  // Array of auctions
-    commission_recipient: AccountAddress,
+/// `apply_commission_change` function to apply the scheduled commission
+/// change once it is effective. Anyone can apply it. Auctions listed before
+/// keep the tiers they were listed with, while new recipients receive the
+/// commission of every auction settled from then on.
+#[receive(contract = "auction", name = "apply_commission_change", enable_logger, mutable, error = "BidError")]
+pub fn apply_commission_change(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let state = host.state_mut();
+    let change = state.pending_commission_change.clone().ok_or(BidError::NoPendingCommissionChange)?;
+    ensure!(ctx.metadata().slot_time() >= change.effective_at, BidError::CommissionChangeNotDue);
+
+    state.commission_tiers = change.commission_tiers.clone();
+    if let Some(shares) = &change.commission_recipients {
+        state.commission_recipients = shares.clone();
+    }
+    state.pending_commission_change = None;
+    logger.log(&AuctionEvent::CommissionChangeApplied(change))?;
+    Ok(())
 }
 
-/// Type of the parameter to create a new auction.
-#[derive(Serialize, SchemaType)]
-pub struct NewAuctionParameter {
-    pub item: String,
-    pub end: Timestamp,
-    pub initial_price: u64,
-    pub token_contract: ContractAddress, // CIS-2 token contract address
-    pub token_id: TokenIdU8,              // CIS-2 token ID
-    pub token_amount: TokenAmountU64,              // Amount of tokens
+/// The commission for a hypothetical sale price.
+#[derive(Debug, Serialize, SchemaType, PartialEq, Eq)]
+pub struct CommissionQuote {
+    pub bps: u16,           // Commission rate applying to the price
+    pub commission: Amount, // Commission on the price
 }
 
-/// Type of the parameter to place a bid.
-#[derive(Serialize, SchemaType)]
-pub struct BidParameter {
-    pub auction_id: u32,  // ID of the auction to bid on
+/// View function to return the commission new auctions pay for a sale
+/// price, so it can be shown before listing.
+#[receive(contract = "auction", name = "view_commission_for", parameter = "Amount", return_value = "CommissionQuote", error = "BidError")]
+pub fn view_commission_for(ctx: &impl HasReceiveContext, host: &Host<State>) -> Result<CommissionQuote, BidError> {
+    let price: Amount = ctx.parameter_cursor().get()?;
+    let bps = commission_rate(&host.state().commission_tiers, price);
+    Ok(CommissionQuote {
+        bps,
+        commission: commission_of(price, bps),
+    })
 }
 
-/// Errors for bidding function.
-#[derive(Debug, PartialEq, Eq, Clone, Reject, Serialize, SchemaType)]
-pub enum BidError {
-    OnlyAccount,
-    BidBelowCurrentBid,
-    BidBelowMinimumRaise,
-    BidTooLate,
-    AuctionAlreadyFinalized,
-    AuctionNotFound,
-    ParameterParsingError,
-    AuctionStillActive,
-    TransferFailed,
-    OnlyNotOwner
+/// View function to return the scheduled commission change, if any.
+#[receive(contract = "auction", name = "view_pending_commission_change", return_value = "Option<CommissionChange>", error = "BidError")]
+pub fn view_pending_commission_change(
+    _ctx: &impl HasReceiveContext,
+    host: &Host<State>,
+) -> Result<Option<CommissionChange>, BidError> {
+    Ok(host.state().pending_commission_change.clone())
 }
 
-/// `create_auction` function to add a new auction to the array.
-#[receive(contract = "auction", name = "create_auction", parameter = "NewAuctionParameter", enable_logger, mutable)]
-pub fn create_auction(
+/// `rescue_tokens` function to let the admin send out CIS-2 tokens that were
+/// sent to the contract by mistake. Only tokens beyond those escrowed for
+/// live auctions can be rescued.
+#[receive(contract = "auction", name = "rescue_tokens", parameter = "RescueParameter", enable_logger, mutable, error = "BidError")]
+pub fn rescue_tokens(
     ctx: &impl HasReceiveContext,
     host: &mut Host<State>,
     logger: &mut impl HasLogger,
 ) -> Result<(), BidError> {
-    let parameter: NewAuctionParameter = ctx.parameter_cursor().get().map_err(|_| BidError::ParameterParsingError)?;
+    let parameter: RescueParameter = ctx.parameter_cursor().get()?;
 
-    let owner = match ctx.sender() {
-        Address::Account(account_address) => account_address,
-        _ => return Err(BidError::OnlyAccount), // Only accounts can create auctions
-    };
+    ensure!(ctx.sender().matches_account(&host.state().admin), BidError::OnlyAdmin);
 
-    // Transfer CIS-2 tokens from the auction creator to the contract
-     let transfer = Transfer {
+    let client = Cis2Client::new(parameter.token_contract);
+    let balance: Result<TokenAmountU64, Cis2ClientError<()>> =
+        client.balance_of(host, parameter.token_id, Address::Contract(ctx.self_address()));
+    let balance = balance.map_err(|_| BidError::TransferFailed)?;
+    let escrowed = host.state().escrowed_amount(parameter.token_contract, parameter.token_id);
+    let unescrowed = balance.0.saturating_sub(escrowed.0);
+    ensure!(parameter.amount.0 <= unescrowed, BidError::RescueExceedsUnescrowed);
+
+    let to = parameter.to.address();
+    let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
         token_id: parameter.token_id,
-        amount: parameter.token_amount,
-        from: Address::Account(owner),
-        to: Receiver::from_contract(ctx.self_address(), OwnedEntrypointName::new_unchecked("onReceivingCIS2".to_string())),
+        amount: parameter.amount,
+        from: Address::Contract(ctx.self_address()),
+        to: parameter.to,
         data: AdditionalData::empty(),
     };
-
-    let client = Cis2Client::new(ContractAddress::new(parameter.token_contract.index, parameter.token_contract.subindex));
     let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
-    // if let Err(err) = &result {
-    //     logger.log(&format!("Transfer failed: {:?}", err)).map_err(|_| BidError::TransferFailed)?;
-    //     return Err(BidError::TransferFailed);
-    // }
+    result.map_err(|_| BidError::TransferFailed)?;
 
-    let auction = Auction {
-        auction_state: AuctionState::NotSoldYet,
-        highest_bidder: None,
-        initial_price: parameter.initial_price,
-        highest_bid: Amount::zero(),
-        item: parameter.item,
-        end: parameter.end,
-        owner,
-        token_contract: parameter.token_contract,
-        token_id: parameter.token_id,
-        token_amount: parameter.token_amount,
+    logger
+        .log(&AuctionEvent::Rescue(RescueEventData {
+            token_contract: parameter.token_contract,
+            token_id: parameter.token_id,
+            amount: parameter.amount,
+            to,
+        }))?;
+    Ok(())
+}
+
+/// `cancel_with_fee` function to let the owner cancel an auction before its
+/// end. If the auction has bids, the owner must attach exactly the
+/// cancellation fee, which goes to the highest bidder along with their bid.
+/// Without bids no fee is due, so fixed-price listings are cancelled for
+/// free. The tokens are returned to the owner.
+#[receive(contract = "auction", name = "cancel_with_fee", parameter = "AuctionIdParameter", payable, enable_logger, mutable, error = "BidError")]
+pub fn cancel_with_fee(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    amount: Amount,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let fee_bps = host.state().cancellation_fee_bps;
+    let auction = host.state().auction(parameter.auction_id)?;
+
+    ensure!(ctx.sender() == auction.owner, BidError::OnlyOwner);
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    ensure!(auction_phase(&auction, ctx.metadata().slot_time()) == Phase::Bidding, BidError::AuctionEnded);
+    ensure!(!auction.kind.is_reverse(), BidError::WrongAuctionKind);
+
+    let fee = match auction.highest_bidder {
+        Some(_) => basis_points_of(auction.highest_bid, fee_bps),
+        None => Amount::zero(),
     };
+    ensure_eq!(amount, fee, BidError::WrongCancellationFee);
 
-    // Add the new auction to the array
     let state = host.state_mut();
-    state.auctions.push(auction);
+    state.set_auction_state(parameter.auction_id, AuctionState::Cancelled);
+    if let Some(bidder) = auction.highest_bidder {
+        state.untrack_highest_bid(&bidder, parameter.auction_id);
+        state.standing_bids_total -= held_ccd(&auction);
 
-    // Return the ID of the newly created auction
-    let id = (state.auctions.len() - 1) as u32;
-    logger.log(&AuctionEvent::Register(AuctionEventData { auction_id: id })).map_err(|_| BidError::TransferFailed)?;
-    Ok(())
+        // Pay back the bid with the fee, or keep it claimable if the transfer
+        // fails. A bid in the alternative bid token is returned in the token,
+        // with the fee in CCD.
+        refund_bid_tokens(ctx, host, logger, parameter.auction_id, &auction, RefundReason::Cancelled)?;
+        let payment = held_ccd(&auction) + fee;
+        if host.invoke_transfer(&bidder, payment).is_err() {
+            host.state_mut().credit_refund(bidder, payment);
+        } else {
+            log_refund(logger, Some(parameter.auction_id), bidder, payment, RefundReason::Cancelled)?;
+        }
+    }
+
+    // Return CIS-2 tokens to the owner, unless they never arrived
+    if auction.escrow_confirmed {
+        host.state_mut().release_auction_escrow(parameter.auction_id, TokenAmountU64(0))?;
+        let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+            token_id: auction.token_id,
+            amount: auction.amount_remaining,
+            from: Address::Contract(ctx.self_address()),
+            to: owner_receiver(auction.owner),
+            data: AdditionalData::empty(),
+        };
+        let client = Cis2Client::new(auction.token_contract);
+        let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+        result.map_err(|_| BidError::TransferFailed)?;
+    }
+
+    logger
+        .log(&AuctionEvent::Cancel(CancelEventData {
+            auction_id: parameter.auction_id,
+            bidder: auction.highest_bidder,
+            refund: held_ccd(&auction),
+            fee,
+        }))?;
+    log_item_event(logger, AuctionEvent::ItemUnlisted, parameter.auction_id, &auction, Amount::zero(), auction.owner)
 }
 
-/// Function to handle receiving CIS-2 tokens.
-#[receive(contract = "auction", name = "onReceivingCIS2", mutable)]
-pub fn on_receiving_cis2(
+/// `retract_bid` function to let the highest bidder withdraw the bid before
+/// the retraction cutoff ahead of the end. The bid is refunded minus the
+/// retraction penalty, which goes to the commission recipients. Outbid
+/// bidders have been refunded already, so their bids no longer back the
+/// auction: the backup bid becomes the highest bid if there is one, and
+/// otherwise the auction reverts to having no bids and the initial price
+/// applies to the next bid again.
+#[receive(contract = "auction", name = "retract_bid", parameter = "AuctionIdParameter", enable_logger, mutable, error = "BidError")]
+pub fn retract_bid(
     ctx: &impl HasReceiveContext,
-    host: &mut Host<State>
-) -> Result<(), ()> {
-    // // Get information about received tokens
-    // let params: OnReceivingCis2Params<ContractTokenId, ContractTokenAmount> = ctx.parameter_cursor().get().map_err(|_| ())?;
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let penalty_bps = host.state().retraction_penalty_bps;
+    let cutoff = host.state().retraction_cutoff;
+    let auction = host.state().auction(parameter.auction_id)?;
+
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    ensure!(!auction.kind.is_reverse(), BidError::WrongAuctionKind);
+    let bidder = match (ctx.sender(), auction.highest_bidder) {
+        (Address::Account(sender), Some(bidder)) if sender == bidder => bidder,
+        _ => bail!(BidError::OnlyHighestBidder),
+    };
+    let retractable = ctx.metadata().slot_time().checked_add(cutoff).is_some_and(|time| time <= auction.end);
+    ensure!(retractable, BidError::RetractionTooLate);
+    // The penalty is kept in CCD, so bids in the alternative bid token stand
+    ensure!(auction.token_bid.is_none(), BidError::TokenBidNotRetractable);
 
-    // // Get the token contract that sent the tokens
-    // let token_contract = match ctx.sender() {
-    //     Address::Contract(contract) => contract,
-    //     _ => return Ok(()), // Non-contract senders are ignored
-    // };
+    let state = host.state_mut();
+    let mut stored = auction.clone();
+    stored.highest_bidder = None;
+    stored.beneficiary = None;
+    stored.highest_bid = Amount::zero();
+    if let AuctionKind::SecondPrice { second_bid } = &mut stored.kind {
+        *second_bid = Amount::zero();
+    }
+    state.store_auction(parameter.auction_id, stored);
+    state.untrack_highest_bid(&bidder, parameter.auction_id);
+    state.balance_funded_bids.remove(&parameter.auction_id);
+    state.standing_bids_total -= auction.highest_bid;
+    let (state, state_builder) = host.state_and_builder();
+    let promoted = state.promote_backup_bid(state_builder, parameter.auction_id);
 
-    // // Find the auction that matches the token contract and token ID
-    // let state = host.state_mut();
-    // if let Some(auction) = state.auctions.iter_mut().find(|auction| {
-    //     auction.token_contract == token_contract && auction.token_id == params.token_id
-    // }) {
-    //     // Assign the received tokens to the auction
-    //     auction.token_amount = params.amount;
-    // } else {
-    //     // If no matching auction is found, return an error
-    //     return Err(());
-    // }
+    let penalty = commission_of(auction.highest_bid, penalty_bps);
+    let penalty_payouts = match penalty > Amount::zero() {
+        true => split_commission(penalty, &state.commission_recipients),
+        false => Vec::new(),
+    };
+    let penalty_payouts = pay_commission(host, logger, parameter.auction_id, penalty_payouts)?;
+    // Refund the rest of the bid, or keep it claimable if the transfer fails
+    let refund = auction.highest_bid - penalty;
+    if host.invoke_transfer(&bidder, refund).is_err() {
+        host.state_mut().credit_refund(bidder, refund);
+    } else {
+        log_refund(logger, Some(parameter.auction_id), bidder, refund, RefundReason::Retracted)?;
+    }
 
+    logger
+        .log(&AuctionEvent::Retract(RetractEventData {
+            auction_id: parameter.auction_id,
+            bidder,
+            bid: auction.highest_bid,
+            penalty_payouts,
+        }))?;
+    if let Some(backup) = promoted {
+        log_backup_promoted(logger, parameter.auction_id, &backup)?;
+    }
     Ok(())
 }
 
-/// `bid` function to place a bid on a specific auction.
-#[receive(contract = "auction", name = "bid", parameter = "BidParameter", payable, mutable, error = "BidError")]
-pub fn auction_bid(
+/// `reclaim_expired` function to let the highest bidder and the owner of an
+/// auction that was never finalized take back their bid and tokens once the
+/// grace period after its end is over. Each of them reclaims with their own
+/// call, and the auction can no longer be finalized after the first one. The
+/// owner of a reverse auction whose tokens were never delivered reclaims the
+/// budget.
+#[receive(contract = "auction", name = "reclaim_expired", parameter = "AuctionIdParameter", enable_logger, mutable, error = "BidError")]
+pub fn reclaim_expired(
     ctx: &impl HasReceiveContext,
-    host: &mut Host<State>,  // Use &mut Host<State> for state-modifying functions
-    amount: Amount,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
 ) -> Result<(), BidError> {
-    let parameter: BidParameter = ctx.parameter_cursor().get().map_err(|_| BidError::ParameterParsingError)?;
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let grace_period = host.state().reclaim_grace_period;
+    let auction = host.state().auction(parameter.auction_id)?;
+    if let AuctionKind::Reverse { budget } = auction.kind {
+        return reclaim_reverse_budget(ctx, host, logger, parameter.auction_id, &auction, budget, grace_period);
+    }
+    // An auction without tokens has nothing to reclaim and is closed by `finalize`
+    ensure!(auction.escrow_confirmed, BidError::EscrowNotConfirmed);
 
-    // Get mutable access to the auction, and ensure it exists
-    let auction = {
-        let auctions = &mut host.state_mut().auctions;
-        auctions.get_mut(parameter.auction_id as usize).ok_or(BidError::AuctionNotFound)?
+    let (mut bid_reclaimed, mut tokens_reclaimed) = match auction.auction_state {
+        AuctionState::NotSoldYet => (auction.highest_bidder.is_none(), false),
+        AuctionState::Lapsed { bid_reclaimed, tokens_reclaimed } => (bid_reclaimed, tokens_reclaimed),
+        _ => bail!(BidError::AuctionAlreadyFinalized),
     };
 
-    // Ensure the auction has not been finalized yet
-    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
-
     let slot_time = ctx.metadata().slot_time();
-    // Ensure the auction has not ended yet
-    ensure!(slot_time <= auction.end, BidError::BidTooLate);
-
-    // Ensure that only accounts can place a bid
-    let sender_address = match ctx.sender() {
-        Address::Contract(_) => bail!(BidError::OnlyAccount),
-        Address::Account(account_address) => account_address,
-    };
+    let reclaimable = auction.end.checked_add(grace_period).is_some_and(|from| slot_time >= from);
+    ensure!(reclaimable, BidError::GracePeriodNotOver);
 
-    ensure!(auction.owner != sender_address, BidError::OnlyNotOwner);
+    // The highest bidder reclaims their bid, the owner the tokens
+    let sender = ctx.sender();
+    let bidder = auction.highest_bidder.filter(|bidder| sender.matches_account(bidder) && !bid_reclaimed);
 
-    // Check if the current highest bid is zero
-    if auction.highest_bid == Amount::zero() {
-        // Ensure the bid is greater than the initial price
-        ensure!(amount.micro_ccd > auction.initial_price, BidError::BidBelowCurrentBid);
+    let mut refund = Amount::zero();
+    let mut tokens = TokenAmountU64(0);
+    if let Some(bidder) = bidder {
+        bid_reclaimed = true;
+        refund = held_ccd(&auction);
+        let state = host.state_mut();
+        state.untrack_highest_bid(&bidder, parameter.auction_id);
+        state.standing_bids_total -= refund;
+    } else if auction.owner == sender && !tokens_reclaimed {
+        tokens_reclaimed = true;
+        tokens = auction.amount_remaining;
+        host.state_mut().release_auction_escrow(parameter.auction_id, TokenAmountU64(0))?;
     } else {
-        // Ensure that the new bid exceeds the current highest bid
-        ensure!(amount > auction.highest_bid, BidError::BidBelowCurrentBid);
+        bail!(BidError::NothingToClaim);
     }
+    host.state_mut().set_auction_state(parameter.auction_id, AuctionState::Lapsed {
+        bid_reclaimed,
+        tokens_reclaimed,
+    });
 
-    // Extract necessary fields from `auction` before releasing mutable borrow
-    let previous_highest_bid = auction.highest_bid;
-    let prev_highest_bidder = auction.highest_bidder.take();
-
-    // Update auction with new highest bid and highest bidder
-    auction.highest_bid = amount;
-    auction.highest_bidder = Some(sender_address);
+    if let (Some(bidder), true) = (bidder, refund > Amount::zero()) {
+        host.invoke_transfer(&bidder, refund)?;
+        log_refund(logger, Some(parameter.auction_id), bidder, refund, RefundReason::Reclaimed)?;
+    }
+    if bidder.is_some() {
+        refund_bid_tokens(ctx, host, logger, parameter.auction_id, &auction, RefundReason::Reclaimed)?;
+    }
+    if tokens.0 > 0 {
+        let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+            token_id: auction.token_id,
+            amount: tokens,
+            from: Address::Contract(ctx.self_address()),
+            to: owner_receiver(sender),
+            data: AdditionalData::empty(),
+        };
+        let client = Cis2Client::new(auction.token_contract);
+        let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+        result.map_err(|_| BidError::TransferFailed)?;
+    }
 
-    // Refund previous highest bidder, if any
-    if let Some(prev_bidder) = prev_highest_bidder {
-        // Refund the previous highest bid
-        host.invoke_transfer(&prev_bidder, previous_highest_bid).unwrap_abort();
+    logger
+        .log(&AuctionEvent::Reclaim(ReclaimEventData {
+            auction_id: parameter.auction_id,
+            account: sender,
+            refund,
+            tokens,
+        }))?;
+    if tokens.0 > 0 {
+        log_item_event(logger, AuctionEvent::ItemReturned, parameter.auction_id, &auction, Amount::zero(), auction.owner)?;
     }
+    Ok(())
+}
+
+/// Refunds the budget of a reverse auction whose tokens were never delivered
+/// to the owner once the grace period after its end is over.
+fn reclaim_reverse_budget(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    auction: &Auction,
+    budget: Amount,
+    grace_period: Duration,
+) -> Result<(), BidError> {
+    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    let reclaimable = auction.end.checked_add(grace_period).is_some_and(|from| ctx.metadata().slot_time() >= from);
+    ensure!(reclaimable, BidError::GracePeriodNotOver);
+    ensure!(ctx.sender() == auction.owner, BidError::NothingToClaim);
 
+    let state = host.state_mut();
+    state.set_auction_state(auction_id, AuctionState::Lapsed {
+        bid_reclaimed:    true,
+        tokens_reclaimed: true,
+    });
+    state.reverse_budgets_total -= budget;
+
+    pay_owner(host, logger, auction_id, auction.owner, budget, PayoutKind::Budget)?;
+    logger
+        .log(&AuctionEvent::Reclaim(ReclaimEventData {
+            auction_id,
+            account: auction.owner,
+            refund: budget,
+            tokens: TokenAmountU64(0),
+        }))?;
     Ok(())
 }
 
-/// View function to return the array of auctions.
-#[receive(contract = "auction", name = "view_auctions", return_value = "Vec<Auction>")]
-pub fn view_auctions(_ctx: &impl HasReceiveContext, host: &Host<State>) -> ReceiveResult<Vec<Auction>> {
-    Ok(host.state().auctions.clone())
+/// Settles an ended auction which has not been finalized yet: the tokens go
+/// to the highest bidder (or the beneficiary they bid for) and the highest
+/// bid is split between the commission recipients and the owner, or the
+/// tokens are returned to the owner if there were no bids. If the tokens
+/// cannot be delivered to the winner, the auction is left pending delivery.
+/// The caller is responsible for checking that the auction has ended and is
+/// still `NotSoldYet`.
+fn settle_auction(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+) -> Result<(), BidError> {
+    let mut auction = host.state().auction(auction_id)?;
+
+    // The backup bid is no longer needed once the auction is settled, but
+    // still counts towards the second price
+    if let Some(backup) = host.state_mut().take_backup_bid(auction_id) {
+        count_backup_bid(&mut auction, &backup);
+        host.state_mut().store_auction(auction_id, auction.clone());
+        refund_backup_bid(host, logger, auction_id, backup, RefundReason::BackupReleased)?;
+    }
+
+    if let Some(winning_bidder) = auction.highest_bidder {
+        let winner = auction.beneficiary.unwrap_or(winning_bidder);
+        let state = host.state_mut();
+        state.set_auction_state(auction_id, AuctionState::PendingDelivery(winner));
+        state.untrack_highest_bid(&winning_bidder, auction_id);
+
+        if !deliver_and_pay_out(ctx, host, logger, auction_id, winner)? {
+            let _ = host.state_mut().settlements.insert(auction_id, SettlementRecord {
+                finalizer: ctx.sender(),
+                settled_at: ctx.metadata().slot_time(),
+                seller_amount: Amount::zero(),
+                commission: Amount::zero(),
+                finalize_reward: Amount::zero(),
+                tokens_delivered: false,
+            });
+            logger.log(&AuctionEvent::DeliveryPending(AuctionEventData { auction_id }))?;
+        }
+        return Ok(());
+    }
+
+    let state = host.state_mut();
+    state.set_auction_state(auction_id, AuctionState::Unsold);
+    state.release_auction_escrow(auction_id, TokenAmountU64(0))?;
+
+    // Return CIS-2 tokens to the auction creator, or keep them for the owner
+    // to reclaim with `reclaim_unsold_remainder` if the transfer fails
+    let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+        token_id: auction.token_id,
+        amount: auction.amount_remaining,
+        from: Address::Contract(ctx.self_address()),
+        to: owner_receiver(auction.owner),
+        data: AdditionalData::empty(),
+    };
+
+    let client = Cis2Client::new(ContractAddress::new(auction.token_contract.index, auction.token_contract.subindex));
+    let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    let returned = result.is_ok();
+    if !returned {
+        host.state_mut().defer_return(auction_id, auction.amount_remaining)?;
+    }
+
+    logger
+        .log(&AuctionEvent::Finalize(FinalizeEventData {
+            auction_id,
+            finalizer: ctx.sender(),
+            commission_payouts: Vec::new(),
+            finalize_reward: Amount::zero(),
+            price: Amount::zero(),
+            winner: None,
+            settled_at: ctx.metadata().slot_time(),
+        }))?;
+    if !returned {
+        logger.log(&AuctionEvent::ReturnDeferred(ReturnDeferredEventData {
+            auction_id,
+            owner: auction.owner,
+            amount: auction.amount_remaining,
+        }))?;
+        return Ok(());
+    }
+    log_item_event(logger, AuctionEvent::ItemReturned, auction_id, &auction, Amount::zero(), auction.owner)
 }
 
-/// `get_auction` function to fetch a specific auction by its ID as a view function.
-#[receive(contract = "auction", name = "get_auction", parameter = "BidParameter", return_value = "Auction")]
-pub fn get_auction(
+/// `reclaim_unsold_remainder` function to let the owner of an unsold auction
+/// take back the tokens that could not be returned when it was finalized.
+#[receive(contract = "auction", name = "reclaim_unsold_remainder", parameter = "AuctionIdParameter", enable_logger, mutable, error = "BidError")]
+pub fn reclaim_unsold_remainder(
     ctx: &impl HasReceiveContext,
-    host: &Host<State>,
-) -> ReceiveResult<Auction> {
-    let parameter: BidParameter = ctx.parameter_cursor().get().map_err(|_| BidError::ParameterParsingError)?;
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let auction = host.state().auction(parameter.auction_id)?;
 
-    // Get immutable access to the auction, and ensure it exists
-    let auction = host
-        .state()
-        .auctions
-        .get(parameter.auction_id as usize)
-        .ok_or(BidError::AuctionNotFound)?;
+    ensure!(ctx.sender() == auction.owner, BidError::OnlyOwner);
+    ensure!(
+        auction.auction_state == AuctionState::Unsold && auction.amount_remaining.0 > 0,
+        BidError::NothingToClaim
+    );
+
+    let state = host.state_mut();
+    state.release_auction_escrow(parameter.auction_id, TokenAmountU64(0))?;
+    state.pending_returns.remove(&parameter.auction_id);
+
+    let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+        token_id: auction.token_id,
+        amount: auction.amount_remaining,
+        from: Address::Contract(ctx.self_address()),
+        to: owner_receiver(auction.owner),
+        data: AdditionalData::empty(),
+    };
+    let client = Cis2Client::new(auction.token_contract);
+    let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    result.map_err(|_| BidError::TransferFailed)?;
 
-    Ok(auction.clone())
+    log_item_event(logger, AuctionEvent::ItemReturned, parameter.auction_id, &auction, Amount::zero(), auction.owner)
 }
 
-/// `finalize` function to finalize a specific auction.
-#[receive(contract = "auction", name = "finalize", parameter = "BidParameter", enable_logger, mutable, error = "BidError")]
-pub fn auction_finalize(ctx: &impl HasReceiveContext, host: &mut Host<State>, logger: &mut impl HasLogger,) -> Result<(), BidError> {
-    let parameter: BidParameter = ctx.parameter_cursor().get().map_err(|_| BidError::ParameterParsingError)?;
+/// Transfers the tokens of an auction pending delivery to the winner and,
+/// only if that succeeds, marks the auction as sold, pays out the charged
+/// price (including the finalize reward to the sender), refunds the rest of
+/// the highest bid and logs the `Finalize` event. Returns whether the tokens
+/// were delivered; if not, the auction is left unchanged.
+fn deliver_and_pay_out(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    winner: AccountAddress,
+) -> Result<bool, BidError> {
+    let auction = host.state().auction(auction_id)?;
 
-    let commission_recipient = host.state().commission_recipient;
-    let auction = host.state().auctions.get(parameter.auction_id as usize).ok_or(BidError::AuctionNotFound)?.clone();
-    // let auction = {
-    //     let auctions = &mut host.state_mut().auctions;
-    //     auctions.get_mut(parameter.auction_id as usize).ok_or(BidError::AuctionNotFound)?
-    // };
+    // Transfer CIS-2 tokens to the winner
+    let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
+        token_id: auction.token_id,
+        amount: auction.amount_remaining,
+        from: Address::Contract(ctx.self_address()),
+        to: Receiver::from_account(winner),
+        data: AdditionalData::empty(),
+    };
+    let client = Cis2Client::new(auction.token_contract);
+    let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    if result.is_err() {
+        return Ok(false);
+    }
+    if auction.token_bid.is_some() {
+        pay_out_bid_tokens(ctx, host, logger, auction_id, &auction, winner)?;
+        return Ok(true);
+    }
 
-    ensure_eq!(auction.auction_state, AuctionState::NotSoldYet, BidError::AuctionAlreadyFinalized);
+    let keeper = match ctx.sender() {
+        Address::Account(account_address) => Some(account_address),
+        Address::Contract(_) => None,
+    };
+    let PayoutSplit {
+        price,
+        commission: commission_amount,
+        commission_payouts,
+        finalize_reward,
+        owner_amount,
+        refund,
+    } = payout_split(&auction, &host.state().commission_recipients, keeper)?;
 
-    let slot_time = ctx.metadata().slot_time();
-    ensure!(slot_time > auction.end, BidError::AuctionStillActive);
+    let state = host.state_mut();
+    state.set_auction_state(auction_id, AuctionState::Sold {
+        winner,
+        price,
+        settled_at: ctx.metadata().slot_time(),
+    });
+    state.release_auction_escrow(auction_id, auction.amount_remaining)?;
+    state.standing_bids_total -= auction.highest_bid;
+    state.stats.record_sale(price, commission_amount);
 
-    if let Some(winning_bidder) = auction.highest_bidder {
-        let commission = auction.highest_bid.micro_ccd / 10;
-        let commission_amount = Amount::from_micro_ccd(commission);
-        let owner_amount = auction.highest_bid - commission_amount;
+    let commission_payouts = pay_commission(host, logger, auction_id, commission_payouts)?;
+    if let (Some(keeper), true) = (keeper, finalize_reward > Amount::zero()) {
+        pay_or_defer(host, logger, auction_id, keeper, finalize_reward, PayoutKind::FinalizeReward)?;
+    }
+    pay_out_owner(host, logger, auction_id, &auction, owner_amount)?;
+    if let (Some(bidder), true) = (auction.highest_bidder, refund > Amount::zero()) {
+        // Refund the rest of the bid, or keep it claimable if the transfer fails
+        if host.invoke_transfer(&bidder, refund).is_err() {
+            host.state_mut().credit_refund(bidder, refund);
+        } else {
+            log_refund(logger, Some(auction_id), bidder, refund, RefundReason::Overpaid)?;
+        }
+    }
+    let _ = host.state_mut().settlements.insert(auction_id, SettlementRecord {
+        finalizer: ctx.sender(),
+        settled_at: ctx.metadata().slot_time(),
+        seller_amount: owner_amount,
+        commission: commission_amount,
+        finalize_reward,
+        tokens_delivered: true,
+    });
 
-        // auction.auction_state = AuctionState::Sold(winning_bidder);
+    logger
+        .log(&AuctionEvent::Finalize(FinalizeEventData {
+            auction_id,
+            finalizer: ctx.sender(),
+            commission_payouts,
+            finalize_reward,
+            price,
+            winner: Some(winner),
+            settled_at: ctx.metadata().slot_time(),
+        }))?;
+    log_item_event(logger, AuctionEvent::ItemSold, auction_id, &auction, price, Address::Account(winner))?;
+    Ok(true)
+}
 
-        // Transfer CIS-2 tokens to the highest bidder
-        let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
-            token_id: auction.token_id,
-            amount: auction.token_amount.into(),
-            from: Address::Contract(ctx.self_address()),
-            to: Receiver::from_account(winning_bidder),
-            data: AdditionalData::empty(),
-        };
-        let client = Cis2Client::new(ContractAddress::new(auction.token_contract.index, auction.token_contract.subindex));
-        let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+/// Pays out a winning bid placed in the alternative bid token, once the
+/// tokens of the auction were delivered, and marks the auction as sold. The
+/// commission is taken in the token, at the rate for the value of the bid,
+/// and split between the commission recipients like a CCD commission; the
+/// rest goes to the owner. Contract sinks receive their share at their
+/// entrypoint. Tokens a recipient does not take are kept for it to claim
+/// with `claim_tokens`, so that no recipient can block the settlement. No
+/// finalize reward is paid, as it is paid in CCD, and the sale counts
+/// towards the statistics without adding CCD volume.
+fn pay_out_bid_tokens(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    auction: &Auction,
+    winner: AccountAddress,
+) -> Result<(), BidError> {
+    let (Some(alt_bid_token), Some(tokens)) = (auction.alt_bid_token, auction.token_bid) else {
+        return Ok(());
+    };
+    let (commission_tokens, owner_tokens) = token_payout_split(auction, tokens);
+    let commission_payouts = split_token_commission(commission_tokens, &host.state().commission_recipients);
 
-        logger.log(&format!("{:?}", result)).map_err(|_| BidError::TransferFailed)?;
+    let slot_time = ctx.metadata().slot_time();
+    let state = host.state_mut();
+    state.set_auction_state(auction_id, AuctionState::Sold {
+        winner,
+        price: auction.highest_bid,
+        settled_at: slot_time,
+    });
+    state.release_auction_escrow(auction_id, auction.amount_remaining)?;
+    state.release_escrow(alt_bid_token.contract, alt_bid_token.token_id, tokens);
+    state.stats.record_sale(Amount::zero(), Amount::zero());
+    let _ = state.settlements.insert(auction_id, SettlementRecord {
+        finalizer: ctx.sender(),
+        settled_at: slot_time,
+        seller_amount: Amount::zero(),
+        commission: Amount::zero(),
+        finalize_reward: Amount::zero(),
+        tokens_delivered: true,
+    });
 
-        host.invoke_transfer(&commission_recipient, commission_amount).map_err(|_| BidError::TransferFailed)?;
-        host.invoke_transfer(&auction.owner, owner_amount).map_err(|_| BidError::TransferFailed)?;
-    } else {
-        // Return CIS-2 tokens to the auction creator
-        let transfer: Transfer<TokenIdU8, TokenAmountU64> = Transfer {
-            token_id: auction.token_id,
-            amount: auction.token_amount.into(),
-            from: Address::Contract(ctx.self_address()),
-            to: Receiver::from_account(auction.owner),
-            data: AdditionalData::empty(),
+    for (recipient, payout) in commission_payouts {
+        if payout.0 == 0 {
+            continue;
+        }
+        let to = match recipient {
+            CommissionSink::Account(account) => Receiver::from_account(account),
+            CommissionSink::Contract { address, entrypoint } => Receiver::from_contract(address, entrypoint),
         };
+        pay_tokens_or_defer(ctx, host, logger, auction_id, &alt_bid_token, to, payout)?;
+    }
+    if owner_tokens.0 > 0 {
+        pay_tokens_or_defer(ctx, host, logger, auction_id, &alt_bid_token, owner_receiver(auction.owner), owner_tokens)?;
+    }
 
-        let client = Cis2Client::new(ContractAddress::new(auction.token_contract.index, auction.token_contract.subindex));
-        let result: Result<bool, Cis2ClientError<()>> = client.transfer(host, transfer);
+    logger
+        .log(&AuctionEvent::TokenPayout(TokenPayoutEventData {
+            auction_id,
+            owner: auction.owner,
+            owner_tokens,
+            commission_tokens,
+        }))?;
+    logger
+        .log(&AuctionEvent::Finalize(FinalizeEventData {
+            auction_id,
+            finalizer: ctx.sender(),
+            commission_payouts: Vec::new(),
+            finalize_reward: Amount::zero(),
+            price: auction.highest_bid,
+            winner: Some(winner),
+            settled_at: slot_time,
+        }))?;
+    log_item_event(logger, AuctionEvent::ItemSold, auction_id, auction, auction.highest_bid, Address::Account(winner))
+}
+
+/// How the highest bid of a sold auction is paid out.
+struct PayoutSplit {
+    price:              Amount, // Charged to the winner
+    commission:         Amount,
+    commission_payouts: Vec<(CommissionSink, Amount)>,
+    finalize_reward:    Amount,
+    owner_amount:       Amount,
+    refund:             Amount, // Rest of the highest bid, returned to the winner
+}
+
+/// Splits the highest bid in CCD of an ended auction with bids, as paid out
+/// by `finalize` and previewed by `view_settlement_preview`. The winner is
+/// charged the price, and any rest of the bid is refunded. The finalize
+/// reward is paid to `keeper`, if the finalizer is an account, and comes out
+/// of the owner's share. It is capped to that share, so that a commission
+/// rate close to the whole price leaves the reward smaller or zero rather
+/// than paying out more than the price.
+fn payout_split(
+    auction: &Auction,
+    recipients: &[CommissionShare],
+    keeper: Option<AccountAddress>,
+) -> Result<PayoutSplit, BidError> {
+    let price = charged_price(auction);
+    let commission = commission_for(&auction.commission_tiers, price);
+    let after_commission = price.checked_sub(commission).ok_or(BidError::ArithmeticOverflow)?;
+    let finalize_reward = match keeper {
+        Some(_) => auction.finalize_reward.min(max_finalize_reward(price)).min(after_commission),
+        None => Amount::zero(),
+    };
+    Ok(PayoutSplit {
+        price,
+        commission,
+        commission_payouts: split_commission(commission, recipients),
+        finalize_reward,
+        owner_amount: after_commission.checked_sub(finalize_reward).ok_or(BidError::ArithmeticOverflow)?,
+        refund: Amount::from_micro_ccd(auction.highest_bid.micro_ccd.saturating_sub(price.micro_ccd)),
+    })
+}
+
+/// Splits `tokens` of a winning bid in the alternative bid token into the
+/// commission, at the rate for the value of the bid, and the owner's share.
+fn token_payout_split(auction: &Auction, tokens: TokenAmountU64) -> (TokenAmountU64, TokenAmountU64) {
+    let rate = commission_rate(&auction.commission_tiers, auction.highest_bid);
+    let commission = TokenAmountU64((u128::from(tokens.0) * u128::from(rate) / u128::from(TOTAL_BASIS_POINTS)) as u64);
+    (commission, TokenAmountU64(tokens.0 - commission.0))
+}
+
+/// The receiver of tokens returned or delivered to an auction owner.
+/// Contract owners receive them through their `onReceivingCIS2` hook.
+fn owner_receiver(owner: Address) -> Receiver {
+    match owner {
+        Address::Account(account) => Receiver::from_account(account),
+        Address::Contract(contract) => Receiver::from_contract(contract, ON_RECEIVING_CIS2.to_owned()),
+    }
+}
+
+/// Pays the shares of a commission of an auction, or of a retraction penalty.
+/// Accounts are paid right away, and contract sinks are invoked with their
+/// share. A recipient failing to take its share does not block the rest of
+/// the settlement: the share of an account is kept for it to claim with
+/// `claim_refund`, and the share of a contract sink is credited to it as
+/// proceeds, to claim with `claim_proceeds`. Returns the payouts transferred
+/// to accounts, for the `Finalize` or `Retract` event.
+fn pay_commission(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    payouts: Vec<(CommissionSink, Amount)>,
+) -> Result<Vec<(AccountAddress, Amount)>, BidError> {
+    let mut account_payouts = Vec::new();
+    for (recipient, payout) in payouts {
+        match recipient {
+            CommissionSink::Account(account) => {
+                if pay_or_defer(host, logger, auction_id, account, payout, PayoutKind::Commission)? {
+                    account_payouts.push((account, payout));
+                }
+            }
+            CommissionSink::Contract { address, entrypoint } => {
+                if payout == Amount::zero() {
+                    continue;
+                }
+                let data = CommissionSinkEventData {
+                    auction_id,
+                    contract: address,
+                    amount: payout,
+                };
+                if host.invoke_contract(&address, &auction_id, entrypoint.as_entrypoint_name(), payout).is_ok() {
+                    logger.log(&AuctionEvent::CommissionDeposited(data))?;
+                } else {
+                    host.state_mut().credit_proceeds(address, payout);
+                    logger.log(&AuctionEvent::CommissionAccrued(data))?;
+                }
+            }
+        }
+    }
+    Ok(account_payouts)
+}
+
+/// Pays a payout of an auction to `account`, or keeps it for the account to
+/// claim with `claim_refund` and logs the `PayoutDeferred` event if the
+/// transfer fails. Returns whether the CCD were transferred.
+fn pay_or_defer(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    account: AccountAddress,
+    amount: Amount,
+    payout: PayoutKind,
+) -> Result<bool, BidError> {
+    // Nothing is owed when a transfer of zero fails
+    if host.invoke_transfer(&account, amount).is_ok() || amount == Amount::zero() {
+        return Ok(true);
+    }
+    host.state_mut().credit_refund(account, amount);
+    logger.log(&AuctionEvent::PayoutDeferred(PayoutDeferredEventData {
+        auction_id,
+        account,
+        amount,
+        payout,
+    }))?;
+    Ok(false)
+}
+
+/// Pays CCD to an auction owner. Accounts are paid right away, or with
+/// `claim_refund` if the transfer fails, while the CCD of contract owners are
+/// kept for them to claim with `claim_proceeds`, so that an owner cannot
+/// block the settlement of its auctions.
+fn pay_owner(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    owner: Address,
+    amount: Amount,
+    payout: PayoutKind,
+) -> Result<(), BidError> {
+    match owner {
+        Address::Account(account) => pay_or_defer(host, logger, auction_id, account, amount, payout).map(|_| ()),
+        Address::Contract(contract) => {
+            host.state_mut().credit_proceeds(contract, amount);
+            Ok(())
+        }
+    }
+}
+
+/// Pays the owner's share of a sold auction, swapped for the payout token of
+/// the auction if it has one. If there is no swap contract or the swap fails,
+/// the share is paid in CCD and the fallback is logged. A share an owner
+/// account cannot be paid is kept for it to claim with `claim_refund`.
+fn pay_out_owner(
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+    auction_id: u32,
+    auction: &Auction,
+    amount: Amount,
+) -> Result<(), BidError> {
+    if let (Some(payout_token), true) = (auction.payout_token, amount > Amount::zero()) {
+        let swapped = match host.state().swap_contract {
+            Some(swap_contract) => SwapClient::new(swap_contract).swap(host, amount, payout_token, auction.owner).is_ok(),
+            None => false,
+        };
+        if swapped {
+            return Ok(());
+        }
+        logger
+            .log(&AuctionEvent::PayoutFallback(PayoutFallbackEventData {
+                auction_id,
+                owner: auction.owner,
+                amount,
+                payout_token,
+            }))?;
+    }
+    pay_owner(host, logger, auction_id, auction.owner, amount, PayoutKind::Proceeds)
+}
 
-        logger.log(&format!("{:?}", result)).map_err(|_| BidError::TransferFailed)?;
+/// The price charged to the highest bidder of an auction with bids: the
+/// highest bid, or for second price auctions the second highest bid, or the
+/// initial price if there was only one bid.
+fn charged_price(auction: &Auction) -> Amount {
+    match auction.kind {
+        AuctionKind::SecondPrice { second_bid } if second_bid > Amount::zero() => second_bid,
+        AuctionKind::SecondPrice { .. } => Amount::from_micro_ccd(auction.initial_price),
+        AuctionKind::FixedPrice { price } => price,
+        _ => auction.highest_bid,
     }
+}
+
+/// The largest finalize reward paid for an auction with the given highest bid.
+fn max_finalize_reward(highest_bid: Amount) -> Amount {
+    commission_of(highest_bid, MAX_FINALIZE_REWARD_BPS)
+}
+
+/// `bps` basis points of `amount`, rounded down to the µCCD so that the
+/// commission never exceeds the rate.
+fn commission_of(amount: Amount, bps: u16) -> Amount {
+    let micro_ccd = u128::from(amount.micro_ccd) * u128::from(bps) / u128::from(TOTAL_BASIS_POINTS);
+    Amount::from_micro_ccd(micro_ccd as u64)
+}
+
+/// `retry_delivery` function to retry the token transfer of an auction
+/// pending delivery, and release the payouts of the highest bid once it
+/// succeeds. Anyone can retry.
+#[receive(contract = "auction", name = "retry_delivery", parameter = "AuctionIdParameter", enable_logger, mutable, error = "BidError")]
+pub fn retry_delivery(
+    ctx: &impl HasReceiveContext,
+    host: &mut Host<State>,
+    logger: &mut impl HasLogger,
+) -> Result<(), BidError> {
+    let parameter: AuctionIdParameter = ctx.parameter_cursor().get()?;
+    let auction = host.state().auction(parameter.auction_id)?;
+
+    let winner = match auction.auction_state {
+        AuctionState::PendingDelivery(winner) => winner,
+        _ => bail!(BidError::NotPendingDelivery),
+    };
 
+    let delivered = deliver_and_pay_out(ctx, host, logger, parameter.auction_id, winner)?;
+    ensure!(delivered, BidError::TransferFailed);
     Ok(())
 }
 
-/// Init function to initialize the state with an empty array of auctions.
-#[init(contract = "auction")]
-pub fn auction_init(_ctx: &InitContext, _state_builder: &mut StateBuilder<ExternStateApi>) -> InitResult<State> {
-    let commission_recipient = _ctx.init_origin();
+/// Init function to initialize the state with an empty map of auctions.
+#[init(contract = "auction", parameter = "InitParameter", event = "AuctionEvent", error = "BidError")]
+pub fn auction_init(ctx: &InitContext, state_builder: &mut StateBuilder<ExternStateApi>) -> Result<State, BidError> {
+    let parameter: InitParameter = ctx.parameter_cursor().get()?;
+    validate_commission_shares(&parameter.commission_recipients)?;
+    ensure!(parameter.cancellation_fee_bps <= TOTAL_BASIS_POINTS, BidError::InvalidCancellationFee);
+    ensure!(parameter.retraction_penalty_bps <= TOTAL_BASIS_POINTS, BidError::InvalidRetractionPenalty);
+    validate_commission_tiers(&parameter.commission_tiers)?;
 
     Ok(State {
-        auctions: Vec::new(),  // Start with an empty array of auctions
-        commission_recipient,
+        auctions: state_builder.new_map(), // Start without auctions
+        auction_count: 0,
+        commission_recipients: parameter.commission_recipients,
+        commission_tiers: parameter.commission_tiers,
+        commission_change_delay: parameter.commission_change_delay,
+        pending_commission_change: None,
+        admin: ctx.init_origin(),
+        auto_settle_late_bids: parameter.auto_settle_late_bids,
+        reclaim_grace_period: parameter.reclaim_grace_period,
+        cancellation_fee_bps: parameter.cancellation_fee_bps,
+        owner_approval_window: parameter.owner_approval_window,
+        retraction_penalty_bps: parameter.retraction_penalty_bps,
+        retraction_cutoff: parameter.retraction_cutoff,
+        pending_refunds: state_builder.new_map(),
+        bids_by_bidder: state_builder.new_map(),
+        auctions_by_token_contract: state_builder.new_map(),
+        settlements: state_builder.new_map(),
+        backup_bids: state_builder.new_map(),
+        backup_bids_total: Amount::zero(),
+        balances: state_builder.new_map(),
+        balances_total: Amount::zero(),
+        balance_funded_bids: state_builder.new_set(),
+        escrowed: state_builder.new_map(),
+        escrow_receipts: state_builder.new_map(),
+        awaiting_escrow: None,
+        cis0_exempt: state_builder.new_set(),
+        swap_contract: None,
+        active_auctions: state_builder.new_map(),
+        max_active_auctions: parameter.max_active_auctions,
+        max_auction_duration: parameter.max_auction_duration,
+        delivery_window: parameter.delivery_window,
+        pending_returns: state_builder.new_set(),
+        bidders_by_auction: state_builder.new_map(),
+        offers: state_builder.new_map(),
+        offers_by_listing: state_builder.new_map(),
+        next_offer_id: 0,
+        offers_total: Amount::zero(),
+        stats: Stats::default(),
+        standing_bids_total: Amount::zero(),
+        reverse_budgets_total: Amount::zero(),
+        pending_refunds_total: Amount::zero(),
+        pending_proceeds: state_builder.new_map(),
+        pending_proceeds_total: Amount::zero(),
+        pending_token_claims: state_builder.new_map(),
     })
 }
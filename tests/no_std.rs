@@ -0,0 +1,43 @@
+//! Checks that the contract builds without the `std` feature, as it is
+//! deployed, so `std`-only code such as `format!` does not creep back in.
+//!
+//! Without `std`, `concordium-std` uses unstable features, so the check builds
+//! with the nightly toolchain and its `wasm32-unknown-unknown` target:
+//!
+//! ```text
+//! rustup toolchain install nightly --target wasm32-unknown-unknown
+//! ```
+use std::{env, path::Path, process::Command};
+
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// Whether the nightly toolchain is installed with the wasm target.
+fn nightly_wasm_target_installed() -> bool {
+    let output = Command::new("rustc").args(["+nightly", "--print", "target-libdir", "--target", WASM_TARGET]).output();
+    output.is_ok_and(|output| {
+        output.status.success() && Path::new(String::from_utf8_lossy(&output.stdout).trim()).is_dir()
+    })
+}
+
+#[test]
+fn test_builds_without_std() {
+    // The check is skipped locally without the toolchain, but never in CI.
+    if !nightly_wasm_target_installed() {
+        assert!(env::var_os("CI").is_none(), "The nightly toolchain with the {WASM_TARGET} target is not installed");
+        eprintln!("Skipping the no_std build: the nightly toolchain with the {WASM_TARGET} target is not installed");
+        return;
+    }
+
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let status = Command::new("cargo")
+        .current_dir(manifest_dir)
+        .args(["+nightly", "build", "--lib", "--release", "--no-default-features", "--features", "bump_alloc"])
+        .args(["--target", WASM_TARGET])
+        // A separate target directory avoids waiting on the lock of the one
+        // running the tests.
+        .arg("--target-dir")
+        .arg(manifest_dir.join("target").join("no-std-check"))
+        .status()
+        .expect("Run cargo");
+    assert!(status.success(), "The contract does not build without the std feature");
+}
@@ -0,0 +1,251 @@
+//! Tests that the serde representation of the contract types stays in sync
+//! with their binary representation.
+#![cfg(feature = "serde")]
+use auction::*;
+use concordium_cis2::{AdditionalData, TokenAmountU64, TokenIdU8};
+use concordium_smart_contract_testing::*;
+use concordium_std::{schema::SchemaType, Serial};
+use concordium_std_derive::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+const ALICE: AccountAddress =
+    account_address!("2wkBET2rRgE8pahuaczxKbmv7ciehqsne57F9gtzf1PVdr2VP3");
+
+/// Check that converting `value` to JSON and back yields a value with the
+/// same binary serialization as the original.
+fn assert_round_trip<T: Serial + Serialize + DeserializeOwned>(value: &T) {
+    let json = serde_json::to_string(value).expect("Serialize to JSON");
+    let parsed: T = serde_json::from_str(&json).expect("Deserialize from JSON");
+    assert_eq!(to_bytes(&parsed), to_bytes(value), "Round-trip through {} changed the value", json);
+}
+
+fn test_auction() -> Auction {
+    Auction {
+        auction_state:          AuctionState::Sold {
+            winner:     ALICE,
+            price:      Amount::from_ccd(3),
+            settled_at: Timestamp::from_timestamp_millis(2000),
+        },
+        highest_bidder:         Some(ALICE),
+        beneficiary:            None,
+        initial_price:          100,
+        highest_bid:            Amount::from_ccd(3),
+        item:                   "Auction item".to_string(),
+        end:                    Timestamp::from_timestamp_millis(1000),
+        owner:                  Address::Account(ALICE),
+        token_contract:         ContractAddress::new(1000, 0),
+        token_id:               TokenIdU8(1),
+        token_amount:           TokenAmountU64(1),
+        settlement_exclusivity: Some(Duration::from_hours(24)),
+        minimum_raise:          Raise::Percent(500),
+        requires_owner_approval: true,
+        finalize_reward:        Amount::from_micro_ccd(500),
+        commission_tiers:       vec![CommissionTier {
+            threshold: Amount::zero(),
+            bps:       1000,
+        }],
+        kind:                   AuctionKind::Reverse {
+            budget: Amount::from_ccd(10),
+        },
+        escrow_confirmed:       true,
+        bid_count:              3,
+        unique_bidders:         2,
+        payout_token:           Some((ContractAddress::new(1001, 0), TokenIdU8(2))),
+        accepts_backup_bids:    true,
+        alt_bid_token:          Some(AltBidToken {
+            contract:          ContractAddress::new(1002, 0),
+            token_id:          TokenIdU8(3),
+            ratio_numerator:   2,
+            ratio_denominator: 3,
+        }),
+        token_bid:              Some(TokenAmountU64(5)),
+        amount_sold:            TokenAmountU64(1),
+        amount_remaining:       TokenAmountU64(0),
+    }
+}
+
+#[test]
+fn test_auction_round_trip() {
+    assert_round_trip(&test_auction());
+    for auction_state in [AuctionState::NotSoldYet, AuctionState::Unsold, AuctionState::Superseded(3), AuctionState::PendingDelivery(ALICE), AuctionState::Cancelled, AuctionState::Rejected] {
+        assert_round_trip(&auction_state);
+    }
+}
+
+#[test]
+fn test_parameters_round_trip() {
+    assert_round_trip(&NewAuctionParameter {
+        item:                   "Auction item".to_string(),
+        end:                    Timestamp::from_timestamp_millis(1000),
+        initial_price:          100,
+        token_contract:         ContractAddress::new(1000, 0),
+        token_id:               TokenIdU8(1),
+        token_amount:           TokenAmountU64(1),
+        settlement_exclusivity: None,
+        minimum_raise:          Raise::Flat(Amount::from_ccd(1)),
+        requires_owner_approval: false,
+        finalize_reward:        Amount::zero(),
+        second_price:           true,
+        payout_token:           Some((ContractAddress::new(1001, 0), TokenIdU8(2))),
+        accepts_backup_bids:    true,
+        alt_bid_token:          None,
+    });
+    assert_round_trip(&ListingParameter {
+        item:                   "Auction item".to_string(),
+        end:                    Timestamp::from_timestamp_millis(1000),
+        initial_price:          100,
+        settlement_exclusivity: Some(Duration::from_hours(24)),
+        minimum_raise:          Raise::Percent(500),
+        requires_owner_approval: true,
+        finalize_reward:        Amount::from_micro_ccd(500),
+        second_price:           false,
+        payout_token:           None,
+        accepts_backup_bids:    true,
+        alt_bid_token:          None,
+    });
+    assert_round_trip(&TransferData::DeliverAsk(7));
+    assert_round_trip(&TransferData::Bid(7));
+    assert_round_trip(&ReverseAuctionParameter {
+        item:           "Auction item".to_string(),
+        end:            Timestamp::from_timestamp_millis(1000),
+        token_contract: ContractAddress::new(1000, 0),
+        token_id:       TokenIdU8(1),
+        token_amount:   TokenAmountU64(1),
+    });
+    assert_round_trip(&FixedPriceParameter {
+        item:           "Auction item".to_string(),
+        price:          Amount::from_ccd(100),
+        token_contract: ContractAddress::new(1000, 0),
+        token_id:       TokenIdU8(1),
+        token_amount:   TokenAmountU64(1),
+    });
+    assert_round_trip(&BidParameter {
+        auction_id:  7,
+        beneficiary: Some(ALICE),
+        data:        AdditionalData::from(vec![1, 2, 3]),
+    });
+    assert_round_trip(&BalanceBidParameter {
+        bid:    BidParameter {
+            auction_id:  7,
+            beneficiary: None,
+            data:        AdditionalData::empty(),
+        },
+        amount: Amount::from_ccd(3),
+    });
+    assert_round_trip(&UpdateItemParameter {
+        auction_id: 7,
+        item:       "Auction item".to_string(),
+    });
+    assert_round_trip(&AuctionIdParameter {
+        auction_id: 7,
+    });
+    assert_round_trip(&BidError::BidTooLate);
+}
+
+#[test]
+fn test_events_round_trip() {
+    assert_round_trip(&AuctionEvent::Register(AuctionEventData {
+        auction_id: 7,
+    }));
+    assert_round_trip(&AuctionEvent::Finalize(FinalizeEventData {
+        auction_id:         7,
+        finalizer:          Address::Account(ALICE),
+        commission_payouts: vec![(ALICE, Amount::from_micro_ccd(100))],
+        finalize_reward:    Amount::zero(),
+        price:              Amount::from_micro_ccd(1000),
+        winner:             Some(ALICE),
+        settled_at:         Timestamp::from_timestamp_millis(2000),
+    }));
+    assert_round_trip(&AuctionEvent::CommissionChangeScheduled(CommissionChange {
+        commission_tiers:      vec![CommissionTier {
+            threshold: Amount::zero(),
+            bps:       2000,
+        }, CommissionTier {
+            threshold: Amount::from_ccd(1000),
+            bps:       1000,
+        }],
+        commission_recipients: Some(vec![CommissionShare {
+            recipient: CommissionSink::Account(ALICE),
+            share_bps: 10000,
+        }]),
+        effective_at:          Timestamp::from_timestamp_millis(1000),
+    }));
+    assert_round_trip(&AuctionEvent::Refund(RefundEventData {
+        auction_id: Some(7),
+        account:    ALICE,
+        amount:     Amount::from_micro_ccd(100),
+        reason:     RefundReason::Outbid,
+    }));
+    assert_round_trip(&AuctionEvent::ItemSold(ItemEventData {
+        auction_id: 7,
+        collection: ContractAddress::new(1000, 0),
+        token_id:   TokenIdU8(1),
+        amount:     TokenAmountU64(1),
+        price:      Amount::from_ccd(3),
+        actor:      Address::Account(ALICE),
+    }));
+    assert_round_trip(&AuctionEvent::Close(AuctionEventData {
+        auction_id: 7,
+    }));
+    assert_round_trip(&AuctionEvent::TokenPayout(TokenPayoutEventData {
+        auction_id:        7,
+        owner:             Address::Account(ALICE),
+        owner_tokens:      TokenAmountU64(9),
+        commission_tokens: TokenAmountU64(1),
+    }));
+    assert_round_trip(&AuctionEvent::ReturnDeferred(ReturnDeferredEventData {
+        auction_id: 7,
+        owner:      Address::Account(ALICE),
+        amount:     TokenAmountU64(5),
+    }));
+    assert_round_trip(&AuctionEvent::PayoutDeferred(PayoutDeferredEventData {
+        auction_id: 7,
+        account:    ALICE,
+        amount:     Amount::from_micro_ccd(100),
+        payout:     PayoutKind::Commission,
+    }));
+    assert_round_trip(&AuctionEvent::PayoutDeferred(PayoutDeferredEventData {
+        auction_id: 7,
+        account:    ALICE,
+        amount:     Amount::from_micro_ccd(100),
+        payout:     PayoutKind::Budget,
+    }));
+    assert_round_trip(&AuctionEvent::TokensDeferred(TokensDeferredEventData {
+        auction_id:     7,
+        recipient:      Address::Account(ALICE),
+        token_contract: ContractAddress::new(1000, 0),
+        token_id:       TokenIdU8(9),
+        amount:         TokenAmountU64(25),
+    }));
+}
+
+/// Check that `view_config` decodes to JSON through its schema, as frontends
+/// read it.
+#[test]
+fn test_config_view_json() {
+    let view = ConfigView::V1(ConfigViewV1 {
+        version:                   CONTRACT_VERSION.to_string(),
+        admin:                     ALICE,
+        commission_recipients:     vec![],
+        commission_tiers:          vec![CommissionTier {
+            threshold: Amount::zero(),
+            bps:       1000,
+        }],
+        commission_change_delay:   Duration::from_days(1),
+        pending_commission_change: None,
+        auto_settle_late_bids:     false,
+        reclaim_grace_period:      Duration::from_days(7),
+        cancellation_fee_bps:      500,
+        owner_approval_window:     Duration::from_days(2),
+        retraction_penalty_bps:    100,
+        retraction_cutoff:         Duration::from_hours(1),
+        max_active_auctions:       20,
+        max_auction_duration:      Duration::from_days(365),
+        delivery_window:           Duration::from_days(7),
+        swap_contract:             None,
+    });
+    let json = <ConfigView as SchemaType>::get_type().to_json_string_pretty(&to_bytes(&view)).expect("Decode with the schema");
+    let json: serde_json::Value = serde_json::from_str(&json).expect("Valid JSON");
+    assert_eq!(json["V1"][0]["version"], CONTRACT_VERSION);
+    assert_eq!(json["V1"][0]["cancellation_fee_bps"], 500);
+}
@@ -1,7 +1,20 @@
 //! Tests for the auction smart contract.
+//!
+//! The tests run against the built modules of the auction contract and of the
+//! mock contracts in `mock-contracts`, so build both before running them, as
+//! described in the README. The modules are not committed, and the tests fail
+//! if either is missing or older than the sources it is built from.
 use auction::*;
+use concordium_cis2::{
+    AdditionalData, BalanceOfQuery, BalanceOfQueryParams, BalanceOfQueryResponse, OnReceivingCis2Params,
+    OperatorUpdate, Receiver, TokenAmountU64, TokenIdU8, Transfer, TransferParams, UpdateOperator,
+    UpdateOperatorParams,
+};
 use concordium_smart_contract_testing::*;
+use concordium_std::{Deserial, Serial};
 use concordium_std_derive::*;
+use mock_contracts::{ForwardParams, MintParams};
+use std::{fs, path::Path, time::SystemTime};
 
 /// The tests accounts.
 const ALICE: AccountAddress =
@@ -14,334 +27,4018 @@ const DAVE: AccountAddress = account_address!("2y57FyMyqAfY7X1SuSWJ5VMt1Z3ZgxbKt
 const SIGNER: Signer = Signer::with_one_key();
 const ACC_INITIAL_BALANCE: Amount = Amount::from_ccd(10000);
 
+/// Energy given to every transaction, enough for the cross-contract calls.
+const ENERGY: Energy = Energy {
+    energy: 100_000,
+};
+
+/// The end of the auctions created by `TestEnv::auction_parameter`.
+const AUCTION_END: Timestamp = Timestamp::from_timestamp_millis(1000);
+
 /// Test a sequence of bids and finalizations:
-/// 0. Auction is initialized.
+/// 0. Carol creates two auctions, escrowing a token for each.
 /// 1. Alice successfully bids 1 CCD.
-/// 2. Alice successfully bids 2 CCD, highest
-/// bid becomes 2 CCD. Alice gets her 1 CCD refunded.
+/// 2. Alice successfully tops up her bid by 1 CCD, highest
+///    bid becomes 2 CCD. Nothing is refunded.
 /// 3. Bob successfully bids 3 CCD, highest
-/// bid becomes 3 CCD. Alice gets her 2 CCD refunded.
+///    bid becomes 3 CCD. Alice gets her 2 CCD refunded.
 /// 4. Alice tries to bid 3 CCD, which matches the current highest bid, which
-/// fails.
+///    fails.
 /// 5. Alice tries to bid 3.5 CCD, which is below the minimum raise
-/// threshold of 1 CCD.
+///    threshold of 1 CCD.
 /// 6. Someone tries to finalize the auction before
-/// its end time. Attempt fails.
+///    its end time. Attempt fails.
 /// 7. Someone tries to bid after the auction has ended (but before it has been
-/// finalized), which fails.
+///    finalized), which fails.
 /// 8. Dave successfully finalizes the auction after
-/// its end time. Carol (the owner of the contract) collects the highest bid
-/// amount.
+///    its end time. Carol (the owner of the auction and the commission recipient)
+///    collects the highest bid amount.
 /// 9. Attempts to subsequently bid or finalize fail.
+/// 10. The second auction, which ends later, still accepts bids.
 #[test]
 fn test_multiple_scenarios() {
-    let (mut chain, contract_address) = initialize_chain_and_auction();
-
-    let parameter = NewAuctionParameter {
-        item:          "Auction item".to_string(),
-        end:           Timestamp::from_timestamp_millis(1000),
-        initial_price: 100, // 100 eurocent = 1 euro
-    };
-
-    let newparameter = NewAuctionParameter {
-        item:          "Auction item 2".to_string(),
-        end:           Timestamp::from_timestamp_millis(1000),
-        initial_price: 100, // 100 eurocent = 1 euro
-    };
-
-    let bidparameter = BidParameter {
-        auction_id: 0,
-    };
-
-    let bidparameter2 = BidParameter {
-        auction_id: 1
-    };
-
-    let _create = chain
-    .contract_update(
-        SIGNER,
-        ALICE,
-        Address::Account(ALICE),
-        Energy::from(10000),
-        UpdateContractPayload {
-            amount:       Amount::zero(),
-            address:      contract_address,
-            receive_name: OwnedReceiveName::new_unchecked("auction.create_auction".to_string()),
-            message:       OwnedParameter::from_serial(&parameter).expect("Serialize parameter"),
-        },
-    )
-    .expect("Auction has been successfully created");
-
-    let _create2 = chain
-    .contract_update(
-        SIGNER,
-        ALICE,
-        Address::Account(ALICE),
-        Energy::from(10000),
-        UpdateContractPayload {
-            amount:       Amount::zero(),
-            address:      contract_address,
-            receive_name: OwnedReceiveName::new_unchecked("auction.create_auction".to_string()),
-            message:       OwnedParameter::from_serial(&newparameter).expect("Serialize parameter"),
-        },
-    )
-    .expect("Auction 2 has been successfully created");
-
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(CAROL, &NewAuctionParameter {
+        minimum_raise: Raise::Flat(Amount::from_ccd(1)),
+        ..env.auction_parameter("Auction item", 1)
+    });
+    let second_auction_id = env.create_auction(CAROL, &NewAuctionParameter {
+        end: Timestamp::from_timestamp_millis(5000),
+        ..env.auction_parameter("Auction item 2", 2)
+    });
 
     // 1. Alice successfully bids 1 CCD.
-    let _update_1 = chain
-        .contract_update(
-            SIGNER,
-            ALICE,
-            Address::Account(ALICE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::from_ccd(1),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.bid".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
-        .expect("Alice successfully bids 1 CCD");
-
-    // 2. Alice successfully bids 2 CCD, highest
-    // bid becomes 2 CCD. Alice gets her 1 CCD refunded.
-    let update_2 = chain
-        .contract_update(
-            SIGNER,
-            ALICE,
-            Address::Account(ALICE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::from_ccd(2),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.bid".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
-        .expect("Alice successfully bids 2 CCD");
-    // Check that 1 CCD is transferred back to ALICE.
-    assert_eq!(update_2.account_transfers().collect::<Vec<_>>()[..], [(
-        contract_address,
-        Amount::from_ccd(1),
-        ALICE
-    )]);
+    env.bid(ALICE, auction_id, Amount::from_ccd(1)).expect("Alice successfully bids 1 CCD");
+
+    // 2. Alice successfully tops up her bid by 1 CCD, highest
+    // bid becomes 2 CCD. Nothing is refunded.
+    let update_2 = env.bid(ALICE, auction_id, Amount::from_ccd(1)).expect("Alice successfully tops up by 1 CCD");
+    assert_eq!(update_2.account_transfers().count(), 0);
+    assert_eq!(env.get_auction(auction_id).highest_bid, Amount::from_ccd(2));
 
     // 3. Bob successfully bids 3 CCD, highest
     // bid becomes 3 CCD. Alice gets her 2 CCD refunded.
-    let update_3 = chain
-        .contract_update(
-            SIGNER,
-            BOB,
-            Address::Account(BOB),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::from_ccd(3),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.bid".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
-        .expect("Bob successfully bids 3 CCD");
+    let update_3 = env.bid(BOB, auction_id, Amount::from_ccd(3)).expect("Bob successfully bids 3 CCD");
     // Check that 2 CCD is transferred back to ALICE.
     assert_eq!(update_3.account_transfers().collect::<Vec<_>>()[..], [(
-        contract_address,
+        env.auction,
         Amount::from_ccd(2),
         ALICE
     )]);
 
     // 4. Alice tries to bid 3 CCD, which matches the current highest bid, which
     // fails.
-    let update_4 = chain
-        .contract_update(
-            SIGNER,
-            ALICE,
-            Address::Account(ALICE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::from_ccd(3),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.bid".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
-        .expect_err("Alice tries to bid 3 CCD");
+    let update_4 = env.bid(ALICE, auction_id, Amount::from_ccd(3)).expect_err("Alice tries to bid 3 CCD");
     // Check that the correct error is returned.
     let rv: BidError = update_4.parse_return_value().expect("Return value is valid");
-    assert_eq!(rv, BidError::BidBelowCurrentBid);
+    assert_eq!(rv, BidError::BidTiesCurrentBid);
 
     // 5. Alice tries to bid 3.5 CCD, which is below the minimum raise threshold of
     // 1 CCD.
-    let update_5 = chain
-        .contract_update(
-            SIGNER,
-            ALICE,
-            Address::Account(ALICE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::from_micro_ccd(3_500_000),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.bid".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
-        .expect_err("Alice tries to bid 3.5 CCD");
+    let update_5 =
+        env.bid(ALICE, auction_id, Amount::from_micro_ccd(3_500_000)).expect_err("Alice tries to bid 3.5 CCD");
     // Check that the correct error is returned.
     let rv: BidError = update_5.parse_return_value().expect("Return value is valid");
     assert_eq!(rv, BidError::BidBelowMinimumRaise);
 
     // 6. Someone tries to finalize the auction before
     // its end time. Attempt fails.
-    let update_6 = chain
-        .contract_update(
-            SIGNER,
-            DAVE,
-            Address::Account(DAVE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::zero(),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.finalize".to_string()),
-                message:     OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
-        .expect_err("Attempt to finalize auction before end time");
+    let update_6 = env.finalize(DAVE, auction_id).expect_err("Attempt to finalize auction before end time");
     // Check that the correct error is returned.
     let rv: BidError = update_6.parse_return_value().expect("Return value is valid");
     assert_eq!(rv, BidError::AuctionStillActive);
 
     // Increment the chain time by 1001 milliseconds.
-    chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
 
     // 7. Someone tries to bid after the auction has ended (but before it has been
     // finalized), which fails.
-    let update_7 = chain
-        .contract_update(
-            SIGNER,
-            DAVE,
-            Address::Account(DAVE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::from_ccd(10),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.bid".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
+    let update_7 = env
+        .bid(DAVE, auction_id, Amount::from_ccd(10))
         .expect_err("Attempt to bid after auction has reached the endtime");
     // Check that the return value is `BidTooLate`.
     let rv: BidError = update_7.parse_return_value().expect("Return value is valid");
     assert_eq!(rv, BidError::BidTooLate);
 
     // 8. Dave successfully finalizes the auction after its end time.
-    let update_8 = chain
-        .contract_update(
-            SIGNER,
-            DAVE,
-            Address::Account(DAVE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::zero(),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.finalize".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
+    let update_8 = env
+        .finalize(DAVE, auction_id)
         .expect("Dave successfully finalizes the auction after its end time");
 
-    // Check that the correct amount is transferred to Carol.
-    assert_eq!(update_8.account_transfers().collect::<Vec<_>>()[..], [(
-        contract_address,
-        Amount::from_ccd(3),
-        CAROL
-    )]);
+    // Check that the commission and the proceeds are transferred to Carol.
+    assert_eq!(update_8.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_micro_ccd(300_000), CAROL),
+        (env.auction, Amount::from_micro_ccd(2_700_000), CAROL)
+    ]);
 
     // 9. Attempts to subsequently bid or finalize fail.
-    let update_9 = chain
-        .contract_update(
-            SIGNER,
-            ALICE,
-            Address::Account(ALICE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::from_ccd(1),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.bid".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
+    let update_9 = env
+        .bid(ALICE, auction_id, Amount::from_ccd(1))
         .expect_err("Attempt to bid after auction has been finalized");
     // Check that the return value is `AuctionAlreadyFinalized`.
     let rv: BidError = update_9.parse_return_value().expect("Return value is valid");
     assert_eq!(rv, BidError::AuctionAlreadyFinalized);
 
-    let update_10 = chain
-        .contract_update(
-            SIGNER,
-            ALICE,
-            Address::Account(ALICE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::zero(),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.finalize".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter).expect("Serialize parameter"),
-            },
-        )
+    let update_10 = env
+        .finalize(ALICE, auction_id)
         .expect_err("Attempt to finalize auction after it has been finalized");
     let rv: BidError = update_10.parse_return_value().expect("Return value is valid");
     assert_eq!(rv, BidError::AuctionAlreadyFinalized);
 
-    // 1. Alice successfully bids 1 CCD.
-    let _update1 = chain
-        .contract_update(
-            SIGNER,
-            ALICE,
-            Address::Account(ALICE),
-            Energy::from(10000),
-            UpdateContractPayload {
-                amount:       Amount::from_ccd(1),
-                address:      contract_address,
-                receive_name: OwnedReceiveName::new_unchecked("auction.bid".to_string()),
-                message:      OwnedParameter::from_serial(&bidparameter2).expect("Serialize parameter"),
-            },
-        )
-        .expect("Alice successfully bids 1 CCD");
+    // 10. Alice successfully bids 1 CCD on the second auction.
+    env.bid(ALICE, second_auction_id, Amount::from_ccd(1)).expect("Alice successfully bids 1 CCD");
+}
+
+/// Test the full life cycle of an auction across the token contract: the
+/// token is escrowed on creation, an outbid bidder is refunded, and on
+/// finalize the token goes to the winner while the highest bid is split
+/// between the commission recipient and the owner.
+#[test]
+fn test_auction_settles_to_winner() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+
+    // The token is held in escrow by the auction contract.
+    assert_eq!(env.token_balance(Address::Account(ALICE), 1), TokenAmountU64(0));
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 1), TokenAmountU64(1));
 
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    let outbid = env.bid(DAVE, auction_id, Amount::from_ccd(20)).expect("Dave bids 20 CCD");
+    assert_eq!(outbid.account_transfers().collect::<Vec<_>>()[..], [(
+        env.auction,
+        Amount::from_ccd(10),
+        BOB
+    )]);
+
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let finalize = env.finalize(BOB, auction_id).expect("Finalize auction");
+
+    // 10% commission to Carol, the rest to Alice.
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(2), CAROL),
+        (env.auction, Amount::from_ccd(18), ALICE)
+    ]);
+    assert_eq!(env.token_balance(Address::Account(DAVE), 1), TokenAmountU64(1));
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 1), TokenAmountU64(0));
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::zero()));
+    assert_eq!(env.get_auction(auction_id).auction_state, AuctionState::Sold {
+        winner:     DAVE,
+        price:      Amount::from_ccd(20),
+        settled_at: Timestamp::from_timestamp_millis(1001),
+    });
 }
 
-/// Setup auction and chain.
-///
-/// Carol is the owner of the auction, which ends at `1000` milliseconds after
-/// the unix epoch. The 'microCCD per euro' exchange rate is set to `1_000_000`,
-/// so 1 CCD = 1 euro.
-fn initialize_chain_and_auction() -> (Chain, ContractAddress) {
-    let mut chain = Chain::builder()
-        .micro_ccd_per_euro(
-            ExchangeRate::new(1_000_000, 1).expect("Exchange rate is in valid range"),
-        )
-        .build()
-        .expect("Exchange rate is in valid range");
+/// Test that the marketplace events of a sale carry the token and the price,
+/// next to the auction events.
+#[test]
+fn test_item_events() {
+    let mut env = TestEnv::new();
+    let sold = env.create_auction(ALICE, &NewAuctionParameter {
+        initial_price: 1_000,
+        ..env.auction_parameter("Sold item", 1)
+    });
+    let returned = env.create_auction(ALICE, &env.auction_parameter("Returned item", 2));
+    let bid = env.bid(BOB, sold, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    assert!(env.events(&bid).contains(&AuctionEvent::ItemBid(env.item_event(
+        sold,
+        1,
+        Amount::from_ccd(10),
+        Address::Account(BOB)
+    ))));
 
-    // Create some accounts on the chain.
-    chain.create_account(Account::new(ALICE, ACC_INITIAL_BALANCE));
-    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
-    chain.create_account(Account::new(CAROL, ACC_INITIAL_BALANCE));
-    chain.create_account(Account::new(DAVE, ACC_INITIAL_BALANCE));
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let finalize = env.finalize(DAVE, sold).expect("Finalize sold auction");
+    assert_eq!(
+        env.events(&finalize).last(),
+        Some(&AuctionEvent::ItemSold(env.item_event(sold, 1, Amount::from_ccd(10), Address::Account(BOB))))
+    );
+    let finalize = env.finalize(DAVE, returned).expect("Finalize auction without bids");
+    assert_eq!(
+        env.events(&finalize).last(),
+        Some(&AuctionEvent::ItemReturned(env.item_event(returned, 2, Amount::zero(), Address::Account(ALICE))))
+    );
+}
 
-    // Load and deploy the module.
-    let module = module_load_v1("concordium-out/module.wasm.v1").expect("Module exists");
-    let deployment = chain.module_deploy_v1(SIGNER, CAROL, module).expect("Deploy valid module");
-
-    // Initialize the auction contract.
-    let init = chain
-        .contract_init(SIGNER, CAROL, Energy::from(10000), InitContractPayload {
-            amount:    Amount::zero(),
-            mod_ref:   deployment.module_reference,
-            init_name: OwnedContractName::new_unchecked("init_auction".to_string()),
-            param:     OwnedParameter::empty(),
+/// Test that finalizing an auction without bids returns the token to the
+/// owner without any CCD transfers.
+#[test]
+fn test_auction_without_bids_returns_token() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let finalize = env.finalize(BOB, auction_id).expect("Finalize auction");
+
+    assert_eq!(finalize.account_transfers().count(), 0);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 1), TokenAmountU64(1));
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 1), TokenAmountU64(0));
+    assert_eq!(env.get_auction(auction_id).auction_state, AuctionState::Unsold);
+}
+
+/// Test that bids after the end and finalization before the end are rejected,
+/// and leave the escrow untouched.
+#[test]
+fn test_bid_too_late_and_finalize_too_early() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+
+    let early = env.finalize(BOB, auction_id).expect_err("Finalize before the end");
+    let rv: BidError = early.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::AuctionStillActive);
+
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let late = env.bid(BOB, auction_id, Amount::from_ccd(1)).expect_err("Bid after the end");
+    let rv: BidError = late.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::BidTooLate);
+
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 1), TokenAmountU64(1));
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::zero()));
+}
+
+/// Test the end boundary of every entrypoint that depends on it, one
+/// millisecond before the end, at the end and one millisecond after: bids,
+/// asks and cancellations are only taken strictly before the end, and
+/// finalizing, relisting, owner decisions and deliveries only work from the
+/// end on.
+#[test]
+fn test_end_boundary() {
+    let mut env = TestEnv::new();
+    for token_id in 4..=7 {
+        env.mint(Address::Account(ALICE), token_id, 1);
+    }
+    let create = |env: &mut TestEnv, token_id, requires_owner_approval| {
+        let parameter = NewAuctionParameter {
+            requires_owner_approval,
+            ..env.auction_parameter("Auction item", token_id)
+        };
+        env.create_auction(ALICE, &parameter)
+    };
+    let bid_on = create(&mut env, 1, false);
+    let cancelled = create(&mut env, 2, false);
+    let late = create(&mut env, 3, false);
+    let relisted = create(&mut env, 4, false);
+    let unbid = create(&mut env, 5, false);
+    let rejected = create(&mut env, 6, true);
+    let accepted = create(&mut env, 7, true);
+    for auction_id in [rejected, accepted] {
+        env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    }
+    let reverse: u32 = env
+        .update(DAVE, "create_reverse_auction", Amount::from_ccd(100), &ReverseAuctionParameter {
+            item:           "Wanted item".to_string(),
+            end:            AUCTION_END,
+            token_contract: env.token,
+            token_id:       TokenIdU8(1),
+            token_amount:   TokenAmountU64(1),
+        })
+        .expect("Create reverse auction")
+        .parse_return_value()
+        .expect("Return value is valid");
+    let id = |auction_id| AuctionIdParameter {
+        auction_id,
+    };
+    let relist = |auction_id| RelistParameter {
+        auction_id,
+        end: Timestamp::from_timestamp_millis(5000),
+        initial_price: None,
+    };
+    let ask = |ccd| AskParameter {
+        auction_id: reverse,
+        ask:        Amount::from_ccd(ccd),
+    };
+    let delivery = AdditionalData::from(to_bytes(&TransferData::DeliverAsk(reverse)));
+
+    // One millisecond before the end, the auction is still running.
+    env.chain.tick_block_time(Duration::from_millis(999)).expect("Increment chain time");
+    env.bid(BOB, bid_on, Amount::from_ccd(1)).expect("Bid before the end");
+    env.update(CAROL, "submit_ask", Amount::zero(), &ask(50)).expect("Ask before the end");
+    env.update(ALICE, "cancel_with_fee", Amount::zero(), &id(cancelled)).expect("Cancel before the end");
+    assert_error(env.finalize(BOB, bid_on), BidError::AuctionStillActive);
+    assert_error(env.update(ALICE, "relist_auction", Amount::zero(), &relist(relisted)), BidError::AuctionStillActive);
+    assert_error(env.update(ALICE, "accept_winning_bid", Amount::zero(), &id(accepted)), BidError::AuctionStillActive);
+    env.transfer_to_auction(CAROL, 1, delivery.clone()).expect_err("Delivery before the end");
+
+    // At the end, it has ended.
+    env.chain.tick_block_time(Duration::from_millis(1)).expect("Increment chain time");
+    assert_error(env.bid(DAVE, bid_on, Amount::from_ccd(2)), BidError::BidTooLate);
+    assert_error(env.update(ALICE, "submit_ask", Amount::zero(), &ask(40)), BidError::BidTooLate);
+    assert_error(env.update(ALICE, "cancel_with_fee", Amount::zero(), &id(late)), BidError::AuctionEnded);
+    env.finalize(DAVE, bid_on).expect("Finalize at the end");
+    assert!(matches!(env.get_auction(bid_on).auction_state, AuctionState::Sold { winner, .. } if winner == BOB));
+    env.update(ALICE, "relist_auction", Amount::zero(), &relist(relisted)).expect("Relist at the end");
+    env.update(ALICE, "reject_winning_bid", Amount::zero(), &id(rejected)).expect("Reject at the end");
+    env.transfer_to_auction(CAROL, 1, delivery).expect("Delivery at the end");
+
+    // One millisecond after the end, it has still ended.
+    env.chain.tick_block_time(Duration::from_millis(1)).expect("Increment chain time");
+    assert_error(env.bid(DAVE, unbid, Amount::from_ccd(2)), BidError::BidTooLate);
+    assert_error(env.update(ALICE, "cancel_with_fee", Amount::zero(), &id(late)), BidError::AuctionEnded);
+    env.update(ALICE, "relist_auction", Amount::zero(), &relist(late)).expect("Relist after the end");
+    env.update(ALICE, "accept_winning_bid", Amount::zero(), &id(accepted)).expect("Accept after the end");
+    env.finalize(DAVE, unbid).expect("Finalize after the end");
+    env.assert_ccd_invariant();
+}
+
+/// Test that the highest bidder can top up their bid: the attached amount is
+/// added to their standing bid without a refund, a zero top-up is rejected,
+/// and the CCD held by the contract always matches the standing bids.
+#[test]
+fn test_top_up_bid() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+
+    env.bid(BOB, auction_id, Amount::from_ccd(100)).expect("Bob bids 100 CCD");
+    env.assert_ccd_invariant();
+
+    let top_up = env.bid(BOB, auction_id, Amount::from_ccd(50)).expect("Bob tops up by 50 CCD");
+    assert_eq!(top_up.account_transfers().count(), 0);
+    assert_eq!(env.get_auction(auction_id).highest_bid, Amount::from_ccd(150));
+    assert!(env.events(&top_up).contains(&AuctionEvent::Bid(BidEventData {
+        auction_id,
+        bidder: BOB,
+        beneficiary: BOB,
+        amount: Amount::from_ccd(150),
+        data: Vec::new(),
+    })));
+    env.assert_ccd_invariant();
+
+    let zero = env.bid(BOB, auction_id, Amount::zero()).expect_err("Zero top-up");
+    let rv: BidError = zero.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::ZeroTopUp);
+
+    // Dave has to beat the combined bid, and Bob gets all of it back.
+    let below = env.bid(DAVE, auction_id, Amount::from_ccd(120)).expect_err("Bid below the topped up bid");
+    let rv: BidError = below.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::BidBelowCurrentBid);
+    let outbid = env.bid(DAVE, auction_id, Amount::from_ccd(160)).expect("Dave bids 160 CCD");
+    assert_eq!(outbid.account_transfers().collect::<Vec<_>>()[..], [(
+        env.auction,
+        Amount::from_ccd(150),
+        BOB
+    )]);
+    env.assert_ccd_invariant();
+}
+
+/// Test that the admin can rescue tokens sent to the contract outside of any
+/// auction, but not tokens escrowed for a live auction.
+#[test]
+fn test_rescue_tokens() {
+    let mut env = TestEnv::new();
+    env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    // A token sent to the contract by mistake, on top of the escrowed one.
+    env.mint(Address::Contract(env.auction), 1, 1);
+
+    let token = env.token;
+    let rescue = |amount| RescueParameter {
+        token_contract: token,
+        token_id:       TokenIdU8(1),
+        amount:         TokenAmountU64(amount),
+        to:             Receiver::from_account(DAVE),
+    };
+
+    let not_admin = env.update(DAVE, "rescue_tokens", Amount::zero(), &rescue(1)).expect_err("Only the admin rescues");
+    let rv: BidError = not_admin.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::OnlyAdmin);
+
+    let escrowed = env.update(CAROL, "rescue_tokens", Amount::zero(), &rescue(2)).expect_err("Rescue escrowed token");
+    let rv: BidError = escrowed.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::RescueExceedsUnescrowed);
+
+    let rescued = env.update(CAROL, "rescue_tokens", Amount::zero(), &rescue(1)).expect("Rescue stranded token");
+    assert!(env.events(&rescued).contains(&AuctionEvent::Rescue(RescueEventData {
+        token_contract: env.token,
+        token_id:       TokenIdU8(1),
+        amount:         TokenAmountU64(1),
+        to:             Address::Account(DAVE),
+    })));
+    assert_eq!(env.token_balance(Address::Account(DAVE), 1), TokenAmountU64(1));
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 1), TokenAmountU64(1));
+
+    env.update(CAROL, "rescue_tokens", Amount::zero(), &rescue(1)).expect_err("Nothing left to rescue");
+}
+
+/// Test that the escrow summary follows the bids and escrows of the auctions
+/// and matches the balances actually held by the contract.
+#[test]
+fn test_escrow_summary() {
+    let mut env = TestEnv::new();
+    let first = env.create_auction(ALICE, &env.auction_parameter("First item", 1));
+    let second = env.create_auction(ALICE, &env.auction_parameter("Second item", 2));
+
+    env.bid(BOB, first, Amount::from_ccd(10)).expect("Bob bids");
+    env.bid(DAVE, first, Amount::from_ccd(20)).expect("Dave outbids Bob");
+    env.bid(BOB, second, Amount::from_ccd(5)).expect("Bob bids");
+    env.bid(BOB, second, Amount::from_ccd(5)).expect("Bob tops up");
+
+    let summary: EscrowSummary = env.view("view_escrow_summary", &());
+    assert_eq!(summary.standing_bids, Amount::from_ccd(30));
+    assert_eq!(summary.pending_refunds, Amount::zero());
+    assert_eq!(Some(summary.total_ccd), env.chain.contract_balance(env.auction));
+    let token_contract = env.token;
+    let escrowed = |token_id| EscrowedTokens {
+        token_contract,
+        token_id:       TokenIdU8(token_id),
+        amount:         TokenAmountU64(1),
+    };
+    assert_eq!(summary.tokens, [escrowed(1), escrowed(2)]);
+
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(BOB, first).expect("Finalize auction");
+
+    let summary: EscrowSummary = env.view("view_escrow_summary", &());
+    assert_eq!(summary.standing_bids, Amount::from_ccd(10));
+    assert_eq!(Some(summary.total_ccd), env.chain.contract_balance(env.auction));
+    assert_eq!(summary.tokens, [escrowed(2)]);
+}
+
+/// Test that the tokens of auctions are counted as sold, remaining or returned
+/// across sales, returns, cancellations, relistings and failed transfers, and
+/// that the owner reclaims tokens whose return failed when finalizing.
+#[test]
+fn test_unsold_remainder() {
+    let mut env = TestEnv::new();
+    let amounts = |env: &TestEnv, auction_id| {
+        let auction = env.get_auction(auction_id);
+        (auction.amount_sold.0, auction.amount_remaining.0)
+    };
+    env.mint(Address::Account(ALICE), 4, 5);
+    let sold = env.create_auction(ALICE, &env.auction_parameter("Sold", 1));
+    let returned = env.create_auction(ALICE, &env.auction_parameter("Returned", 2));
+    let deferred = env.create_auction(ALICE, &NewAuctionParameter {
+        token_amount: TokenAmountU64(5),
+        ..env.auction_parameter("Deferred return", 4)
+    });
+    let pending = env.create_auction(CAROL, &env.auction_parameter("Pending delivery", 1));
+    let cancelled = env.create_auction(CAROL, &env.auction_parameter("Cancelled", 2));
+    let relisted = env.create_auction(CAROL, &env.auction_parameter("Relisted", 3));
+    env.bid(BOB, sold, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(BOB, pending, Amount::from_ccd(5)).expect("Bob bids 5 CCD");
+    assert_eq!(amounts(&env, deferred), (0, 5));
+    env.assert_token_invariant();
+
+    env.update(CAROL, "cancel_with_fee", Amount::zero(), &AuctionIdParameter {
+        auction_id: cancelled,
+    })
+    .expect("Cancel the auction");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(DAVE, sold).expect("Finalize sold auction");
+    env.finalize(DAVE, returned).expect("Finalize unsold auction");
+    env.update(CAROL, "relist_auction", Amount::zero(), &RelistParameter {
+        auction_id:    relisted,
+        end:           Timestamp::from_timestamp_millis(5000),
+        initial_price: None,
+    })
+    .expect("Relist the auction");
+    assert_eq!(amounts(&env, sold), (1, 0));
+    assert_eq!(amounts(&env, returned), (0, 0));
+    assert_eq!(amounts(&env, cancelled), (0, 0));
+    assert_eq!(amounts(&env, relisted), (0, 0));
+    assert_eq!(amounts(&env, relisted + 1), (0, 1));
+    env.assert_token_invariant();
+
+    // The unsold tokens stay escrowed when returning them fails, and so do
+    // the tokens of the winner when delivering them fails
+    env.set_fail_transfers(true);
+    let finalize = env.finalize(DAVE, deferred).expect("Finalize without returning the tokens");
+    assert_eq!(env.events(&finalize)[1..], [AuctionEvent::ReturnDeferred(ReturnDeferredEventData {
+        auction_id: deferred,
+        owner:      Address::Account(ALICE),
+        amount:     TokenAmountU64(5),
+    })]);
+    assert_eq!(env.get_auction(deferred).auction_state, AuctionState::Unsold);
+    env.finalize(DAVE, pending).expect("Finalize without delivering the tokens");
+    assert_eq!(amounts(&env, deferred), (0, 5));
+    assert_eq!(amounts(&env, pending), (0, 1));
+    let summary: EscrowSummary = env.view("view_escrow_summary", &());
+    assert_eq!(summary.unsold_remainders, [(deferred, TokenAmountU64(5))]);
+    env.assert_token_invariant();
+
+    let reclaim = AuctionIdParameter {
+        auction_id: deferred,
+    };
+    assert_error(env.update(BOB, "reclaim_unsold_remainder", Amount::zero(), &reclaim), BidError::OnlyOwner);
+    assert_error(env.update(ALICE, "reclaim_unsold_remainder", Amount::zero(), &reclaim), BidError::TransferFailed);
+    assert_error(
+        env.update(ALICE, "reclaim_unsold_remainder", Amount::zero(), &AuctionIdParameter {
+            auction_id: returned,
+        }),
+        BidError::NothingToClaim,
+    );
+
+    env.set_fail_transfers(false);
+    env.update(DAVE, "retry_delivery", Amount::zero(), &AuctionIdParameter {
+        auction_id: pending,
+    })
+    .expect("Deliver the tokens");
+    let update = env.update(ALICE, "reclaim_unsold_remainder", Amount::zero(), &reclaim).expect("Reclaim the tokens");
+    assert_eq!(env.events(&update), [AuctionEvent::ItemReturned(ItemEventData {
+        auction_id: deferred,
+        collection: env.token,
+        token_id:   TokenIdU8(4),
+        amount:     TokenAmountU64(5),
+        price:      Amount::zero(),
+        actor:      Address::Account(ALICE),
+    })]);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 4), TokenAmountU64(5));
+    assert_error(env.update(ALICE, "reclaim_unsold_remainder", Amount::zero(), &reclaim), BidError::NothingToClaim);
+    assert_eq!(amounts(&env, deferred), (0, 0));
+    assert_eq!(amounts(&env, pending), (1, 0));
+    assert!(env.view::<EscrowSummary>("view_escrow_summary", &()).unsold_remainders.is_empty());
+    env.assert_token_invariant();
+}
+
+/// Test that once the grace period after the end is over, the highest bidder
+/// and the owner of an unfinalized auction can each reclaim their bid and
+/// tokens, after which the auction can no longer be finalized.
+#[test]
+fn test_reclaim_expired() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids");
+
+    // Within the grace period the auction can only be finalized. The period
+    // starts at the end, one second in.
+    env.chain.tick_block_time(Duration::from_days(90)).expect("Increment chain time");
+    let early = env.update(BOB, "reclaim_expired", Amount::zero(), &AuctionIdParameter {
+        auction_id,
+    });
+    let rv: BidError = early.expect_err("Reclaim in grace period").parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::GracePeriodNotOver);
+
+    env.chain.tick_block_time(Duration::from_millis(1000)).expect("Increment chain time");
+    let reclaim_bid = env
+        .update(BOB, "reclaim_expired", Amount::zero(), &AuctionIdParameter {
+            auction_id,
+        })
+        .expect("Bob reclaims his bid");
+    assert_eq!(reclaim_bid.account_transfers().collect::<Vec<_>>()[..], [(
+        env.auction,
+        Amount::from_ccd(10),
+        BOB
+    )]);
+    assert_eq!(env.get_auction(auction_id).auction_state, AuctionState::Lapsed {
+        bid_reclaimed:    true,
+        tokens_reclaimed: false,
+    });
+    env.finalize(DAVE, auction_id).expect_err("Finalize a lapsed auction");
+    env.update(BOB, "reclaim_expired", Amount::zero(), &AuctionIdParameter {
+        auction_id,
+    })
+    .expect_err("Bob reclaims twice");
+
+    env.update(ALICE, "reclaim_expired", Amount::zero(), &AuctionIdParameter {
+        auction_id,
+    })
+    .expect("Alice reclaims her token");
+    assert_eq!(env.token_balance(Address::Account(ALICE), 1), TokenAmountU64(1));
+    assert_eq!(env.get_auction(auction_id).auction_state, AuctionState::Lapsed {
+        bid_reclaimed:    true,
+        tokens_reclaimed: true,
+    });
+    env.assert_ccd_invariant();
+}
+
+/// Test bidding on behalf of a beneficiary: the beneficiary receives the
+/// tokens, while refunds go back to the account that paid.
+#[test]
+fn test_bid_for_beneficiary() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    let bid_for = |beneficiary: AccountAddress| BidParameter {
+        auction_id,
+        beneficiary: Some(beneficiary),
+        data: AdditionalData::empty(),
+    };
+
+    // Neither the owner nor a bid for the owner is allowed.
+    let for_owner = env.update(BOB, "bid", Amount::from_ccd(1), &bid_for(ALICE)).expect_err("Bid for the owner");
+    let rv: BidError = for_owner.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::OnlyNotOwner);
+
+    let bob_bid = env.update(BOB, "bid", Amount::from_ccd(10), &bid_for(CAROL)).expect("Bob bids for Carol");
+    assert_eq!(env.events(&bob_bid), [
+        AuctionEvent::Bid(BidEventData {
+            auction_id,
+            bidder: BOB,
+            beneficiary: CAROL,
+            amount: Amount::from_ccd(10),
+            data: Vec::new(),
+        }),
+        AuctionEvent::ItemBid(env.item_event(auction_id, 1, Amount::from_ccd(10), Address::Account(BOB)))
+    ]);
+
+    // Bob is outbid and refunded, even though he bid for Carol.
+    let dave_bid = env.update(DAVE, "bid", Amount::from_ccd(20), &bid_for(BOB)).expect("Dave bids for Bob");
+    assert_eq!(dave_bid.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(10), BOB)]);
+    env.assert_ccd_invariant();
+
+    // Dave pays, Bob receives the token.
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(DAVE, auction_id).expect("Finalize auction");
+    assert!(matches!(env.get_auction(auction_id).auction_state, AuctionState::Sold { winner, .. } if winner == BOB));
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+    assert_eq!(env.token_balance(Address::Account(DAVE), 1), TokenAmountU64(0));
+}
+
+/// Test that data attached to a bid is logged with the bid event, and that
+/// data above `MAX_BID_DATA_SIZE` bytes is rejected.
+#[test]
+fn test_bid_data() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    let bid_with = |data: Vec<u8>| BidParameter {
+        auction_id,
+        beneficiary: None,
+        data: AdditionalData::from(data),
+    };
+
+    let too_large = env
+        .update(BOB, "bid", Amount::from_ccd(1), &bid_with(vec![7; MAX_BID_DATA_SIZE + 1]))
+        .expect_err("Bid with too much data");
+    let rv: BidError = too_large.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::BidDataTooLarge);
+
+    let update = env
+        .update(BOB, "bid", Amount::from_ccd(1), &bid_with(vec![7; MAX_BID_DATA_SIZE]))
+        .expect("Bid with the maximum amount of data");
+    assert_eq!(env.events(&update), [
+        AuctionEvent::Bid(BidEventData {
+            auction_id,
+            bidder: BOB,
+            beneficiary: BOB,
+            amount: Amount::from_ccd(1),
+            data: vec![7; MAX_BID_DATA_SIZE],
+        }),
+        AuctionEvent::ItemBid(env.item_event(auction_id, 1, Amount::from_ccd(1), Address::Account(BOB)))
+    ]);
+}
+
+/// Test that when the tokens cannot be delivered to the winner, the CCD stay
+/// in the contract until `retry_delivery` succeeds in delivering the tokens.
+#[test]
+fn test_retry_delivery() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    let retry_parameter = AuctionIdParameter {
+        auction_id,
+    };
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    env.set_fail_transfers(true);
+    let finalize = env.finalize(DAVE, auction_id).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().count(), 0);
+    assert_eq!(env.events(&finalize), [AuctionEvent::DeliveryPending(AuctionEventData {
+        auction_id,
+    })]);
+    assert_eq!(env.get_auction(auction_id).auction_state, AuctionState::PendingDelivery(BOB));
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::from_ccd(10)));
+    let settlement: SettlementStatus = env.view("view_settlement", &retry_parameter);
+    assert!(matches!(settlement, SettlementStatus::Settled(SettlementRecord { tokens_delivered: false, .. })));
+    env.assert_ccd_invariant();
+
+    // The auction cannot be finalized again, and retrying fails while the
+    // token contract keeps rejecting transfers.
+    let again = env.finalize(DAVE, auction_id).expect_err("Finalize auction pending delivery");
+    let rv: BidError = again.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::AuctionAlreadyFinalized);
+    let failed = env.update(DAVE, "retry_delivery", Amount::zero(), &retry_parameter).expect_err("Retry fails");
+    let rv: BidError = failed.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::TransferFailed);
+
+    env.set_fail_transfers(false);
+    let retry = env.update(DAVE, "retry_delivery", Amount::zero(), &retry_parameter).expect("Retry succeeds");
+    assert_eq!(retry.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(1), CAROL),
+        (env.auction, Amount::from_ccd(9), ALICE)
+    ]);
+    assert!(matches!(env.get_auction(auction_id).auction_state, AuctionState::Sold { winner, .. } if winner == BOB));
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+    let settlement: SettlementStatus = env.view("view_settlement", &retry_parameter);
+    assert!(matches!(settlement, SettlementStatus::Settled(SettlementRecord { tokens_delivered: true, .. })));
+    env.assert_ccd_invariant();
+
+    let delivered = env.update(DAVE, "retry_delivery", Amount::zero(), &retry_parameter).expect_err("Already delivered");
+    let rv: BidError = delivered.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::NotPendingDelivery);
+}
+
+/// Test that `create_auction` returns the ID it registers the auction under,
+/// and that IDs are assigned in order.
+#[test]
+fn test_create_auction_id() {
+    let mut env = TestEnv::new();
+    for (token_id, expected_id) in [(1, 0), (2, 1), (3, 2)] {
+        let update = env
+            .update(ALICE, "create_auction", Amount::zero(), &env.auction_parameter("Auction item", token_id))
+            .expect("Create auction");
+        let auction_id: u32 = update.parse_return_value().expect("Return value is valid");
+        assert_eq!(auction_id, expected_id);
+        let registered: Vec<u32> = env
+            .events(&update)
+            .into_iter()
+            .filter_map(|event| match event {
+                AuctionEvent::Register(data) => Some(data.auction_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(registered, [auction_id]);
+        assert_eq!(env.get_auction(auction_id).token_id, TokenIdU8(token_id));
+    }
+}
+
+/// Test listing tokens for auction by transferring them to the contract
+/// with the listing parameters as data, and that transfers without valid
+/// listing data are rejected.
+#[test]
+fn test_list_via_transfer() {
+    let mut env = TestEnv::new();
+    let listing = ListingParameter {
+        item: "Listed item".to_string(),
+        end: AUCTION_END,
+        initial_price: 5,
+        settlement_exclusivity: None,
+        minimum_raise: Raise::Flat(Amount::zero()),
+        requires_owner_approval: false,
+        finalize_reward: Amount::zero(),
+        second_price: false,
+        payout_token: None,
+        accepts_backup_bids: false,
+        alt_bid_token: None,
+    };
+
+    for data in [AdditionalData::empty(), AdditionalData::from(vec![1, 2, 3])] {
+        env.transfer_to_auction(ALICE, 1, data).expect_err("Transfer without listing data");
+        assert_eq!(env.token_balance(Address::Account(ALICE), 1), TokenAmountU64(1));
+    }
+
+    let update = env
+        .transfer_to_auction(ALICE, 1, AdditionalData::from(to_bytes(&TransferData::List(listing))))
+        .expect("Transfer with listing data");
+    assert_eq!(env.events(&update), [
+        AuctionEvent::Register(AuctionEventData {
+            auction_id: 0,
+        }),
+        AuctionEvent::ItemListed(env.item_event(0, 1, Amount::from_micro_ccd(5), Address::Account(ALICE)))
+    ]);
+    let auction = env.get_auction(0);
+    assert_eq!(auction.owner, Address::Account(ALICE));
+    assert_eq!(auction.item, "Listed item");
+    assert_eq!(auction.initial_price, 5);
+    assert_eq!((auction.token_contract, auction.token_id, auction.token_amount), (
+        env.token,
+        TokenIdU8(1),
+        TokenAmountU64(1)
+    ));
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 1), TokenAmountU64(1));
+
+    // The listed auction works like any other.
+    env.bid(BOB, 0, Amount::from_ccd(1)).expect("Bob bids 1 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(BOB, 0).expect("Finalize auction");
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+}
+
+/// Test that relisting an ended auction without bids moves its tokens to a
+/// new auction, closing the old one so that it takes no bids and returns no
+/// tokens when finalized.
+#[test]
+fn test_relist_auction() {
+    let mut env = TestEnv::new();
+    let old_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    let update = env
+        .update(ALICE, "relist_auction", Amount::zero(), &RelistParameter {
+            auction_id:    old_id,
+            end:           Timestamp::from_timestamp_millis(5000),
+            initial_price: Some(5),
+        })
+        .expect("Relist the auction");
+    let new_id = old_id + 1;
+    assert_eq!(env.events(&update), [
+        AuctionEvent::Close(AuctionEventData {
+            auction_id: old_id,
+        }),
+        AuctionEvent::Register(AuctionEventData {
+            auction_id: new_id,
+        }),
+        AuctionEvent::ItemUnlisted(env.item_event(old_id, 1, Amount::zero(), Address::Account(ALICE))),
+        AuctionEvent::ItemListed(env.item_event(new_id, 1, Amount::from_micro_ccd(5), Address::Account(ALICE)))
+    ]);
+    assert_eq!(env.get_auction(old_id).auction_state, AuctionState::Superseded(new_id));
+    assert_eq!(env.get_auction(new_id).initial_price, 5);
+
+    // The old auction takes no bids, and finalizing it returns no tokens.
+    env.bid(BOB, old_id, Amount::from_ccd(1)).expect_err("Bid on the old auction");
+    let rv: BidError = env
+        .finalize(DAVE, old_id)
+        .expect_err("Finalize the old auction")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::AuctionAlreadyFinalized);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 1), TokenAmountU64(0));
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 1), TokenAmountU64(1));
+    env.assert_token_invariant();
+
+    // The new auction sells the escrowed tokens.
+    env.bid(BOB, new_id, Amount::from_ccd(1)).expect("Bob bids 1 CCD");
+    env.chain.tick_block_time(Duration::from_millis(4000)).expect("Increment chain time");
+    env.finalize(DAVE, new_id).expect("Finalize the new auction");
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+    env.assert_ccd_invariant();
+}
+
+/// Test that the owner can cancel an auction with bids before its end by
+/// paying the cancellation fee to the highest bidder, and an auction without
+/// bids for free.
+#[test]
+fn test_cancel_with_fee() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("With bids", 1));
+    let unbid_id = env.create_auction(ALICE, &env.auction_parameter("Without bids", 2));
+    let ended_id = env.create_auction(ALICE, &env.auction_parameter("Ended", 3));
+    let cancel = |auction_id| AuctionIdParameter {
+        auction_id,
+    };
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+
+    let not_owner = env.update(BOB, "cancel_with_fee", Amount::zero(), &cancel(auction_id)).expect_err("Not the owner");
+    let rv: BidError = not_owner.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::OnlyOwner);
+
+    // The fee is 5% of the highest bid, and has to be attached exactly.
+    for wrong_fee in [Amount::zero(), Amount::from_ccd(1)] {
+        let update = env.update(ALICE, "cancel_with_fee", wrong_fee, &cancel(auction_id)).expect_err("Wrong fee");
+        let rv: BidError = update.parse_return_value().expect("Return value is valid");
+        assert_eq!(rv, BidError::WrongCancellationFee);
+    }
+
+    let fee = Amount::from_micro_ccd(500_000);
+    let update = env.update(ALICE, "cancel_with_fee", fee, &cancel(auction_id)).expect("Cancel with fee");
+    assert_eq!(update.account_transfers().collect::<Vec<_>>()[..], [(
+        env.auction,
+        Amount::from_ccd(10) + fee,
+        BOB
+    )]);
+    assert_eq!(env.events(&update), [
+        AuctionEvent::Refund(RefundEventData {
+            auction_id: Some(auction_id),
+            account:    BOB,
+            amount:     Amount::from_ccd(10) + fee,
+            reason:     RefundReason::Cancelled,
+        }),
+        AuctionEvent::Cancel(CancelEventData {
+            auction_id,
+            bidder: Some(BOB),
+            refund: Amount::from_ccd(10),
+            fee,
+        }),
+        AuctionEvent::ItemUnlisted(env.item_event(auction_id, 1, Amount::zero(), Address::Account(ALICE)))
+    ]);
+    assert_eq!(env.get_auction(auction_id).auction_state, AuctionState::Cancelled);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 1), TokenAmountU64(1));
+    env.assert_ccd_invariant();
+
+    // Without bids, no fee is due.
+    env.update(ALICE, "cancel_with_fee", Amount::zero(), &cancel(unbid_id)).expect("Cancel without bids");
+    assert_eq!(env.get_auction(unbid_id).auction_state, AuctionState::Cancelled);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 2), TokenAmountU64(1));
+
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let ended = env.update(ALICE, "cancel_with_fee", Amount::zero(), &cancel(ended_id)).expect_err("Auction ended");
+    let rv: BidError = ended.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::AuctionEnded);
+}
+
+/// Test that the owner of an auction requiring approval can accept or
+/// reject the winning bid within the approval window, and that the auction is
+/// settled by default once the window is over.
+#[test]
+fn test_owner_approval() {
+    let mut env = TestEnv::new();
+    let create = |env: &mut TestEnv, token_id| {
+        let parameter = NewAuctionParameter {
+            requires_owner_approval: true,
+            ..env.auction_parameter("Auction item", token_id)
+        };
+        let auction_id = env.create_auction(ALICE, &parameter);
+        env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+        AuctionIdParameter {
+            auction_id,
+        }
+    };
+    let accepted = create(&mut env, 1);
+    let rejected = create(&mut env, 2);
+    let defaulted = create(&mut env, 3);
+
+    assert_error(env.update(ALICE, "accept_winning_bid", Amount::zero(), &accepted), BidError::AuctionStillActive);
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    assert_error(env.finalize(DAVE, accepted.auction_id), BidError::AwaitingOwnerApproval);
+    assert_error(env.update(BOB, "accept_winning_bid", Amount::zero(), &accepted), BidError::OnlyOwner);
+
+    let accept = env.update(ALICE, "accept_winning_bid", Amount::zero(), &accepted).expect("Accept winning bid");
+    assert_eq!(env.events(&accept)[0], AuctionEvent::Approval(ApprovalEventData {
+        auction_id: accepted.auction_id,
+        outcome:    ApprovalOutcome::Accepted,
+    }));
+    assert!(matches!(env.get_auction(accepted.auction_id).auction_state, AuctionState::Sold { winner, .. } if winner == BOB));
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+
+    let reject = env.update(ALICE, "reject_winning_bid", Amount::zero(), &rejected).expect("Reject winning bid");
+    assert_eq!(reject.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(10), BOB)]);
+    assert_eq!(env.events(&reject), [
+        AuctionEvent::Approval(ApprovalEventData {
+            auction_id: rejected.auction_id,
+            outcome:    ApprovalOutcome::Rejected,
+        }),
+        AuctionEvent::Refund(RefundEventData {
+            auction_id: Some(rejected.auction_id),
+            account:    BOB,
+            amount:     Amount::from_ccd(10),
+            reason:     RefundReason::Rejected,
+        }),
+        AuctionEvent::ItemReturned(env.item_event(rejected.auction_id, 2, Amount::zero(), Address::Account(ALICE)))
+    ]);
+    assert_eq!(env.get_auction(rejected.auction_id).auction_state, AuctionState::Rejected);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 2), TokenAmountU64(1));
+    env.assert_ccd_invariant();
+
+    // Once the window is over, the owner can no longer decide and anyone can
+    // settle the auction.
+    env.chain.tick_block_time(Duration::from_hours(72)).expect("Increment chain time");
+    assert_error(env.update(ALICE, "reject_winning_bid", Amount::zero(), &defaulted), BidError::ApprovalWindowOver);
+    let finalize = env.finalize(DAVE, defaulted.auction_id).expect("Settle by default");
+    assert_eq!(env.events(&finalize)[0], AuctionEvent::Approval(ApprovalEventData {
+        auction_id: defaulted.auction_id,
+        outcome:    ApprovalOutcome::SettledByDefault,
+    }));
+    assert!(matches!(env.get_auction(defaulted.auction_id).auction_state, AuctionState::Sold { winner, .. } if winner == BOB));
+    env.assert_ccd_invariant();
+}
+
+/// Test that the finalize reward is paid to the finalizer out of the owner's
+/// share, capped to a share of the highest bid, and only if there is a
+/// winner.
+#[test]
+fn test_finalize_reward() {
+    let mut env = TestEnv::new();
+    let with_reward = |env: &TestEnv, token_id, finalize_reward| NewAuctionParameter {
+        finalize_reward,
+        ..env.auction_parameter("Auction item", token_id)
+    };
+
+    let too_large = env
+        .update(ALICE, "create_auction", Amount::zero(), &with_reward(&env, 1, MAX_FINALIZE_REWARD + Amount::from_micro_ccd(1)))
+        .expect_err("Finalize reward above the maximum");
+    let rv: BidError = too_large.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::InvalidFinalizeReward);
+
+    let uncapped = env.create_auction(ALICE, &with_reward(&env, 1, Amount::from_micro_ccd(200_000)));
+    let capped = env.create_auction(ALICE, &with_reward(&env, 2, Amount::from_ccd(1)));
+    let unbid = env.create_auction(ALICE, &with_reward(&env, 3, Amount::from_ccd(1)));
+    env.bid(BOB, uncapped, Amount::from_ccd(50)).expect("Bob bids 50 CCD");
+    env.bid(BOB, capped, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    // The reward is below 1% of the highest bid, so it is paid in full.
+    let finalize = env.finalize(DAVE, uncapped).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(5), CAROL),
+        (env.auction, Amount::from_micro_ccd(200_000), DAVE),
+        (env.auction, Amount::from_micro_ccd(44_800_000), ALICE)
+    ]);
+
+    // The reward is capped to 1% of the highest bid.
+    let finalize = env.finalize(DAVE, capped).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(1), CAROL),
+        (env.auction, Amount::from_micro_ccd(100_000), DAVE),
+        (env.auction, Amount::from_micro_ccd(8_900_000), ALICE)
+    ]);
+    assert!(env.events(&finalize).contains(&AuctionEvent::Finalize(FinalizeEventData {
+        auction_id:         capped,
+        finalizer:          Address::Account(DAVE),
+        commission_payouts: vec![(CAROL, Amount::from_ccd(1))],
+        finalize_reward:    Amount::from_micro_ccd(100_000),
+        price:              Amount::from_ccd(10),
+        winner:             Some(BOB),
+        settled_at:         env.chain.block_time(),
+    })));
+
+    // Without a winner there is nothing to pay the reward from.
+    let finalize = env.finalize(DAVE, unbid).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().count(), 0);
+    env.assert_ccd_invariant();
+}
+
+/// Test that the finalize reward is capped to what the commission leaves of
+/// the price, so that a commission of the whole price leaves no reward and
+/// nothing is paid out beyond the price.
+#[test]
+fn test_finalize_reward_full_commission() {
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        commission_tiers: vec![CommissionTier {
+            threshold: Amount::zero(),
+            bps:       TOTAL_BASIS_POINTS,
+        }],
+        ..default_init_parameter()
+    });
+    let auction_id = env.create_auction(ALICE, &NewAuctionParameter {
+        finalize_reward: Amount::from_ccd(1),
+        ..env.auction_parameter("Auction item", 1)
+    });
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    let preview: SettlementPreview = env.view("view_settlement_preview", &AuctionIdParameter {
+        auction_id,
+    });
+    assert_eq!(
+        (preview.commission, preview.finalize_reward, preview.owner_amount),
+        (Amount::from_ccd(10), Amount::zero(), Amount::zero())
+    );
+
+    let finalize = env.finalize(DAVE, auction_id).expect("Finalize auction");
+    assert!(env.events(&finalize).iter().any(|event| matches!(event, AuctionEvent::Finalize(data)
+        if data.finalize_reward == Amount::zero() && data.commission_payouts == [(CAROL, Amount::from_ccd(10))])));
+    let settlement: SettlementStatus = env.view("view_settlement", &AuctionIdParameter {
+        auction_id,
+    });
+    assert!(matches!(settlement, SettlementStatus::Settled(record)
+        if record.seller_amount == Amount::zero() && record.finalize_reward == Amount::zero()));
+    assert_eq!(env.view::<EscrowSummary>("view_escrow_summary", &()).pending_refunds, Amount::zero());
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::zero()));
+    env.assert_ccd_invariant();
+}
+
+/// Test that the lifetime statistics count created auctions and settled
+/// sales, but not auctions pending delivery or ended without bids.
+#[test]
+fn test_stats() {
+    let mut env = TestEnv::new();
+    let sold = env.create_auction(ALICE, &env.auction_parameter("Sold", 1));
+    let pending = env.create_auction(ALICE, &env.auction_parameter("Pending delivery", 2));
+    let unbid = env.create_auction(ALICE, &env.auction_parameter("Without bids", 3));
+    env.bid(BOB, sold, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(BOB, pending, Amount::from_ccd(20)).expect("Bob bids 20 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    env.finalize(DAVE, sold).expect("Finalize auction");
+    env.finalize(DAVE, unbid).expect("Finalize auction");
+    env.set_fail_transfers(true);
+    env.finalize(DAVE, pending).expect("Finalize auction");
+
+    let expected = Stats {
+        auctions_created: 3,
+        auctions_sold:    1,
+        volume_settled:   Amount::from_ccd(10),
+        commission_paid:  Amount::from_ccd(1),
+    };
+    assert_eq!(env.view::<Stats>("view_stats", &()), expected);
+    // Views do not change the statistics.
+    assert_eq!(env.view::<Stats>("view_stats", &()), expected);
+}
+
+/// Test that auctions with an item description longer than
+/// `MAX_ITEM_LENGTH` bytes are rejected, whether created or listed.
+#[test]
+fn test_item_length() {
+    let mut env = TestEnv::new();
+    // Multi-byte characters count with their encoded length.
+    let too_long = "é".repeat(MAX_ITEM_LENGTH / 2) + "x";
+    let parameter = env.auction_parameter(&too_long, 1);
+
+    let update = env.update(ALICE, "create_auction", Amount::zero(), &parameter).expect_err("Item too long");
+    let rv: BidError = update.parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::InvalidItemDescription);
+
+    let listing = ListingParameter {
+        item: too_long,
+        end: AUCTION_END,
+        initial_price: 0,
+        settlement_exclusivity: None,
+        minimum_raise: Raise::Flat(Amount::zero()),
+        requires_owner_approval: false,
+        finalize_reward: Amount::zero(),
+        second_price: false,
+        payout_token: None,
+        accepts_backup_bids: false,
+        alt_bid_token: None,
+    };
+    env.transfer_to_auction(ALICE, 1, AdditionalData::from(to_bytes(&TransferData::List(listing)))).expect_err("Item too long");
+    assert_eq!(env.token_balance(Address::Account(ALICE), 1), TokenAmountU64(1));
+
+    let longest = env.auction_parameter(&"é".repeat(MAX_ITEM_LENGTH / 2), 1);
+    env.create_auction(ALICE, &longest);
+}
+
+/// Test that commission changes only become effective after the delay, and
+/// that auctions keep the commission tiers they were listed with.
+#[test]
+fn test_commission_change() {
+    let mut env = TestEnv::new();
+    let change = |effective_at| CommissionChange {
+        commission_tiers: vec![CommissionTier {
+            threshold: Amount::zero(),
+            bps:       2000,
+        }],
+        commission_recipients: None,
+        effective_at,
+    };
+    let delay_over = env.chain.block_time().checked_add(Duration::from_days(7)).expect("Valid timestamp");
+
+    let old_rate = env.create_auction(ALICE, &env.auction_parameter("Old rate", 1));
+    env.bid(BOB, old_rate, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+
+    let too_soon = change(Timestamp::from_timestamp_millis(delay_over.millis - 1));
+    assert_error(env.update(CAROL, "schedule_commission_change", Amount::zero(), &too_soon), BidError::CommissionChangeTooSoon);
+    assert_error(env.update(BOB, "schedule_commission_change", Amount::zero(), &change(delay_over)), BidError::OnlyAdmin);
+
+    // A scheduled change can be cancelled.
+    env.update(CAROL, "schedule_commission_change", Amount::zero(), &change(delay_over)).expect("Schedule change");
+    let cancel = env.update(CAROL, "cancel_commission_change", Amount::zero(), &()).expect("Cancel change");
+    assert_eq!(env.events(&cancel), [AuctionEvent::CommissionChangeCancelled(change(delay_over))]);
+    assert_eq!(env.view::<Option<CommissionChange>>("view_pending_commission_change", &()), None);
+    assert_error(env.update(CAROL, "cancel_commission_change", Amount::zero(), &()), BidError::NoPendingCommissionChange);
+
+    let schedule = env.update(CAROL, "schedule_commission_change", Amount::zero(), &change(delay_over)).expect("Schedule change");
+    assert_eq!(env.events(&schedule), [AuctionEvent::CommissionChangeScheduled(change(delay_over))]);
+    assert_eq!(env.view::<Option<CommissionChange>>("view_pending_commission_change", &()), Some(change(delay_over)));
+    assert_error(env.update(DAVE, "apply_commission_change", Amount::zero(), &()), BidError::CommissionChangeNotDue);
+
+    env.chain.tick_block_time(Duration::from_days(7)).expect("Increment chain time");
+    let apply = env.update(DAVE, "apply_commission_change", Amount::zero(), &()).expect("Apply change");
+    assert_eq!(env.events(&apply), [AuctionEvent::CommissionChangeApplied(change(delay_over))]);
+
+    let new_rate = env.create_auction(ALICE, &NewAuctionParameter {
+        end: delay_over.checked_add(Duration::from_days(1)).expect("Valid timestamp"),
+        ..env.auction_parameter("New rate", 2)
+    });
+    env.bid(BOB, new_rate, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_days(2)).expect("Increment chain time");
+
+    let finalize = env.finalize(DAVE, old_rate).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(1), CAROL),
+        (env.auction, Amount::from_ccd(9), ALICE)
+    ]);
+    let finalize = env.finalize(DAVE, new_rate).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(2), CAROL),
+        (env.auction, Amount::from_ccd(8), ALICE)
+    ]);
+}
+
+/// Test a reverse auction: sellers ask descending prices within the budget,
+/// and after the end the lowest asker delivers the tokens and is paid, with
+/// the rest of the budget refunded. Without asks the budget is refunded.
+#[test]
+fn test_reverse_auction() {
+    let mut env = TestEnv::new();
+    let parameter = ReverseAuctionParameter {
+        item: "Wanted item".to_string(),
+        end: AUCTION_END,
+        token_contract: env.token,
+        token_id: TokenIdU8(1),
+        token_amount: TokenAmountU64(1),
+    };
+    let create = |env: &mut TestEnv, budget| -> u32 {
+        env.update(DAVE, "create_reverse_auction", budget, &parameter)
+            .expect("Create reverse auction")
+            .parse_return_value()
+            .expect("Return value is valid")
+    };
+    let auction_id = create(&mut env, Amount::from_ccd(100));
+    let unfilled_id = create(&mut env, Amount::from_ccd(50));
+    let ask = |ask| AskParameter {
+        auction_id,
+        ask,
+    };
+    env.assert_ccd_invariant();
+
+    env.update(ALICE, "submit_ask", Amount::zero(), &ask(Amount::from_ccd(80))).expect("Alice asks 80 CCD");
+    assert_error(env.update(CAROL, "submit_ask", Amount::zero(), &ask(Amount::from_ccd(80))), BidError::AskNotBelowCurrentAsk);
+    assert_error(env.update(CAROL, "submit_ask", Amount::zero(), &ask(Amount::from_ccd(101))), BidError::AskAboveBudget);
+    assert_error(env.update(DAVE, "submit_ask", Amount::zero(), &ask(Amount::from_ccd(60))), BidError::OnlyNotOwner);
+    assert_error(env.bid(BOB, auction_id, Amount::from_ccd(1)), BidError::WrongAuctionKind);
+    let update = env.update(CAROL, "submit_ask", Amount::zero(), &ask(Amount::from_ccd(70))).expect("Carol asks 70 CCD");
+    assert_eq!(env.events(&update), [AuctionEvent::Ask(AskEventData {
+        auction_id,
+        asker: CAROL,
+        ask: Amount::from_ccd(70),
+    })]);
+
+    // Only the lowest asker can deliver, and only after the end.
+    let delivery = AdditionalData::from(to_bytes(&TransferData::DeliverAsk(auction_id)));
+    env.transfer_to_auction(CAROL, 1, delivery.clone()).expect_err("Delivery before the end");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    assert_error(env.finalize(BOB, auction_id), BidError::AwaitingDelivery);
+    env.transfer_to_auction(ALICE, 1, delivery.clone()).expect_err("Delivery by another asker");
+
+    // Carol is paid her ask minus the 10% commission (which she also receives
+    // as the commission recipient), and Dave gets the rest of his budget back.
+    let update = env.transfer_to_auction(CAROL, 1, delivery).expect("Carol delivers the token");
+    assert_eq!(update.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(7), CAROL),
+        (env.auction, Amount::from_ccd(63), CAROL),
+        (env.auction, Amount::from_ccd(30), DAVE)
+    ]);
+    assert_eq!(env.token_balance(Address::Account(DAVE), 1), TokenAmountU64(1));
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 1), TokenAmountU64(0));
+    assert!(matches!(env.get_auction(auction_id).auction_state, AuctionState::Sold { winner, .. } if winner == CAROL));
+    env.assert_ccd_invariant();
+
+    let update = env.finalize(BOB, unfilled_id).expect("Finalize unfilled reverse auction");
+    assert_eq!(update.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(50), DAVE)]);
+    assert_eq!(env.get_auction(unfilled_id).auction_state, AuctionState::Unsold);
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::zero()));
+}
+
+/// Test that the budget of a reverse auction is refunded when the lowest
+/// asker never delivers: by its owner right after the end, and by anyone once
+/// the delivery window is over.
+#[test]
+fn test_reverse_auction_undelivered() {
+    let mut env = TestEnv::new();
+    let parameter = ReverseAuctionParameter {
+        item: "Wanted item".to_string(),
+        end: AUCTION_END,
+        token_contract: env.token,
+        token_id: TokenIdU8(1),
+        token_amount: TokenAmountU64(1),
+    };
+    let create = |env: &mut TestEnv| -> u32 {
+        let auction_id = env
+            .update(DAVE, "create_reverse_auction", Amount::from_ccd(100), &parameter)
+            .expect("Create reverse auction")
+            .parse_return_value()
+            .expect("Return value is valid");
+        env.update(CAROL, "submit_ask", Amount::zero(), &AskParameter {
+            auction_id,
+            ask: Amount::from_ccd(70),
         })
-        .expect("Initialize auction");
-    
-    (chain, init.contract_address)
-}
\ No newline at end of file
+        .expect("Carol asks 70 CCD");
+        auction_id
+    };
+    let owner_id = create(&mut env);
+    let anyone_id = create(&mut env);
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    // Dave stops waiting for the delivery as soon as the auction ended.
+    assert_error(env.finalize(BOB, owner_id), BidError::AwaitingDelivery);
+    let update = env.finalize(DAVE, owner_id).expect("Owner refunds the budget");
+    assert_eq!(update.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(100), DAVE)]);
+    assert_eq!(env.get_auction(owner_id).auction_state, AuctionState::Unsold);
+    let delivery = AdditionalData::from(to_bytes(&TransferData::DeliverAsk(owner_id)));
+    env.transfer_to_auction(CAROL, 1, delivery).expect_err("Delivery after the refund");
+    env.assert_ccd_invariant();
+
+    // Anyone can refund the budget once the delivery window is over.
+    env.chain.tick_block_time(Duration::from_days(7)).expect("Increment chain time");
+    let update = env.finalize(BOB, anyone_id).expect("Anyone refunds the budget");
+    assert_eq!(update.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(100), DAVE)]);
+    assert_eq!(env.get_auction(anyone_id).auction_state, AuctionState::Unsold);
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::zero()));
+    env.assert_ccd_invariant();
+}
+
+/// Test that the winner of a second price auction pays the second highest
+/// bid, or the initial price after a single bid, and is refunded the rest.
+#[test]
+fn test_second_price() {
+    let mut env = TestEnv::new();
+    let create = |env: &mut TestEnv, token_id| {
+        let parameter = NewAuctionParameter {
+            initial_price: 1_000_000,
+            second_price: true,
+            ..env.auction_parameter("Auction item", token_id)
+        };
+        env.create_auction(ALICE, &parameter)
+    };
+    let single_bid = create(&mut env, 1);
+    let two_bids = create(&mut env, 2);
+    let tie = create(&mut env, 3);
+
+    env.bid(BOB, single_bid, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(BOB, two_bids, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(DAVE, two_bids, Amount::from_ccd(15)).expect("Dave bids 15 CCD");
+    // A bid matching the highest bid is rejected and does not become the
+    // second highest bid; a top-up does not change it either.
+    env.bid(BOB, tie, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(DAVE, tie, Amount::from_ccd(12)).expect("Dave bids 12 CCD");
+    let rv: BidError =
+        env.bid(BOB, tie, Amount::from_ccd(12)).expect_err("Tie").parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::BidTiesCurrentBid);
+    env.bid(DAVE, tie, Amount::from_ccd(1)).expect("Dave tops up by 1 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    // A single bid pays the initial price.
+    let finalize = env.finalize(CAROL, single_bid).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_micro_ccd(100_000), CAROL),
+        (env.auction, Amount::from_micro_ccd(900_000), ALICE),
+        (env.auction, Amount::from_ccd(9), BOB)
+    ]);
+    assert!(env.events(&finalize).contains(&AuctionEvent::Finalize(FinalizeEventData {
+        auction_id:         single_bid,
+        finalizer:          Address::Account(CAROL),
+        commission_payouts: vec![(CAROL, Amount::from_micro_ccd(100_000))],
+        finalize_reward:    Amount::zero(),
+        price:              Amount::from_ccd(1),
+        winner:             Some(BOB),
+        settled_at:         env.chain.block_time(),
+    })));
+
+    let finalize = env.finalize(CAROL, two_bids).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(1), CAROL),
+        (env.auction, Amount::from_ccd(9), ALICE),
+        (env.auction, Amount::from_ccd(5), DAVE)
+    ]);
+
+    let finalize = env.finalize(CAROL, tie).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(1), CAROL),
+        (env.auction, Amount::from_ccd(9), ALICE),
+        (env.auction, Amount::from_ccd(3), DAVE)
+    ]);
+    assert!(matches!(env.get_auction(tie).auction_state, AuctionState::Sold { winner, .. } if winner == DAVE));
+    env.assert_ccd_invariant();
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::zero()));
+}
+
+/// Test that a backup bid of a second price auction only sets the price if
+/// it still stands at settlement: a withdrawn backup bid sets none, and one
+/// above the outbid highest bid sets the price instead of it.
+#[test]
+fn test_second_price_backup_bids() {
+    let mut env = TestEnv::new();
+    let create = |env: &mut TestEnv, token_id| {
+        let parameter = NewAuctionParameter {
+            initial_price: 1_000_000,
+            second_price: true,
+            minimum_raise: Raise::Flat(Amount::from_ccd(5)),
+            accepts_backup_bids: true,
+            ..env.auction_parameter("Auction item", token_id)
+        };
+        env.create_auction(ALICE, &parameter)
+    };
+    let withdrawn = create(&mut env, 1);
+    let standing = create(&mut env, 2);
+    let price = |env: &TestEnv, finalize: &ContractInvokeSuccess| {
+        env.events(finalize).iter().find_map(|event| match event {
+            AuctionEvent::Finalize(data) => Some(data.price),
+            _ => None,
+        })
+    };
+
+    // Dave's backup bid of 14 CCD is short of the minimum raise over 10 CCD,
+    // and withdrawing it leaves Bob's single bid paying the initial price
+    env.bid(BOB, withdrawn, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(DAVE, withdrawn, Amount::from_ccd(14)).expect("Dave places a backup bid of 14 CCD");
+    env.update(DAVE, "withdraw_backup_bid", Amount::zero(), &AuctionIdParameter {
+        auction_id: withdrawn,
+    })
+    .expect("Dave withdraws");
+
+    // Carol outbids Bob while Dave's backup bid of 14 CCD stands, so Carol
+    // pays 14 CCD rather than Bob's outbid 10 CCD
+    env.bid(BOB, standing, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(DAVE, standing, Amount::from_ccd(14)).expect("Dave places a backup bid of 14 CCD");
+    env.bid(CAROL, standing, Amount::from_ccd(20)).expect("Carol bids 20 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    let preview: SettlementPreview = env.view("view_settlement_preview", &AuctionIdParameter {
+        auction_id: standing,
+    });
+    assert_eq!((preview.price, preview.refund), (Amount::from_ccd(14), Amount::from_ccd(6)));
+
+    let finalize = env.finalize(BOB, withdrawn).expect("Finalize auction");
+    assert_eq!(price(&env, &finalize), Some(Amount::from_ccd(1)));
+    let finalize = env.finalize(BOB, standing).expect("Finalize auction");
+    assert_eq!(price(&env, &finalize), Some(Amount::from_ccd(14)));
+    let events = env.events(&finalize);
+    assert!(events.contains(&AuctionEvent::Refund(RefundEventData {
+        auction_id: Some(standing),
+        account:    CAROL,
+        amount:     Amount::from_ccd(6),
+        reason:     RefundReason::Overpaid,
+    })));
+    assert!(events.contains(&AuctionEvent::Refund(RefundEventData {
+        auction_id: Some(standing),
+        account:    DAVE,
+        amount:     Amount::from_ccd(14),
+        reason:     RefundReason::BackupReleased,
+    })));
+    env.assert_ccd_invariant();
+}
+
+/// Test tiered commission: the rate of the highest tier not above the sale
+/// price applies to the whole price, so 5% below 1000 CCD, 3% from 1000 CCD
+/// and 1% from 10000 CCD.
+#[test]
+fn test_tiered_commission() {
+    let tier = |ccd, bps| CommissionTier {
+        threshold: Amount::from_ccd(ccd),
+        bps,
+    };
+    let tiers = vec![tier(0, 500), tier(1000, 300), tier(10000, 100)];
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        commission_tiers: tiers.clone(),
+        ..default_init_parameter()
+    });
+    let quote = |env: &TestEnv, price| env.view::<CommissionQuote>("view_commission_for", &price);
+
+    assert_eq!(quote(&env, Amount::from_micro_ccd(999_999_999)), CommissionQuote {
+        bps:        500,
+        commission: Amount::from_micro_ccd(49_999_999),
+    });
+    assert_eq!(quote(&env, Amount::from_ccd(1000)), CommissionQuote {
+        bps:        300,
+        commission: Amount::from_ccd(30),
+    });
+    assert_eq!(quote(&env, Amount::from_ccd(10000)), CommissionQuote {
+        bps:        100,
+        commission: Amount::from_ccd(100),
+    });
+
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    env.bid(BOB, auction_id, Amount::from_ccd(2000)).expect("Bob bids 2000 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let finalize = env.finalize(DAVE, auction_id).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(60), CAROL),
+        (env.auction, Amount::from_ccd(1940), ALICE)
+    ]);
+
+    // Tiers must start at zero, have increasing thresholds and valid rates.
+    let (mut chain, module_reference) = initialize_chain();
+    for commission_tiers in
+        [vec![], vec![tier(1, 500)], vec![tier(0, 500), tier(1000, 300), tier(1000, 100)], vec![tier(0, 10001)]]
+    {
+        let parameter = InitParameter {
+            commission_tiers,
+            ..default_init_parameter()
+        };
+        init_auction(&mut chain, module_reference, &parameter).expect_err("Invalid commission tiers");
+    }
+    let change = CommissionChange {
+        commission_tiers:      vec![tier(0, 500), tier(0, 300)],
+        commission_recipients: None,
+        effective_at:     env.chain.block_time().checked_add(Duration::from_days(7)).expect("Valid timestamp"),
+    };
+    let rv: BidError = env
+        .update(CAROL, "schedule_commission_change", Amount::zero(), &change)
+        .expect_err("Invalid commission tiers")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::InvalidCommissionRate);
+}
+
+/// Test that new commission recipients are only paid once the scheduled
+/// change is applied after the delay.
+#[test]
+fn test_commission_recipients_change() {
+    let mut env = TestEnv::new();
+    let recipients = |shares: &[(AccountAddress, u16)]| {
+        shares
+            .iter()
+            .map(|&(account, share_bps)| CommissionShare {
+                recipient: CommissionSink::Account(account),
+                share_bps,
+            })
+            .collect::<Vec<_>>()
+    };
+    let effective_at = env.chain.block_time().checked_add(Duration::from_days(7)).expect("Valid timestamp");
+    let change = |commission_recipients| CommissionChange {
+        commission_tiers: default_init_parameter().commission_tiers,
+        commission_recipients,
+        effective_at,
+    };
+    let view_recipients = |env: &TestEnv| {
+        let ConfigView::V1(config) = env.view("view_config", &());
+        config.commission_recipients
+    };
+
+    // Shares must add up to the whole commission.
+    let rv: BidError = env
+        .update(CAROL, "schedule_commission_change", Amount::zero(), &change(Some(recipients(&[(DAVE, 5000)]))))
+        .expect_err("Invalid commission shares")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::InvalidCommissionShares);
+
+    let new_recipients = recipients(&[(DAVE, 10000)]);
+    env.update(CAROL, "schedule_commission_change", Amount::zero(), &change(Some(new_recipients.clone())))
+        .expect("Schedule change");
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let finalize = env.finalize(DAVE, auction_id).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(1), CAROL),
+        (env.auction, Amount::from_ccd(9), ALICE)
+    ]);
+    assert_eq!(view_recipients(&env), recipients(&[(CAROL, 10000)]));
+
+    env.chain.tick_block_time(Duration::from_days(7)).expect("Increment chain time");
+    env.update(DAVE, "apply_commission_change", Amount::zero(), &()).expect("Apply change");
+    assert_eq!(view_recipients(&env), new_recipients);
+}
+
+/// Test that a share of the commission can be paid into a treasury contract,
+/// with the auction ID as memo, and that a treasury rejecting it does not
+/// block the settlement, but is credited the share as proceeds.
+#[test]
+fn test_commission_sink() {
+    // The treasury is the first contract initialized after the environment
+    let treasury = ContractAddress::new(2, 0);
+    let recipients = vec![
+        CommissionShare {
+            recipient: CommissionSink::Contract {
+                address:    treasury,
+                entrypoint: OwnedEntrypointName::new_unchecked("deposit".to_string()),
+            },
+            share_bps: 5000,
+        },
+        CommissionShare {
+            recipient: CommissionSink::Account(CAROL),
+            share_bps: 5000,
+        },
+    ];
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        commission_recipients: recipients,
+        ..default_init_parameter()
+    });
+    assert_eq!(env.init_mock("treasury_mock"), treasury);
+    let sink_event = |auction_id| CommissionSinkEventData {
+        auction_id,
+        contract: treasury,
+        amount: Amount::from_ccd(1),
+    };
+
+    let deposited = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    env.bid(BOB, deposited, Amount::from_ccd(20)).expect("Bob bids 20 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let finalize = env.finalize(DAVE, deposited).expect("Finalize auction");
+    let events = env.events(&finalize);
+    assert!(events.contains(&AuctionEvent::CommissionDeposited(sink_event(deposited))));
+    assert!(events.iter().any(|event| matches!(event, AuctionEvent::Finalize(data)
+        if data.commission_payouts == [(CAROL, Amount::from_ccd(1))])));
+    let deposits: Vec<(u32, Amount)> = env
+        .chain
+        .contract_invoke(CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      treasury,
+            receive_name: OwnedReceiveName::new_unchecked("treasury_mock.deposits".to_string()),
+            message:      OwnedParameter::empty(),
+        })
+        .expect("View deposits")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(deposits, [(deposited, Amount::from_ccd(1))]);
+    assert_eq!(env.chain.contract_balance(treasury), Some(Amount::from_ccd(1)));
+
+    // A treasury rejecting the deposit is credited the share to claim.
+    env.set_fail_deposits(treasury, true);
+    let accrued = env.create_auction(ALICE, &NewAuctionParameter {
+        end: Timestamp::from_timestamp_millis(2000),
+        ..env.auction_parameter("Auction item", 2)
+    });
+    env.bid(BOB, accrued, Amount::from_ccd(20)).expect("Bob bids 20 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1000)).expect("Increment chain time");
+    let finalize = env.finalize(DAVE, accrued).expect("Finalize auction despite the rejected deposit");
+    assert!(env.events(&finalize).contains(&AuctionEvent::CommissionAccrued(sink_event(accrued))));
+    assert!(matches!(env.get_auction(accrued).auction_state, AuctionState::Sold { .. }));
+    assert_eq!(env.view::<Amount>("view_proceeds", &treasury), Amount::from_ccd(1));
+    assert_eq!(env.chain.contract_balance(treasury), Some(Amount::from_ccd(1)));
+    env.assert_ccd_invariant();
+}
+
+/// Test that a commission share that cannot be transferred to its account
+/// does not block the settlement: the winner still receives the tokens and
+/// the owner the proceeds, and the share is kept for the account to claim.
+#[test]
+fn test_deferred_commission() {
+    let frozen = AccountAddress([9; 32]);
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        commission_recipients: vec![CommissionShare {
+            recipient: CommissionSink::Account(frozen),
+            share_bps: 10000,
+        }],
+        ..default_init_parameter()
+    });
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    let finalize = env.finalize(DAVE, auction_id).expect("Finalize despite the failed commission transfer");
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(9), ALICE)]);
+    let events = env.events(&finalize);
+    assert!(events.contains(&AuctionEvent::PayoutDeferred(PayoutDeferredEventData {
+        auction_id,
+        account: frozen,
+        amount: Amount::from_ccd(1),
+        payout: PayoutKind::Commission,
+    })));
+    assert!(events.iter().any(|event| matches!(event, AuctionEvent::Finalize(data) if data.commission_payouts.is_empty())));
+    assert!(matches!(env.get_auction(auction_id).auction_state, AuctionState::Sold { winner, .. } if winner == BOB));
+    let refunds: Vec<(AccountAddress, Amount)> = env.view("view_refunds", &PageParameter {
+        skip: 0,
+        take: MAX_PAGE_SIZE,
+    });
+    assert_eq!(refunds, [(frozen, Amount::from_ccd(1))]);
+    env.assert_ccd_invariant();
+
+    // Once the account exists, it claims its share.
+    env.chain.create_account(Account::new(frozen, ACC_INITIAL_BALANCE));
+    let claim = env.update(frozen, "claim_refund", Amount::zero(), &()).expect("Claim the commission");
+    assert_eq!(claim.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(1), frozen)]);
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::zero()));
+}
+
+/// Test that accounts leave the bidder index of the auctions they are
+/// outbid in or that settle, and that outbid refunds paid right away leave
+/// nothing to claim, while a deferred payout is listed as a refund.
+#[test]
+fn test_account_position() {
+    let frozen = AccountAddress([9; 32]);
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        commission_recipients: vec![CommissionShare {
+            recipient: CommissionSink::Account(frozen),
+            share_bps: 10000,
+        }],
+        ..default_init_parameter()
+    });
+    let auction_ids: Vec<u32> =
+        (1..=3).map(|token_id| env.create_auction(ALICE, &env.auction_parameter("Auction item", token_id))).collect();
+    let position = |env: &TestEnv, account: AccountAddress| -> AccountPosition {
+        env.view("view_account_position", &account)
+    };
+    let refunds = |env: &TestEnv| -> Vec<(AccountAddress, Amount)> {
+        env.view("view_refunds", &PageParameter {
+            skip: 0,
+            take: MAX_PAGE_SIZE,
+        })
+    };
+    let leading = |pending_refund, highest_bids: &[(usize, u64)]| AccountPosition {
+        pending_refund,
+        highest_bids: highest_bids.iter().map(|&(index, ccd)| (auction_ids[index], Amount::from_ccd(ccd))).collect(),
+    };
+
+    for &auction_id in &auction_ids {
+        env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    }
+    assert_eq!(position(&env, BOB), leading(Amount::zero(), &[(0, 10), (1, 10), (2, 10)]));
+
+    // Bob is refunded right away when Dave outbids him in the second auction.
+    let update = env.bid(DAVE, auction_ids[1], Amount::from_ccd(20)).expect("Dave bids 20 CCD");
+    assert_eq!(update.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(10), BOB)]);
+    assert_eq!(position(&env, BOB), leading(Amount::zero(), &[(0, 10), (2, 10)]));
+    assert_eq!(position(&env, DAVE), leading(Amount::zero(), &[(1, 20)]));
+    assert!(refunds(&env).is_empty());
+
+    env.bid(BOB, auction_ids[1], Amount::from_ccd(30)).expect("Bob bids 30 CCD");
+    assert_eq!(position(&env, BOB), leading(Amount::zero(), &[(0, 10), (1, 30), (2, 10)]));
+    assert_eq!(position(&env, DAVE), leading(Amount::zero(), &[]));
+    assert!(refunds(&env).is_empty());
+
+    // Settling the first auction removes it from Bob's position, and the
+    // commission that cannot be paid is listed for its recipient to claim.
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(DAVE, auction_ids[0]).expect("Finalize auction");
+    assert_eq!(position(&env, BOB), leading(Amount::zero(), &[(1, 30), (2, 10)]));
+    assert_eq!(refunds(&env), [(frozen, Amount::from_ccd(1))]);
+    assert_eq!(position(&env, frozen), leading(Amount::from_ccd(1), &[]));
+    env.assert_ccd_invariant();
+}
+
+/// Test that CCD returned to a bidder are logged with the reason: when
+/// outbid, when a late bid is returned after settling, when the winning bid
+/// is above the second price and when an expired bid is reclaimed.
+#[test]
+fn test_refund_events() {
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        auto_settle_late_bids: true,
+        ..default_init_parameter()
+    });
+    let refund = |auction_id, account, amount, reason| {
+        AuctionEvent::Refund(RefundEventData {
+            auction_id: Some(auction_id),
+            account,
+            amount,
+            reason,
+        })
+    };
+    let second_price = env.create_auction(ALICE, &NewAuctionParameter {
+        second_price: true,
+        ..env.auction_parameter("Second price", 1)
+    });
+    let expired = env.create_auction(ALICE, &env.auction_parameter("Expired", 2));
+
+    env.bid(BOB, second_price, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    let outbid = env.bid(DAVE, second_price, Amount::from_ccd(15)).expect("Dave bids 15 CCD");
+    assert_eq!(env.events(&outbid)[2..], [refund(second_price, BOB, Amount::from_ccd(10), RefundReason::Outbid)]);
+    env.bid(BOB, expired, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    // The late bid settles the auction, Dave pays the second price and both
+    // refunds are logged.
+    let late_bid = env.bid(BOB, second_price, Amount::from_ccd(20)).expect("Late bid settles");
+    let events = env.events(&late_bid);
+    assert!(events.contains(&refund(second_price, DAVE, Amount::from_ccd(5), RefundReason::Overpaid)));
+    assert_eq!(events.last(), Some(&refund(second_price, BOB, Amount::from_ccd(20), RefundReason::LateBid)));
+
+    env.chain.tick_block_time(Duration::from_days(90)).expect("Increment chain time");
+    let reclaim = env
+        .update(BOB, "reclaim_expired", Amount::zero(), &AuctionIdParameter {
+            auction_id: expired,
+        })
+        .expect("Bob reclaims his bid");
+    assert!(env.events(&reclaim).contains(&refund(expired, BOB, Amount::from_ccd(10), RefundReason::Reclaimed)));
+    env.assert_ccd_invariant();
+}
+
+/// Test that `view_state` returns the configuration, the auction count and
+/// the requested page of auctions with their IDs, tagged with its version.
+#[test]
+fn test_view_state() {
+    let mut env = TestEnv::new();
+    for (item, token_id) in [("First item", 1), ("Second item", 2), ("Third item", 3)] {
+        env.create_auction(ALICE, &env.auction_parameter(item, token_id));
+    }
+    let page = |skip, take| PageParameter {
+        skip,
+        take,
+    };
+    let parameter = StateViewParameter {
+        auctions: page(1, 1),
+        refunds:  page(0, 10),
+    };
+
+    let view: StateView = env.view("view_state", &parameter);
+    assert_eq!(to_bytes(&view)[0], 0, "Version 1 is tagged with 0");
+    let StateView::V1(state) = view;
+    assert_eq!(state.admin, CAROL);
+    assert_eq!(state.commission_tiers, default_init_parameter().commission_tiers);
+    assert_eq!(state.cancellation_fee_bps, 500);
+    assert_eq!(state.stats.auctions_created, 3);
+    assert_eq!(state.auction_count, 3);
+    let auctions: Vec<(u32, String)> = state.auctions.into_iter().map(|(id, auction)| (id, auction.item)).collect();
+    assert_eq!(auctions, [(1, "Second item".to_string())]);
+    assert_eq!(state.pending_refunds, []);
+}
+
+/// Test that `view_config` returns the configuration and the version of the
+/// contract, tagged with its version.
+#[test]
+fn test_view_config() {
+    let env = TestEnv::new();
+    let view: ConfigView = env.view("view_config", &());
+    assert_eq!(to_bytes(&view)[0], 0, "Version 1 is tagged with 0");
+    let ConfigView::V1(config) = view;
+    let parameter = default_init_parameter();
+    assert_eq!(config.version, CONTRACT_VERSION);
+    assert_eq!(config.admin, CAROL);
+    assert_eq!(config.commission_tiers, parameter.commission_tiers);
+    assert_eq!(config.pending_commission_change, None);
+    assert_eq!(config.cancellation_fee_bps, 500);
+    assert_eq!(config.max_active_auctions, 20);
+    assert_eq!(config.max_auction_duration, Duration::from_days(365));
+    assert_eq!(config.delivery_window, Duration::from_days(7));
+    assert_eq!(config.swap_contract, None);
+}
+
+/// Test that an auction is only created for tokens that arrived in its own
+/// escrow transfer, so the same token cannot be listed twice, and that the
+/// auction contract cannot be used as the token contract.
+#[test]
+fn test_escrow_guards() {
+    let mut env = TestEnv::new();
+    let parameter = env.auction_parameter("Auction item", 1);
+    env.create_auction(ALICE, &parameter);
+
+    // The token is already escrowed, so Alice no longer holds it and no
+    // auction is created.
+    assert_error(env.update(ALICE, "create_auction", Amount::zero(), &parameter), BidError::InsufficientTokenBalance {
+        balance:  TokenAmountU64(0),
+        required: TokenAmountU64(1),
+    });
+    assert_eq!(env.view::<Vec<Auction>>("view_auctions", &()).len(), 1);
+
+    let own_tokens = NewAuctionParameter {
+        token_contract: env.auction,
+        ..env.auction_parameter("Auction item", 2)
+    };
+    assert_error(env.update(ALICE, "create_auction", Amount::zero(), &own_tokens), BidError::TokenContractIsSelf);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 2), TokenAmountU64(1));
+}
+
+/// Test that creating an auction checks the owner's balance and operator
+/// approval before the escrow transfer, and that the transfer is still
+/// checked when it fails after the checks passed.
+#[test]
+fn test_escrow_preflight() {
+    let mut env = TestEnv::new();
+    let parameter = NewAuctionParameter {
+        token_amount: TokenAmountU64(2),
+        ..env.auction_parameter("Auction item", 4)
+    };
+
+    env.mint(Address::Account(BOB), 4, 1);
+    assert_error(env.update(BOB, "create_auction", Amount::zero(), &parameter), BidError::InsufficientTokenBalance {
+        balance:  TokenAmountU64(1),
+        required: TokenAmountU64(2),
+    });
+    env.mint(Address::Account(BOB), 4, 1);
+    assert_error(env.update(BOB, "create_auction", Amount::zero(), &parameter), BidError::MissingOperatorApproval);
+
+    // The checks pass, but the transfer itself fails.
+    env.add_operator(BOB, Address::Contract(env.auction));
+    env.set_fail_transfers(true);
+    assert_error(env.update(BOB, "create_auction", Amount::zero(), &parameter), BidError::EscrowNotReceived);
+    assert_eq!(env.token_balance(Address::Account(BOB), 4), TokenAmountU64(2));
+
+    env.set_fail_transfers(false);
+    env.create_auction(BOB, &parameter);
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 4), TokenAmountU64(2));
+}
+
+/// Test that the highest bidder can retract the bid before the cutoff, paying
+/// the penalty to the commission recipients, after which the auction has no
+/// bids again.
+#[test]
+fn test_retract_bid() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &NewAuctionParameter {
+        end: Timestamp::from_timestamp_millis(3_600_000),
+        ..env.auction_parameter("Auction item", 1)
+    });
+    let retract = AuctionIdParameter {
+        auction_id,
+    };
+
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(DAVE, auction_id, Amount::from_ccd(20)).expect("Dave bids 20 CCD");
+    assert_error(env.update(BOB, "retract_bid", Amount::zero(), &retract), BidError::OnlyHighestBidder);
+
+    // The penalty is 2% of the bid.
+    let update = env.update(DAVE, "retract_bid", Amount::zero(), &retract).expect("Dave retracts");
+    assert_eq!(update.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_micro_ccd(400_000), CAROL),
+        (env.auction, Amount::from_micro_ccd(19_600_000), DAVE)
+    ]);
+    assert_eq!(env.events(&update), [
+        AuctionEvent::Refund(RefundEventData {
+            auction_id: Some(auction_id),
+            account:    DAVE,
+            amount:     Amount::from_micro_ccd(19_600_000),
+            reason:     RefundReason::Retracted,
+        }),
+        AuctionEvent::Retract(RetractEventData {
+            auction_id,
+            bidder: DAVE,
+            bid: Amount::from_ccd(20),
+            penalty_payouts: vec![(CAROL, Amount::from_micro_ccd(400_000))],
+        })
+    ]);
+    let auction = env.get_auction(auction_id);
+    assert_eq!((auction.highest_bidder, auction.highest_bid), (None, Amount::zero()));
+    env.assert_ccd_invariant();
+
+    // Bob's earlier bid was refunded, so any bid is accepted again.
+    env.bid(BOB, auction_id, Amount::from_ccd(1)).expect("Bob bids 1 CCD");
+    env.chain.tick_block_time(Duration::from_minutes(50)).expect("Increment chain time");
+    env.chain.tick_block_time(Duration::from_millis(1)).expect("Increment chain time");
+    assert_error(env.update(BOB, "retract_bid", Amount::zero(), &retract), BidError::RetractionTooLate);
+}
+
+/// Test the ordering rules of backup bids: a bid tying the highest bid, or
+/// beating it by less than the minimum raise, stands as the backup bid if it
+/// beats the current backup bid, which is refunded. The backup bid takes the
+/// lead when the highest bidder retracts, can be withdrawn at any time and is
+/// refunded when the auction is settled.
+#[test]
+fn test_backup_bids() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &NewAuctionParameter {
+        end: Timestamp::from_timestamp_millis(3_600_000),
+        minimum_raise: Raise::Flat(Amount::from_ccd(2)),
+        accepts_backup_bids: true,
+        ..env.auction_parameter("Auction item", 1)
+    });
+    let parameter = AuctionIdParameter {
+        auction_id,
+    };
+    let backup_bid = |env: &TestEnv| env.view::<Option<BackupBid>>("view_backup_bid", &parameter);
+    let backup = |bidder, ccd| {
+        Some(BackupBid {
+            bidder,
+            beneficiary: None,
+            amount: Amount::from_ccd(ccd),
+        })
+    };
+
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    let tie = env.bid(DAVE, auction_id, Amount::from_ccd(10)).expect("Dave ties with 10 CCD");
+    assert_eq!(tie.account_transfers().count(), 0);
+    assert_eq!(env.events(&tie), [AuctionEvent::BackupBid(BackupBidEventData {
+        auction_id,
+        bidder: DAVE,
+        amount: Amount::from_ccd(10),
+    })]);
+    assert_eq!(backup_bid(&env), backup(DAVE, 10));
+    assert_eq!(env.get_auction(auction_id).highest_bidder, Some(BOB));
+    env.assert_ccd_invariant();
+
+    // The earlier backup bid wins a tie, and bids below the highest bid are
+    // rejected as before.
+    assert_error(env.bid(CAROL, auction_id, Amount::from_ccd(10)), BidError::BidTiesCurrentBid);
+    assert_error(env.bid(CAROL, auction_id, Amount::from_ccd(9)), BidError::BidBelowCurrentBid);
+    // A bid below the minimum raise beats the backup bid, which is refunded.
+    let replaced = env.bid(CAROL, auction_id, Amount::from_ccd(11)).expect("Carol bids 11 CCD");
+    assert_eq!(replaced.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(10), DAVE)]);
+    assert_eq!(backup_bid(&env), backup(CAROL, 11));
+    env.assert_ccd_invariant();
+
+    // The backup bid takes the lead when the highest bidder retracts.
+    let retract = env.update(BOB, "retract_bid", Amount::zero(), &parameter).expect("Bob retracts");
+    assert_eq!(env.events(&retract).last(), Some(&AuctionEvent::BackupPromoted(BackupBidEventData {
+        auction_id,
+        bidder: CAROL,
+        amount: Amount::from_ccd(11),
+    })));
+    let auction = env.get_auction(auction_id);
+    assert_eq!((auction.highest_bidder, auction.highest_bid), (Some(CAROL), Amount::from_ccd(11)));
+    assert_eq!(backup_bid(&env), None);
+    env.assert_ccd_invariant();
+
+    // Only the backup bidder can withdraw the backup bid, at any time.
+    env.bid(DAVE, auction_id, Amount::from_ccd(11)).expect("Dave ties with 11 CCD");
+    assert_error(env.update(BOB, "withdraw_backup_bid", Amount::zero(), &parameter), BidError::NotBackupBidder);
+    let withdraw = env.update(DAVE, "withdraw_backup_bid", Amount::zero(), &parameter).expect("Dave withdraws");
+    assert_eq!(withdraw.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(11), DAVE)]);
+    assert_eq!(backup_bid(&env), None);
+    env.assert_ccd_invariant();
+
+    // A standing backup bid is refunded when the auction is settled.
+    env.bid(DAVE, auction_id, Amount::from_ccd(12)).expect("Dave bids 12 CCD");
+    env.chain.tick_block_time(Duration::from_millis(3_600_001)).expect("Increment chain time");
+    let finalize = env.finalize(BOB, auction_id).expect("Finalize auction");
+    assert!(env.events(&finalize).contains(&AuctionEvent::Refund(RefundEventData {
+        auction_id: Some(auction_id),
+        account:    DAVE,
+        amount:     Amount::from_ccd(12),
+        reason:     RefundReason::BackupReleased,
+    })));
+    assert!(matches!(env.get_auction(auction_id).auction_state, AuctionState::Sold { winner, .. } if winner == CAROL));
+    assert_eq!(backup_bid(&env), None);
+    env.assert_ccd_invariant();
+}
+
+/// Test bidding from deposited balances across several auctions: outbid bids
+/// funded from a balance are credited back to it without transfers, while
+/// bids with attached CCD are refunded as before, and the contract holds
+/// exactly the balances plus the standing bids throughout.
+#[test]
+fn test_deposit_and_bid() {
+    let mut env = TestEnv::new();
+    let first = env.create_auction(ALICE, &env.auction_parameter("First item", 1));
+    let second = env.create_auction(ALICE, &env.auction_parameter("Second item", 2));
+    let bid_from_balance = |env: &mut TestEnv, bidder, auction_id, ccd| {
+        env.update(bidder, "bid_from_balance", Amount::zero(), &BalanceBidParameter {
+            bid:    BidParameter {
+                auction_id,
+                beneficiary: None,
+                data: AdditionalData::empty(),
+            },
+            amount: Amount::from_ccd(ccd),
+        })
+    };
+    let balance = |env: &TestEnv, account| env.view::<Amount>("view_balance", &account);
+    let assert_holdings = |env: &TestEnv| {
+        let summary: EscrowSummary = env.view("view_escrow_summary", &());
+        assert_eq!(env.chain.contract_balance(env.auction), Some(summary.balances + summary.standing_bids));
+        env.assert_ccd_invariant();
+    };
+
+    env.update(BOB, "deposit", Amount::from_ccd(100), &()).expect("Bob deposits 100 CCD");
+    env.update(DAVE, "deposit", Amount::from_ccd(50), &()).expect("Dave deposits 50 CCD");
+    bid_from_balance(&mut env, BOB, first, 30).expect("Bob bids 30 CCD from his balance");
+    assert_eq!(balance(&env, BOB), Amount::from_ccd(70));
+    assert_holdings(&env);
+
+    // Bob's bid is credited back to his balance when Dave outbids him.
+    let outbid = env.bid(DAVE, first, Amount::from_ccd(35)).expect("Dave bids 35 CCD");
+    assert_eq!(outbid.account_transfers().count(), 0);
+    assert!(env.events(&outbid).contains(&AuctionEvent::BalanceCredited(BalanceEventData {
+        account: BOB,
+        amount:  Amount::from_ccd(30),
+        balance: Amount::from_ccd(100),
+    })));
+    assert_holdings(&env);
+
+    bid_from_balance(&mut env, BOB, second, 60).expect("Bob bids 60 CCD from his balance");
+    assert_error(bid_from_balance(&mut env, BOB, first, 50), BidError::InsufficientBalance);
+    // Dave's attached bid is transferred back when Bob outbids him.
+    let outbid = bid_from_balance(&mut env, BOB, first, 40).expect("Bob bids 40 CCD from his balance");
+    assert_eq!(outbid.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(35), DAVE)]);
+    assert_eq!(balance(&env, BOB), Amount::zero());
+    assert_error(bid_from_balance(&mut env, BOB, first, 1), BidError::InsufficientBalance);
+    assert_holdings(&env);
+
+    // A rejected bid leaves the balance untouched.
+    assert_error(bid_from_balance(&mut env, DAVE, second, 45), BidError::BidBelowCurrentBid);
+    assert_eq!(balance(&env, DAVE), Amount::from_ccd(50));
+    assert_error(env.update(DAVE, "withdraw", Amount::zero(), &Amount::from_ccd(51)), BidError::InsufficientBalance);
+    let withdraw = env.update(DAVE, "withdraw", Amount::zero(), &Amount::from_ccd(20)).expect("Dave withdraws 20 CCD");
+    assert_eq!(withdraw.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(20), DAVE)]);
+    assert_holdings(&env);
+
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(BOB, first).expect("Finalize first auction");
+    env.finalize(BOB, second).expect("Finalize second auction");
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+    assert_eq!(env.token_balance(Address::Account(BOB), 2), TokenAmountU64(1));
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::from_ccd(30)));
+    assert_holdings(&env);
+}
+
+/// Test that no auction is created when the token contract reports the escrow
+/// transfer as done without calling the receive hook, even though the owner
+/// holds the token and made the contract an operator, and that the transfer is
+/// rolled back so the token stays with the owner.
+#[test]
+fn test_escrow_not_received() {
+    let mut env = TestEnv::new();
+    let parameter = env.auction_parameter("Auction item", 2);
+    env.set_skip_receive_hook(true);
+    assert_error(env.update(ALICE, "create_auction", Amount::zero(), &parameter), BidError::EscrowNotReceived);
+    assert!(env.view::<Vec<Auction>>("view_auctions", &()).is_empty());
+    assert_eq!(env.token_balance(Address::Account(ALICE), 2), TokenAmountU64(1));
+    assert_eq!(env.token_balance(Address::Contract(env.auction), 2), TokenAmountU64(0));
+
+    // Once the hook is called again, the same token is listed.
+    env.set_skip_receive_hook(false);
+    let auction_id = env.create_auction(ALICE, &parameter);
+    assert!(env.get_auction(auction_id).escrow_confirmed);
+    env.assert_token_invariant();
+}
+
+/// Test that auctions are only created for token contracts reporting CIS-2
+/// support, unless the admin exempted the contract.
+#[test]
+fn test_cis2_support_check() {
+    let mut env = TestEnv::new();
+    let parameter = env.auction_parameter("Auction item", 1);
+    let exemption = |env: &TestEnv, exempt| Cis0ExemptionParameter {
+        token_contract: env.token,
+        exempt,
+    };
+    env.set_supports_cis2(false);
+
+    let rv: BidError = env
+        .update(ALICE, "create_auction", Amount::zero(), &parameter)
+        .expect_err("Token contract denies CIS-2")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::TokenContractNotCis2);
+
+    let rv: BidError = env
+        .update(BOB, "set_cis0_exemption", Amount::zero(), &exemption(&env, true))
+        .expect_err("Only the admin exempts")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::OnlyAdmin);
+
+    env.update(CAROL, "set_cis0_exemption", Amount::zero(), &exemption(&env, true)).expect("Exempt the contract");
+    assert!(env.view::<bool>("view_cis0_exemption", &env.token));
+    env.create_auction(ALICE, &parameter);
+
+    env.update(CAROL, "set_cis0_exemption", Amount::zero(), &exemption(&env, false)).expect("Check the contract again");
+    env.update(ALICE, "create_auction", Amount::zero(), &env.auction_parameter("Auction item", 2))
+        .expect_err("Token contract denies CIS-2");
+}
+
+/// Test fixed-price listings: they cannot be bid on or finalized, a purchase
+/// of at least the price settles them right away with the overpayment
+/// refunded, and the owner can cancel them for free.
+#[test]
+fn test_fixed_price_listing() {
+    let mut env = TestEnv::new();
+    let list = |env: &mut TestEnv, token_id| -> u32 {
+        let parameter = FixedPriceParameter {
+            item:           "Listed item".to_string(),
+            price:          Amount::from_ccd(100),
+            token_contract: env.token,
+            token_id:       TokenIdU8(token_id),
+            token_amount:   TokenAmountU64(1),
+        };
+        env.update(ALICE, "create_listing", Amount::zero(), &parameter)
+            .expect("Create listing")
+            .parse_return_value()
+            .expect("Return value is valid")
+    };
+    let sold = list(&mut env, 1);
+    let cancelled = list(&mut env, 2);
+    let listing = AuctionIdParameter {
+        auction_id: sold,
+    };
+    assert_eq!(env.get_auction(sold).kind, AuctionKind::FixedPrice {
+        price: Amount::from_ccd(100),
+    });
+
+    assert_error(env.bid(BOB, sold, Amount::from_ccd(100)), BidError::FixedPriceListing);
+    assert_error(env.finalize(DAVE, sold), BidError::FixedPriceListing);
+    assert_error(env.update(BOB, "purchase", Amount::from_ccd(99), &listing), BidError::PaymentBelowPrice);
+    assert_error(env.update(ALICE, "purchase", Amount::from_ccd(100), &listing), BidError::OnlyNotOwner);
+
+    let purchase = env.update(BOB, "purchase", Amount::from_ccd(110), &listing).expect("Bob buys");
+    assert_eq!(purchase.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(10), CAROL),
+        (env.auction, Amount::from_ccd(90), ALICE),
+        (env.auction, Amount::from_ccd(10), BOB)
+    ]);
+    assert!(matches!(env.get_auction(sold).auction_state, AuctionState::Sold { winner, .. } if winner == BOB));
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+    assert_error(env.update(DAVE, "purchase", Amount::from_ccd(100), &listing), BidError::AuctionAlreadyFinalized);
+
+    env.update(ALICE, "cancel_with_fee", Amount::zero(), &AuctionIdParameter {
+        auction_id: cancelled,
+    })
+    .expect("Cancel listing");
+    assert_eq!(env.get_auction(cancelled).auction_state, AuctionState::Cancelled);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 2), TokenAmountU64(1));
+    env.assert_ccd_invariant();
+    assert_eq!(env.chain.contract_balance(env.auction), Some(Amount::zero()));
+}
+
+/// Test offers: they can be made on listings and auctions without bids,
+/// cancelled by the offerer, expired by anyone once they can no longer be
+/// accepted, and accepting one settles the sale and refunds the others.
+#[test]
+fn test_offers() {
+    let mut env = TestEnv::new();
+    let listing: u32 = env
+        .update(ALICE, "create_listing", Amount::zero(), &FixedPriceParameter {
+            item:           "Listed item".to_string(),
+            price:          Amount::from_ccd(100),
+            token_contract: env.token,
+            token_id:       TokenIdU8(1),
+            token_amount:   TokenAmountU64(1),
+        })
+        .expect("Create listing")
+        .parse_return_value()
+        .expect("Return value is valid");
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 2));
+    let expiry = Timestamp::from_timestamp_millis(3_600_000);
+    let offer = |env: &mut TestEnv, offerer, listing_id, amount| {
+        env.update(offerer, "make_offer", amount, &MakeOfferParameter {
+            listing_id,
+            expiry,
+        })
+    };
+    let offer_event = |offer_id, listing_id, offerer, ccd| OfferEventData {
+        offer_id,
+        listing_id,
+        offerer,
+        amount: Amount::from_ccd(ccd),
+    };
+
+    assert_error(offer(&mut env, ALICE, listing, Amount::from_ccd(60)), BidError::OnlyNotOwner);
+    assert_error(offer(&mut env, BOB, listing, Amount::zero()), BidError::ZeroOffer);
+    let bob_offer = offer(&mut env, BOB, listing, Amount::from_ccd(60)).expect("Bob offers 60 CCD");
+    assert_eq!(env.events(&bob_offer), [AuctionEvent::OfferMade(offer_event(0, listing, BOB, 60))]);
+    offer(&mut env, DAVE, listing, Amount::from_ccd(70)).expect("Dave offers 70 CCD");
+    offer(&mut env, BOB, auction_id, Amount::from_ccd(10)).expect("Bob offers 10 CCD");
+    let offers: Vec<(u64, Offer)> = env.view("view_offers", &AuctionIdParameter {
+        auction_id: listing,
+    });
+    assert_eq!(offers.iter().map(|(offer_id, offer)| (*offer_id, offer.amount)).collect::<Vec<_>>(), [
+        (0, Amount::from_ccd(60)),
+        (1, Amount::from_ccd(70))
+    ]);
+    env.assert_ccd_invariant();
+
+    assert_error(env.update(DAVE, "cancel_offer", Amount::zero(), &0u64), BidError::OnlyOfferer);
+    assert_error(env.update(CAROL, "expire_offer", Amount::zero(), &1u64), BidError::OfferStillValid);
+
+    // Once the auction has a bid, Bob's offer for it can be expired by anyone.
+    env.bid(DAVE, auction_id, Amount::from_ccd(5)).expect("Dave bids 5 CCD");
+    let expire = env.update(CAROL, "expire_offer", Amount::zero(), &2u64).expect("Expire offer");
+    assert_eq!(expire.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(10), BOB)]);
+    assert_eq!(env.events(&expire), [AuctionEvent::OfferExpired(offer_event(2, auction_id, BOB, 10))]);
+
+    let accept = env
+        .update(ALICE, "accept_offer", Amount::zero(), &AcceptOfferParameter {
+            listing_id: listing,
+            offer_id:   1,
+        })
+        .expect("Accept Dave's offer");
+    assert_eq!(accept.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(60), BOB),
+        (env.auction, Amount::from_ccd(7), CAROL),
+        (env.auction, Amount::from_ccd(63), ALICE)
+    ]);
+    let events = env.events(&accept);
+    assert_eq!(events[..2], [
+        AuctionEvent::OfferAccepted(offer_event(1, listing, DAVE, 70)),
+        AuctionEvent::OfferCancelled(offer_event(0, listing, BOB, 60))
+    ]);
+    assert!(matches!(env.get_auction(listing).auction_state, AuctionState::Sold { winner, .. } if winner == DAVE));
+    assert_eq!(env.token_balance(Address::Account(DAVE), 1), TokenAmountU64(1));
+    assert_eq!(env.view::<Vec<(u64, Offer)>>("view_offers", &AuctionIdParameter {
+        auction_id: listing,
+    }), []);
+    env.assert_ccd_invariant();
+}
+
+/// Test that an account cannot have more active auctions than the maximum,
+/// and that auctions stop counting once they are settled.
+#[test]
+fn test_max_active_auctions() {
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        max_active_auctions: 2,
+        ..default_init_parameter()
+    });
+    let active = |env: &TestEnv| env.view::<ActiveAuctions>("view_active_auctions", &Address::Account(ALICE));
+
+    let first = env.create_auction(ALICE, &env.auction_parameter("First item", 1));
+    env.create_auction(ALICE, &env.auction_parameter("Second item", 2));
+    assert_eq!(active(&env), ActiveAuctions {
+        count: 2,
+        max:   2,
+    });
+    assert_error(env.update(ALICE, "create_auction", Amount::zero(), &env.auction_parameter("Third item", 3)), BidError::TooManyActiveAuctions);
+    // Other accounts are counted separately.
+    env.create_auction(CAROL, &env.auction_parameter("Carol's item", 1));
+
+    // Settled auctions no longer count.
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(DAVE, first).expect("Finalize auction");
+    assert_eq!(active(&env).count, 1);
+    let later = |env: &TestEnv, item, token_id| NewAuctionParameter {
+        end: Timestamp::from_timestamp_millis(3_600_000),
+        ..env.auction_parameter(item, token_id)
+    };
+    env.create_auction(ALICE, &later(&env, "Third item", 3));
+    assert_error(env.update(ALICE, "create_auction", Amount::zero(), &later(&env, "First item again", 1)), BidError::TooManyActiveAuctions);
+
+    env.update(BOB, "set_max_active_auctions", Amount::zero(), &3u32).expect_err("Only the admin changes the maximum");
+    env.update(CAROL, "set_max_active_auctions", Amount::zero(), &3u32).expect("Raise the maximum");
+    assert_eq!(active(&env), ActiveAuctions {
+        count: 2,
+        max:   3,
+    });
+    env.create_auction(ALICE, &later(&env, "First item again", 1));
+}
+
+/// Test that every bid and top-up is counted, and that a bidder is counted
+/// once however often they bid.
+#[test]
+fn test_auction_stats() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    let stats = |env: &TestEnv| env.view::<AuctionStats>("view_auction_stats", &AuctionIdParameter {
+        auction_id,
+    });
+    assert_eq!(stats(&env), AuctionStats {
+        bid_count:      0,
+        unique_bidders: 0,
+    });
+
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(DAVE, auction_id, Amount::from_ccd(20)).expect("Dave bids 20 CCD");
+    env.bid(BOB, auction_id, Amount::from_ccd(30)).expect("Bob bids 30 CCD");
+    env.bid(BOB, auction_id, Amount::from_ccd(5)).expect("Bob tops up by 5 CCD");
+    env.bid(DAVE, auction_id, Amount::from_ccd(10)).expect_err("Bid below the current bid");
+    assert_eq!(stats(&env), AuctionStats {
+        bid_count:      4,
+        unique_bidders: 2,
+    });
+    let auction = env.get_auction(auction_id);
+    assert_eq!((auction.bid_count, auction.unique_bidders), (4, 2));
+}
+
+/// Test that the owner can change the item description until the first bid,
+/// and that the change is validated like on creation.
+#[test]
+fn test_update_auction_item() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction itme", 1));
+    let update_item = |item: &str| UpdateItemParameter {
+        auction_id,
+        item: item.to_string(),
+    };
+
+    assert_error(env.update(BOB, "update_auction_item", Amount::zero(), &update_item("Bob's item")), BidError::OnlyOwner);
+    assert_error(
+        env.update(ALICE, "update_auction_item", Amount::zero(), &update_item(&"x".repeat(MAX_ITEM_LENGTH + 1))),
+        BidError::InvalidItemDescription,
+    );
+
+    let update = env.update(ALICE, "update_auction_item", Amount::zero(), &update_item("Auction item")).expect("Fix the typo");
+    assert_eq!(env.get_auction(auction_id).item, "Auction item");
+    let events = env.events(&update);
+    assert!(
+        matches!(events[..], [AuctionEvent::ItemUpdated(ItemUpdatedEventData { auction_id: id, .. })] if id == auction_id)
+    );
+    // The hash identifies the description.
+    let again = env.update(ALICE, "update_auction_item", Amount::zero(), &update_item("Auction item")).expect("Update again");
+    let other = env.update(ALICE, "update_auction_item", Amount::zero(), &update_item("Other item")).expect("Update again");
+    assert_eq!(env.events(&again), events);
+    assert_ne!(env.events(&other), events);
+
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    assert_error(env.update(ALICE, "update_auction_item", Amount::zero(), &update_item("Auction item")), BidError::AuctionHasBids);
+    assert_eq!(env.get_auction(auction_id).item, "Other item");
+}
+
+/// Test that the owner's share of an auction with a payout token is swapped
+/// for the token, while the commission stays in CCD, and that the share is
+/// paid in CCD if the swap fails.
+#[test]
+fn test_payout_token() {
+    let mut env = TestEnv::new();
+    let swap = env.init_mock("swap_mock");
+    let with_payout_token = |env: &TestEnv, token_id| NewAuctionParameter {
+        payout_token: Some((env.token, TokenIdU8(9))),
+        ..env.auction_parameter("Auction item", token_id)
+    };
+    let swapped = env.create_auction(ALICE, &with_payout_token(&env, 1));
+    let fallback = env.create_auction(ALICE, &with_payout_token(&env, 2));
+    env.bid(BOB, swapped, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(BOB, fallback, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    env.update(BOB, "set_swap_contract", Amount::zero(), &Some(swap)).expect_err("Only the admin sets the swap contract");
+    env.update(CAROL, "set_swap_contract", Amount::zero(), &Some(swap)).expect("Set the swap contract");
+
+    let finalize = env.finalize(DAVE, swapped).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(1), CAROL)]);
+    assert_eq!(env.token_balance(Address::Account(ALICE), 9), TokenAmountU64(9_000_000));
+    assert_eq!(env.chain.contract_balance(swap), Some(Amount::from_ccd(9)));
+    assert!(!env.events(&finalize).iter().any(|event| matches!(event, AuctionEvent::PayoutFallback(_))));
+
+    env.set_fail_swaps(swap, true);
+    let finalize = env.finalize(DAVE, fallback).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [
+        (env.auction, Amount::from_ccd(1), CAROL),
+        (env.auction, Amount::from_ccd(9), ALICE)
+    ]);
+    assert!(env.events(&finalize).contains(&AuctionEvent::PayoutFallback(PayoutFallbackEventData {
+        auction_id:   fallback,
+        owner:        Address::Account(ALICE),
+        amount:       Amount::from_ccd(9),
+        payout_token: (env.token, TokenIdU8(9)),
+    })));
+    assert_eq!(env.token_balance(Address::Account(ALICE), 9), TokenAmountU64(9_000_000));
+    env.assert_ccd_invariant();
+}
+
+/// Test bids in the alternative bid token of an auction: they are compared
+/// with CCD bids by their value at the ratio of the auction, rounded down,
+/// an outbid bid is refunded in the asset it was placed in, and the owner is
+/// paid in the asset of the winning bid.
+#[test]
+fn test_alt_bid_token() {
+    let mut env = TestEnv::new();
+    // One unit of token 9 is worth 1.5 µCCD
+    let alt_bid_token = AltBidToken {
+        contract:          env.token,
+        token_id:          TokenIdU8(9),
+        ratio_numerator:   3,
+        ratio_denominator: 2,
+    };
+    assert_eq!(alt_bid_token.value_of(TokenAmountU64(301)), Some(Amount::from_micro_ccd(451)));
+    assert_eq!(alt_bid_token.value_of(TokenAmountU64(u64::MAX)), None);
+    let rv: BidError = env
+        .update(ALICE, "create_auction", Amount::zero(), &NewAuctionParameter {
+            alt_bid_token: Some(AltBidToken {
+                ratio_denominator: 0,
+                ..alt_bid_token
+            }),
+            ..env.auction_parameter("Auction item", 1)
+        })
+        .expect_err("Zero ratio")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::InvalidAltBidToken);
+
+    let auction_id = env.create_auction(ALICE, &NewAuctionParameter {
+        alt_bid_token: Some(alt_bid_token),
+        ..env.auction_parameter("Auction item", 1)
+    });
+    env.mint(Address::Account(BOB), 9, 1000);
+    env.mint(Address::Account(DAVE), 8, 1000);
+    let token_bid = |tokens, value| {
+        AuctionEvent::TokenBid(TokenBidEventData {
+            auction_id,
+            bidder: BOB,
+            tokens: TokenAmountU64(tokens),
+            value: Amount::from_micro_ccd(value),
+        })
+    };
+
+    // 300 tokens are worth 450 µCCD, which a CCD bid has to beat
+    let bid = env.bid_tokens(BOB, auction_id, 9, 300).expect("Bob bids 300 tokens");
+    assert!(env.events(&bid).contains(&token_bid(300, 450)));
+    let rv: BidError = env
+        .bid(DAVE, auction_id, Amount::from_micro_ccd(450))
+        .expect_err("Dave ties the value of the tokens")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::BidTiesCurrentBid);
+    env.bid_tokens(DAVE, auction_id, 8, 400).expect_err("Dave bids a token the auction does not accept");
+
+    // Outbid in CCD, Bob gets his tokens back
+    let bid = env.bid(DAVE, auction_id, Amount::from_micro_ccd(451)).expect("Dave bids 451 µCCD");
+    assert!(env.events(&bid).contains(&AuctionEvent::TokenRefund(TokenRefundEventData {
+        auction_id,
+        account: BOB,
+        tokens: TokenAmountU64(300),
+        reason: RefundReason::Outbid,
+    })));
+    assert_eq!(env.token_balance(Address::Account(BOB), 9), TokenAmountU64(1000));
+
+    // 301 tokens are worth 451.5 µCCD, rounded down to a tie
+    env.bid_tokens(BOB, auction_id, 9, 301).expect_err("Bob ties after rounding");
+    let bid = env.bid_tokens(BOB, auction_id, 9, 302).expect("Bob bids 302 tokens");
+    assert!(env.events(&bid).contains(&AuctionEvent::Refund(RefundEventData {
+        auction_id: Some(auction_id),
+        account:    DAVE,
+        amount:     Amount::from_micro_ccd(451),
+        reason:     RefundReason::Outbid,
+    })));
+
+    // Bob tops up his bid in the token only
+    let rv: BidError = env
+        .bid(BOB, auction_id, Amount::from_micro_ccd(100))
+        .expect_err("Bob tops up in CCD")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::BidAssetMismatch);
+    let bid = env.bid_tokens(BOB, auction_id, 9, 98).expect("Bob tops up 98 tokens");
+    assert!(env.events(&bid).contains(&token_bid(400, 600)));
+    let auction = env.get_auction(auction_id);
+    assert_eq!((auction.highest_bid, auction.token_bid), (Amount::from_micro_ccd(600), Some(TokenAmountU64(400))));
+    env.assert_ccd_invariant();
+
+    // The owner is paid in the token, minus the commission of 10% to Carol
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let finalize = env.finalize(DAVE, auction_id).expect("Finalize auction");
+    assert!(env.events(&finalize).contains(&AuctionEvent::TokenPayout(TokenPayoutEventData {
+        auction_id,
+        owner: Address::Account(ALICE),
+        owner_tokens: TokenAmountU64(360),
+        commission_tokens: TokenAmountU64(40),
+    })));
+    assert_eq!(env.token_balance(Address::Account(ALICE), 9), TokenAmountU64(360));
+    assert_eq!(env.token_balance(Address::Account(CAROL), 9), TokenAmountU64(40));
+    assert_eq!(env.token_balance(Address::Account(BOB), 9), TokenAmountU64(600));
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+    env.assert_ccd_invariant();
+}
+
+/// Test that tokens of the alternative bid token that a bidder or commission
+/// recipient does not take are kept for it to claim, instead of blocking the
+/// outbid or the settlement, and that a contract sink receives its share at
+/// its entrypoint.
+#[test]
+fn test_deferred_token_payouts() {
+    // The sink is the first contract initialized after the environment
+    let sink = ContractAddress::new(2, 0);
+    // The proxy accepts tokens at `onReceivingCIS2` only, so its share fails
+    let recipients = vec![
+        CommissionShare {
+            recipient: CommissionSink::Contract {
+                address:    sink,
+                entrypoint: OwnedEntrypointName::new_unchecked("missing".to_string()),
+            },
+            share_bps: 5000,
+        },
+        CommissionShare {
+            recipient: CommissionSink::Account(CAROL),
+            share_bps: 5000,
+        },
+    ];
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        commission_recipients: recipients,
+        ..default_init_parameter()
+    });
+    assert_eq!(env.init_mock("proxy_mock"), sink);
+    let auction_id = env.create_auction(ALICE, &NewAuctionParameter {
+        alt_bid_token: Some(AltBidToken {
+            contract:          env.token,
+            token_id:          TokenIdU8(9),
+            ratio_numerator:   1,
+            ratio_denominator: 1,
+        }),
+        ..env.auction_parameter("Auction item", 1)
+    });
+    env.mint(Address::Account(BOB), 9, 1000);
+    let claim = ClaimTokensParameter {
+        token_contract: env.token,
+        token_id:       TokenIdU8(9),
+        entrypoint:     OwnedEntrypointName::new_unchecked("onReceivingCIS2".to_string()),
+    };
+    let token_contract = env.token;
+    let deferred = |recipient, amount| {
+        AuctionEvent::TokensDeferred(TokensDeferredEventData {
+            auction_id,
+            recipient,
+            token_contract,
+            token_id: TokenIdU8(9),
+            amount: TokenAmountU64(amount),
+        })
+    };
+
+    // Returning Bob's tokens fails, and Dave outbids him all the same
+    env.bid_tokens(BOB, auction_id, 9, 300).expect("Bob bids 300 tokens");
+    env.set_fail_transfers(true);
+    let outbid = env.bid(DAVE, auction_id, Amount::from_micro_ccd(400)).expect("Outbid despite the failed refund");
+    env.set_fail_transfers(false);
+    let events = env.events(&outbid);
+    assert!(events.contains(&deferred(Address::Account(BOB), 300)));
+    assert!(!events.iter().any(|event| matches!(event, AuctionEvent::TokenRefund(_))));
+    env.assert_token_invariant();
+    env.update(BOB, "claim_tokens", Amount::zero(), &claim).expect("Bob claims his tokens");
+    assert_eq!(env.token_balance(Address::Account(BOB), 9), TokenAmountU64(1000));
+    let rv: BidError = env
+        .update(BOB, "claim_tokens", Amount::zero(), &claim)
+        .expect_err("Nothing left to claim")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::NothingToClaim);
+
+    // The sink rejects its half of the commission of 50 tokens, and the
+    // auction is settled all the same
+    env.bid_tokens(BOB, auction_id, 9, 500).expect("Bob bids 500 tokens");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let finalize = env.finalize(DAVE, auction_id).expect("Finalize despite the rejected share");
+    assert!(env.events(&finalize).contains(&deferred(Address::Contract(sink), 25)));
+    assert_eq!(env.token_balance(Address::Account(BOB), 1), TokenAmountU64(1));
+    assert_eq!(env.token_balance(Address::Account(ALICE), 9), TokenAmountU64(450));
+    assert_eq!(env.token_balance(Address::Account(CAROL), 9), TokenAmountU64(25));
+    let summary: EscrowSummary = env.view("view_escrow_summary", &());
+    assert_eq!(summary.token_claims, [TokenClaim {
+        token_contract: env.token,
+        token_id:       TokenIdU8(9),
+        recipient:      Address::Contract(sink),
+        amount:         TokenAmountU64(25),
+    }]);
+    env.assert_token_invariant();
+    env.assert_ccd_invariant();
+
+    // The sink claims its share at the entrypoint it accepts tokens at
+    env.forward(sink, env.auction, "claim_tokens", &claim).expect("The sink claims its share");
+    assert_eq!(env.token_balance(Address::Contract(sink), 9), TokenAmountU64(25));
+    assert!(env.view::<EscrowSummary>("view_escrow_summary", &()).token_claims.is_empty());
+}
+
+/// Test that a contract can own auctions: it gets unsold tokens back through
+/// its receive hook, and claims the proceeds of sold auctions.
+#[test]
+fn test_contract_owner() {
+    let mut env = TestEnv::new();
+    let treasury = env.init_mock("proxy_mock");
+    env.mint(Address::Contract(treasury), 4, 1);
+    env.mint(Address::Contract(treasury), 5, 1);
+    let operator = UpdateOperatorParams(vec![UpdateOperator {
+        update:   OperatorUpdate::Add,
+        operator: Address::Contract(env.auction),
+    }]);
+    env.forward(treasury, env.token, "updateOperator", &operator).expect("Make the auction contract an operator");
+
+    env.forward(treasury, env.auction, "create_auction", &env.auction_parameter("Sold item", 4)).expect("Create auction");
+    env.forward(treasury, env.auction, "create_auction", &env.auction_parameter("Unsold item", 5)).expect("Create auction");
+    let auctions = env.view::<Vec<Auction>>("view_auctions", &());
+    assert_eq!(auctions[0].owner, Address::Contract(treasury));
+    env.bid(BOB, 0, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+
+    // The commission is paid, and the rest is kept for the treasury.
+    let finalize = env.finalize(DAVE, 0).expect("Finalize auction");
+    assert_eq!(finalize.account_transfers().collect::<Vec<_>>()[..], [(env.auction, Amount::from_ccd(1), CAROL)]);
+    assert_eq!(env.view::<Amount>("view_proceeds", &treasury), Amount::from_ccd(9));
+    assert_eq!(env.view::<EscrowSummary>("view_escrow_summary", &()).pending_proceeds, Amount::from_ccd(9));
+    env.assert_ccd_invariant();
+    env.finalize(DAVE, 1).expect("Finalize auction");
+    assert_eq!(env.token_balance(Address::Contract(treasury), 5), TokenAmountU64(1));
+
+    let deposit = OwnedEntrypointName::new_unchecked("deposit".to_string());
+    // Accounts claim their payouts with `claim_refund` instead.
+    assert_error(env.update(ALICE, "claim_proceeds", Amount::zero(), &deposit), BidError::UseClaimRefund);
+    env.forward(treasury, env.auction, "claim_proceeds", &deposit).expect("Claim the proceeds");
+    assert_eq!(env.chain.contract_balance(treasury), Some(Amount::from_ccd(9)));
+    assert_eq!(env.view::<Amount>("view_proceeds", &treasury), Amount::zero());
+    env.assert_ccd_invariant();
+}
+
+/// Test that `view_auctions` returns 1000 auctions within the energy of a
+/// transaction, and fails with a specific error beyond that.
+#[test]
+fn test_view_auctions_cap() {
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        max_active_auctions: 2000,
+        ..default_init_parameter()
+    });
+    env.mint(Address::Account(ALICE), 1, MAX_VIEW_AUCTIONS as u64);
+    for _ in 0..MAX_VIEW_AUCTIONS {
+        env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    }
+    let view = |env: &TestEnv| {
+        env.chain.contract_invoke(ALICE, Address::Account(ALICE), ENERGY, UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      env.auction,
+            receive_name: OwnedReceiveName::new_unchecked("auction.view_auctions".to_string()),
+            message:      OwnedParameter::empty(),
+        })
+    };
+
+    let success = view(&env).expect("View 1000 auctions");
+    let auctions: Vec<Auction> = success.parse_return_value().expect("Return value is valid");
+    assert_eq!(auctions.len(), MAX_VIEW_AUCTIONS);
+    assert!(auctions.iter().all(|auction| auction.item == "Auction item"));
+    assert!(success.energy_used < ENERGY, "Used {} energy", success.energy_used.energy);
+
+    env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    let rv: BidError =
+        view(&env).expect_err("Too many auctions").parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, BidError::TooManyAuctionsToView);
+}
+
+/// Test that the settlement record of an auction is only reported once it is
+/// sold, with the finalizer and the amounts paid out.
+#[test]
+fn test_view_settlement() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    let unsold_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 2));
+    let settlement = |env: &TestEnv, auction_id| env.view::<SettlementStatus>("view_settlement", &AuctionIdParameter {
+        auction_id,
+    });
+    assert_eq!(settlement(&env, auction_id), SettlementStatus::NotSettled);
+
+    env.bid(DAVE, auction_id, Amount::from_ccd(20)).expect("Dave bids 20 CCD");
+    assert_eq!(settlement(&env, auction_id), SettlementStatus::NotSettled);
+
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(BOB, auction_id).expect("Finalize auction");
+    env.finalize(BOB, unsold_id).expect("Finalize auction without bids");
+    assert_eq!(settlement(&env, auction_id), SettlementStatus::Settled(SettlementRecord {
+        finalizer:        Address::Account(BOB),
+        settled_at:       Timestamp::from_timestamp_millis(1001),
+        seller_amount:    Amount::from_ccd(18),
+        commission:       Amount::from_ccd(2),
+        finalize_reward:  Amount::zero(),
+        tokens_delivered: true,
+    }));
+    assert_eq!(settlement(&env, unsold_id), SettlementStatus::NotSettled);
+}
+
+/// Test that the settlement preview of an ended auction reports exactly what
+/// `finalize` then pays out, and the status of auctions that would not
+/// settle to a winner.
+#[test]
+fn test_settlement_preview() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &NewAuctionParameter {
+        second_price: true,
+        finalize_reward: Amount::from_micro_ccd(1000),
+        ..env.auction_parameter("Auction item", 1)
+    });
+    let unsold_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 2));
+    let preview = |env: &TestEnv, auction_id| env.view::<SettlementPreview>("view_settlement_preview", &AuctionIdParameter {
+        auction_id,
+    });
+    assert_eq!(preview(&env, auction_id).status, PreviewStatus::NotYetEnded);
+
+    env.bid(BOB, auction_id, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    env.bid(DAVE, auction_id, Amount::from_ccd(20)).expect("Dave bids 20 CCD");
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    assert_eq!(preview(&env, unsold_id), SettlementPreview {
+        status:             PreviewStatus::WouldReturnUnsold,
+        winner:             None,
+        price:              Amount::zero(),
+        commission:         Amount::zero(),
+        commission_payouts: Vec::new(),
+        finalize_reward:    Amount::zero(),
+        owner_amount:       Amount::zero(),
+        refund:             Amount::zero(),
+        owner_tokens:       TokenAmountU64(0),
+        commission_tokens:  TokenAmountU64(0),
+    });
+
+    // Views are invoked by Alice, who then finalizes and earns the reward
+    let expected = preview(&env, auction_id);
+    assert_eq!(expected.status, PreviewStatus::WouldSettleToWinner);
+    assert_eq!(expected.winner, Some(DAVE));
+    let finalize = env.finalize(ALICE, auction_id).expect("Finalize auction");
+    let events = env.events(&finalize);
+    let finalized = events
+        .iter()
+        .find_map(|event| match event {
+            AuctionEvent::Finalize(data) => Some(data),
+            _ => None,
+        })
+        .expect("Finalize event");
+    assert_eq!(finalized.price, expected.price);
+    assert_eq!(finalized.finalize_reward, expected.finalize_reward);
+    assert_eq!(finalized.winner, expected.winner);
+    assert_eq!(finalized.commission_payouts, [(CAROL, expected.commission)]);
+    assert_eq!(expected.commission_payouts, [(CommissionSink::Account(CAROL), expected.commission)]);
+    assert!(events.contains(&AuctionEvent::Refund(RefundEventData {
+        auction_id: Some(auction_id),
+        account:    DAVE,
+        amount:     expected.refund,
+        reason:     RefundReason::Overpaid,
+    })));
+    let settlement: SettlementStatus = env.view("view_settlement", &AuctionIdParameter {
+        auction_id,
+    });
+    assert_eq!(settlement, SettlementStatus::Settled(SettlementRecord {
+        finalizer:        Address::Account(ALICE),
+        settled_at:       Timestamp::from_timestamp_millis(1001),
+        seller_amount:    expected.owner_amount,
+        commission:       expected.commission,
+        finalize_reward:  expected.finalize_reward,
+        tokens_delivered: true,
+    }));
+    assert_eq!(expected.owner_amount + expected.commission + expected.finalize_reward, Amount::from_ccd(10));
+
+    assert_eq!(preview(&env, auction_id).status, PreviewStatus::AlreadyFinalized);
+}
+
+/// Test that entrypoints, including views, fail with the variant of
+/// `BidError` for the failure rather than a fallback, so clients can decode
+/// the reason with the schema.
+#[test]
+fn test_entrypoint_errors() {
+    let mut env = TestEnv::new();
+    let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 1));
+    let view_error = |env: &TestEnv, entrypoint: &str, parameter: OwnedParameter| -> BidError {
+        env.chain
+            .contract_invoke(ALICE, Address::Account(ALICE), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      env.auction,
+                receive_name: OwnedReceiveName::new_unchecked(format!("auction.{}", entrypoint)),
+                message:      parameter,
+            })
+            .expect_err("View fails")
+            .parse_return_value()
+            .expect("Return value is valid")
+    };
+
+    for entrypoint in ["create_auction", "bid", "finalize", "withdraw", "onReceivingCIS2"] {
+        let rv: BidError = env
+            .update(ALICE, entrypoint, Amount::zero(), &())
+            .expect_err("Update without parameter fails")
+            .parse_return_value()
+            .expect("Return value is valid");
+        assert_eq!(rv, BidError::ParameterParsingError, "Entrypoint {}", entrypoint);
+    }
+    // Only token contracts call the receive hook.
+    let hook = OnReceivingCis2Params {
+        token_id: TokenIdU8(1),
+        amount:   TokenAmountU64(1),
+        from:     Address::Account(ALICE),
+        data:     AdditionalData::empty(),
+    };
+    let rv: BidError = env
+        .update(ALICE, "onReceivingCIS2", Amount::zero(), &hook)
+        .expect_err("Hook called by an account fails")
+        .parse_return_value()
+        .expect("Return value is valid");
+    assert_eq!(rv, BidError::OnlyContract);
+    for entrypoint in ["get_auction", "view_settlement", "view_balance", "view_auction_stats", "view_refunds"] {
+        assert_eq!(view_error(&env, entrypoint, OwnedParameter::empty()), BidError::ParameterParsingError, "View {}", entrypoint);
+    }
+    for entrypoint in ["get_auction", "view_settlement", "view_backup_bid", "view_auction_stats"] {
+        let unknown = OwnedParameter::from_serial(&AuctionIdParameter {
+            auction_id: auction_id + 1,
+        })
+        .expect("Serialize parameter");
+        assert_eq!(view_error(&env, entrypoint, unknown), BidError::AuctionNotFound, "View {}", entrypoint);
+    }
+}
+
+/// Test the latest end `validate_end` allows for a new auction, and that it
+/// rejects settlement exclusivity windows overflowing a timestamp.
+#[test]
+fn test_validate_end() {
+    let now = Timestamp::from_timestamp_millis(500);
+    let max_duration = Duration::from_days(30);
+    let latest = now.checked_add(max_duration).expect("Valid timestamp");
+    let last = Timestamp::from_timestamp_millis(u64::MAX);
+
+    assert_eq!(validate_end(latest, None, now, max_duration), Ok(()));
+    for end in [latest.checked_add(Duration::from_millis(1)).expect("Valid timestamp"), last] {
+        assert_eq!(validate_end(end, None, now, max_duration), Err(BidError::AuctionTooLong));
+    }
+    // A maximum reaching past the last timestamp allows any end
+    assert_eq!(validate_end(last, None, now, Duration::from_millis(u64::MAX)), Ok(()));
+
+    let window = Duration::from_millis(u64::MAX - latest.timestamp_millis());
+    assert_eq!(validate_end(latest, Some(window), now, max_duration), Ok(()));
+    assert_eq!(
+        validate_end(latest, Some(Duration::from_millis(window.millis() + 1)), now, max_duration),
+        Err(BidError::ArithmeticOverflow)
+    );
+}
+
+/// Test that the extreme values of timestamps, amounts and token amounts are
+/// either accepted or rejected with a `BidError`, and never make an
+/// entrypoint trap.
+#[test]
+fn test_extreme_parameters() {
+    let mut env = TestEnv::new();
+    let latest = Timestamp::from_timestamp_millis(0).checked_add(Duration::from_days(365)).expect("Valid timestamp");
+
+    // Ends after the maximum duration, up to the last timestamp
+    for end in [u64::MAX, u64::MAX - 1, u64::MAX / 2, latest.timestamp_millis() + 1] {
+        let end = Timestamp::from_timestamp_millis(end);
+        let parameter = NewAuctionParameter {
+            end,
+            ..env.auction_parameter("Eternal item", 1)
+        };
+        assert_error(env.update(ALICE, "create_auction", Amount::zero(), &parameter), BidError::AuctionTooLong);
+        let reverse = ReverseAuctionParameter {
+            item: "Eternal wanted item".to_string(),
+            end,
+            token_contract: env.token,
+            token_id: TokenIdU8(1),
+            token_amount: TokenAmountU64(1),
+        };
+        assert_error(env.update(DAVE, "create_reverse_auction", Amount::from_ccd(1), &reverse), BidError::AuctionTooLong);
+        let listing = ListingParameter {
+            item: "Eternal item".to_string(),
+            end,
+            initial_price: 0,
+            settlement_exclusivity: None,
+            minimum_raise: Raise::Flat(Amount::zero()),
+            requires_owner_approval: false,
+            finalize_reward: Amount::zero(),
+            second_price: false,
+            payout_token: None,
+            accepts_backup_bids: false,
+            alt_bid_token: None,
+        };
+        env.transfer_to_auction(ALICE, 1, AdditionalData::from(to_bytes(&TransferData::List(listing)))).expect_err("Listing too long");
+    }
+    assert_eq!(env.token_balance(Address::Account(ALICE), 1), TokenAmountU64(1));
+    assert_error(
+        env.update(ALICE, "create_auction", Amount::zero(), &NewAuctionParameter {
+            settlement_exclusivity: Some(Duration::from_millis(u64::MAX)),
+            ..env.auction_parameter("Forever exclusive item", 1)
+        }),
+        BidError::ArithmeticOverflow,
+    );
+    assert_error(
+        env.update(ALICE, "create_auction", Amount::zero(), &NewAuctionParameter {
+            finalize_reward: Amount::from_micro_ccd(u64::MAX),
+            ..env.auction_parameter("Auction item", 1)
+        }),
+        BidError::InvalidFinalizeReward,
+    );
+
+    // The latest end is allowed, and a relisted auction may not end later
+    let longest = env.create_auction(ALICE, &NewAuctionParameter {
+        end: latest,
+        ..env.auction_parameter("Longest item", 1)
+    });
+    let ended = env.create_auction(ALICE, &env.auction_parameter("Ended item", 2));
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    let relist = RelistParameter {
+        auction_id:    ended,
+        end:           Timestamp::from_timestamp_millis(u64::MAX),
+        initial_price: Some(u64::MAX),
+    };
+    assert_error(env.update(ALICE, "relist_auction", Amount::zero(), &relist), BidError::AuctionTooLong);
+    env.update(BOB, "set_max_auction_duration", Amount::zero(), &Duration::from_millis(u64::MAX))
+        .expect_err("Only the admin changes the maximum");
+    env.update(CAROL, "set_max_auction_duration", Amount::zero(), &Duration::from_millis(u64::MAX))
+        .expect("Remove the maximum");
+    env.update(ALICE, "relist_auction", Amount::zero(), &relist).expect("Relist without a maximum");
+    // No bid exceeds an initial price of `u64::MAX` µCCD
+    let relisted = ended + 1;
+    assert_error(env.bid(BOB, relisted, Amount::from_ccd(1)), BidError::BidBelowCurrentBid);
+
+    // A bid of almost all CCD there are, and a minimum raise that no bid meets
+    let rich = AccountAddress([7; 32]);
+    env.chain.create_account(Account::new(rich, Amount::from_micro_ccd(u64::MAX)));
+    let unraisable = env.create_auction(CAROL, &NewAuctionParameter {
+        end: latest,
+        minimum_raise: Raise::Flat(Amount::from_micro_ccd(u64::MAX)),
+        ..env.auction_parameter("Unraisable item", 1)
+    });
+    let huge_bid = Amount::from_micro_ccd(u64::MAX - ACC_INITIAL_BALANCE.micro_ccd);
+    env.bid(rich, unraisable, huge_bid).expect("Bid almost all CCD");
+    env.bid(rich, longest, Amount::from_ccd(1)).expect("Bid the rest");
+    assert_error(env.bid(BOB, unraisable, Amount::from_ccd(10)), BidError::BidBelowCurrentBid);
+    env.assert_ccd_invariant();
+    assert_eq!(env.get_auction(unraisable).highest_bid, huge_bid);
+
+    // An escrow of the most tokens of a token ID there can be
+    env.mint(Address::Account(ALICE), 4, u64::MAX);
+    let all_tokens = env.create_auction(ALICE, &NewAuctionParameter {
+        end: latest,
+        token_amount: TokenAmountU64(u64::MAX),
+        ..env.auction_parameter("All tokens", 4)
+    });
+    env.update(ALICE, "cancel_with_fee", Amount::zero(), &AuctionIdParameter {
+        auction_id: all_tokens,
+    })
+    .expect("Cancel the auction");
+    assert_eq!(env.token_balance(Address::Account(ALICE), 4), TokenAmountU64(u64::MAX));
+}
+
+/// Test every rejection of `validate_bid`, and how it places valid bids,
+/// without deploying the contract.
+#[test]
+fn test_validate_bid() {
+    let now = Timestamp::from_timestamp_millis(500);
+    let auction = Auction {
+        auction_state:           AuctionState::NotSoldYet,
+        highest_bidder:          Some(BOB),
+        beneficiary:             None,
+        initial_price:           100,
+        highest_bid:             Amount::from_ccd(10),
+        item:                    "Auction item".to_string(),
+        end:                     AUCTION_END,
+        owner:                   Address::Account(ALICE),
+        token_contract:          ContractAddress::new(1000, 0),
+        token_id:                TokenIdU8(1),
+        token_amount:            TokenAmountU64(1),
+        settlement_exclusivity:  None,
+        minimum_raise:           Raise::Flat(Amount::from_ccd(1)),
+        requires_owner_approval: false,
+        finalize_reward:         Amount::zero(),
+        commission_tiers:        Vec::new(),
+        kind:                    AuctionKind::Standard,
+        escrow_confirmed:        true,
+        bid_count:               1,
+        unique_bidders:          1,
+        payout_token:            None,
+        accepts_backup_bids:     false,
+        alt_bid_token:           None,
+        token_bid:               None,
+        amount_sold:             TokenAmountU64(0),
+        amount_remaining:        TokenAmountU64(1),
+    };
+    let validate = |auction: &Auction, sender, beneficiary, amount, backup: Option<&BackupBid>| {
+        validate_bid(auction, now, sender, beneficiary, amount, backup)
+    };
+    let with = |update: fn(&mut Auction)| {
+        let mut auction = auction.clone();
+        update(&mut auction);
+        auction
+    };
+
+    let rejections = [
+        (with(|a| a.auction_state = AuctionState::Cancelled), DAVE, DAVE, Amount::from_ccd(20), BidError::AuctionAlreadyFinalized),
+        (with(|a| a.kind = AuctionKind::Reverse {
+            budget: Amount::from_ccd(10),
+        }), DAVE, DAVE, Amount::from_ccd(20), BidError::WrongAuctionKind),
+        (with(|a| a.kind = AuctionKind::FixedPrice {
+            price: Amount::from_ccd(10),
+        }), DAVE, DAVE, Amount::from_ccd(20), BidError::FixedPriceListing),
+        (with(|a| a.escrow_confirmed = false), DAVE, DAVE, Amount::from_ccd(20), BidError::EscrowNotConfirmed),
+        (with(|a| a.end = Timestamp::from_timestamp_millis(500)), DAVE, DAVE, Amount::from_ccd(20), BidError::BidTooLate),
+        (auction.clone(), ALICE, DAVE, Amount::from_ccd(20), BidError::OnlyNotOwner),
+        (auction.clone(), DAVE, ALICE, Amount::from_ccd(20), BidError::OnlyNotOwner),
+        (auction.clone(), BOB, BOB, Amount::zero(), BidError::ZeroTopUp),
+        (auction.clone(), BOB, BOB, Amount::from_micro_ccd(u64::MAX), BidError::ArithmeticOverflow),
+        (with(|a| {
+            a.highest_bidder = None;
+            a.highest_bid = Amount::zero();
+        }), DAVE, DAVE, Amount::from_micro_ccd(100), BidError::BidBelowCurrentBid),
+        (auction.clone(), DAVE, DAVE, Amount::from_ccd(9), BidError::BidBelowCurrentBid),
+        (auction.clone(), DAVE, DAVE, Amount::from_ccd(10), BidError::BidTiesCurrentBid),
+        (auction.clone(), DAVE, DAVE, Amount::from_micro_ccd(10_500_000), BidError::BidBelowMinimumRaise),
+    ];
+    for (auction, sender, beneficiary, amount, error) in rejections {
+        assert_eq!(validate(&auction, sender, beneficiary, amount, None), Err(error));
+    }
+
+    // A backup bid must exceed the current backup bid, which wins ties
+    let backups = with(|a| a.accepts_backup_bids = true);
+    let backup = BackupBid {
+        bidder:      CAROL,
+        beneficiary: None,
+        amount:      Amount::from_ccd(10),
+    };
+    assert_eq!(validate(&backups, DAVE, DAVE, Amount::from_ccd(9), Some(&backup)), Err(BidError::BidBelowCurrentBid));
+    assert_eq!(validate(&backups, DAVE, DAVE, Amount::from_ccd(10), Some(&backup)), Err(BidError::BidTiesCurrentBid));
+    assert_eq!(validate(&backups, DAVE, DAVE, Amount::from_ccd(10), None), Ok(BidPlacement::Backup));
+    assert_eq!(validate(&backups, DAVE, DAVE, Amount::from_micro_ccd(10_500_000), Some(&backup)), Ok(BidPlacement::Backup));
+    // A top-up of the highest bidder never becomes a backup bid
+    assert_eq!(validate(&backups, BOB, BOB, Amount::from_micro_ccd(500_000), None), Err(BidError::BidBelowMinimumRaise));
+
+    assert_eq!(validate(&auction, DAVE, DAVE, Amount::from_ccd(11), None), Ok(BidPlacement::Highest {
+        bid_total: Amount::from_ccd(11),
+        is_top_up: false,
+    }));
+    assert_eq!(validate(&auction, BOB, DAVE, Amount::from_ccd(1), None), Ok(BidPlacement::Highest {
+        bid_total: Amount::from_ccd(11),
+        is_top_up: true,
+    }));
+}
+
+/// Test that the energy of a bid does not grow with the number of unrelated
+/// auctions, as a bid only loads the auction it is placed on.
+#[test]
+fn test_bid_energy() {
+    let mut env = TestEnv::with_init_parameter(&InitParameter {
+        max_active_auctions: 1000,
+        ..default_init_parameter()
+    });
+    env.mint(Address::Account(ALICE), 4, 200);
+    let first_bid = |env: &mut TestEnv| {
+        let auction_id = env.create_auction(ALICE, &env.auction_parameter("Auction item", 4));
+        env.bid(BOB, auction_id, Amount::from_ccd(1)).expect("Bid").energy_used.energy
+    };
+
+    let few = first_bid(&mut env);
+    for _ in 0..198 {
+        env.create_auction(ALICE, &env.auction_parameter("Unrelated auction item", 4));
+    }
+    let many = first_bid(&mut env);
+    // Lookups in the state trie get slightly more expensive as it grows, which
+    // is far less than loading every auction would cost.
+    assert!(many <= few + few / 5, "Bid used {} energy with 200 auctions, {} with one", many, few);
+}
+
+/// Test a minimum raise in basis points of the highest bid: the required
+/// raise is rounded up, so even a tiny bid must be raised by at least 1 µCCD,
+/// and a top-up has to meet the raise with the combined bid.
+#[test]
+fn test_percent_minimum_raise() {
+    let mut env = TestEnv::new();
+    let five_percent = env.create_auction(ALICE, &NewAuctionParameter {
+        minimum_raise: Raise::Percent(500),
+        ..env.auction_parameter("Five percent", 1)
+    });
+    let one_bps = env.create_auction(ALICE, &NewAuctionParameter {
+        minimum_raise: Raise::Percent(1),
+        ..env.auction_parameter("One basis point", 2)
+    });
+
+    // 5% of 10 CCD is exactly 0.5 CCD.
+    env.bid(BOB, five_percent, Amount::from_ccd(10)).expect("Bob bids 10 CCD");
+    assert_error(env.bid(DAVE, five_percent, Amount::from_micro_ccd(10_499_999)), BidError::BidBelowMinimumRaise);
+    env.bid(DAVE, five_percent, Amount::from_micro_ccd(10_500_000)).expect("Dave raises by 5%");
+    // 5% of 10.5 CCD is 0.525 CCD, which Dave's top-up must reach in total.
+    assert_error(env.bid(DAVE, five_percent, Amount::from_micro_ccd(524_999)), BidError::BidBelowMinimumRaise);
+    env.bid(DAVE, five_percent, Amount::from_micro_ccd(525_000)).expect("Dave tops up by 5%");
+
+    // 1 bps of 101 µCCD is 0.0101 µCCD, which rounds up to 1 µCCD.
+    env.bid(BOB, one_bps, Amount::from_micro_ccd(101)).expect("Bob bids 101 µCCD");
+    env.bid(DAVE, one_bps, Amount::from_micro_ccd(102)).expect("Dave raises by 1 µCCD");
+    // 5% of 101 µCCD is 5.05 µCCD, which rounds up to 6 µCCD.
+    let rounding = env.create_auction(ALICE, &NewAuctionParameter {
+        minimum_raise: Raise::Percent(500),
+        ..env.auction_parameter("Rounding", 3)
+    });
+    env.bid(BOB, rounding, Amount::from_micro_ccd(101)).expect("Bob bids 101 µCCD");
+    assert_error(env.bid(DAVE, rounding, Amount::from_micro_ccd(106)), BidError::BidBelowMinimumRaise);
+    env.bid(DAVE, rounding, Amount::from_micro_ccd(107)).expect("Dave raises by 6 µCCD");
+}
+
+/// Test that `get_auctions` returns the auctions in the requested order, with
+/// `None` for an unknown ID in the middle of the list.
+#[test]
+fn test_get_auctions() {
+    let mut env = TestEnv::new();
+    let first = env.create_auction(ALICE, &env.auction_parameter("First item", 1));
+    let second = env.create_auction(ALICE, &env.auction_parameter("Second item", 2));
+
+    let auctions: Vec<Option<Auction>> = env.view("get_auctions", &vec![second, 42u32, first]);
+
+    let items: Vec<Option<String>> = auctions.into_iter().map(|auction| auction.map(|a| a.item)).collect();
+    assert_eq!(items, [Some("Second item".to_string()), None, Some("First item".to_string())]);
+}
+
+/// Test that `get_auctions_range` returns the auctions of a range in ID
+/// order, capped at the page size, together with the number of auctions.
+#[test]
+fn test_get_auctions_range() {
+    let mut env = TestEnv::new();
+    for (item, token_id) in [("First item", 1), ("Second item", 2), ("Third item", 3)] {
+        env.create_auction(ALICE, &env.auction_parameter(item, token_id));
+    }
+    let range = |env: &TestEnv, from, to| {
+        let range: AuctionRange = env.view("get_auctions_range", &AuctionRangeParameter {
+            from,
+            to,
+        });
+        (range.auction_count, range.auctions.into_iter().map(|(id, auction)| (id, auction.item)).collect::<Vec<_>>())
+    };
+
+    assert_eq!(range(&env, 1, 3), (3, vec![(1, "Second item".to_string()), (2, "Third item".to_string())]));
+    assert_eq!(range(&env, 0, u32::MAX).1.len(), 3);
+    assert_eq!(range(&env, 5, 10), (3, vec![]));
+    assert_eq!(range(&env, 2, 1), (3, vec![]));
+}
+
+/// Test that the auctions of a token contract can be listed, optionally for
+/// one token, and that closed auctions leave the list.
+#[test]
+fn test_auctions_by_token_contract() {
+    let mut env = TestEnv::new();
+    let first = env.create_auction(ALICE, &env.auction_parameter("First item", 1));
+    let second = env.create_auction(ALICE, &env.auction_parameter("Second item", 2));
+    let third = env.create_auction(ALICE, &NewAuctionParameter {
+        end: Timestamp::from_timestamp_millis(3_600_000),
+        ..env.auction_parameter("Third item", 3)
+    });
+    let by_token_contract = |env: &TestEnv, token_contract, token_id| {
+        let mut auction_ids: Vec<u32> = env
+            .view::<Vec<(u32, Auction)>>("view_auctions_by_token_contract", &TokenContractAuctionsParameter {
+                token_contract,
+                token_id,
+                page: PageParameter {
+                    skip: 0,
+                    take: MAX_PAGE_SIZE,
+                },
+            })
+            .into_iter()
+            .map(|(auction_id, _)| auction_id)
+            .collect();
+        auction_ids.sort();
+        auction_ids
+    };
+
+    assert_eq!(by_token_contract(&env, env.token, None), [first, second, third]);
+    assert_eq!(by_token_contract(&env, env.token, Some(TokenIdU8(2))), [second]);
+    assert!(by_token_contract(&env, env.auction, None).is_empty());
+
+    env.update(ALICE, "cancel_with_fee", Amount::zero(), &AuctionIdParameter {
+        auction_id: second,
+    })
+    .expect("Cancel auction");
+    assert_eq!(by_token_contract(&env, env.token, None), [first, third]);
+    assert!(by_token_contract(&env, env.token, Some(TokenIdU8(2))).is_empty());
+
+    env.chain.tick_block_time(Duration::from_millis(1001)).expect("Increment chain time");
+    env.finalize(DAVE, first).expect("Finalize auction");
+    assert_eq!(by_token_contract(&env, env.token, None), [third]);
+}
+
+/// Test that every event round-trips through its serialization and that the
+/// byte layout (tag byte followed by the event data) stays stable.
+#[test]
+fn test_event_serialization() {
+    let finalize_layout = [
+        &[1u8, 7, 0, 0, 0, 0][..],
+        &ALICE.0[..],
+        &[1, 0, 0, 0][..],
+        &BOB.0[..],
+        &[100, 0, 0, 0, 0, 0, 0, 0][..],
+        &[10, 0, 0, 0, 0, 0, 0, 0][..],
+        &[200, 0, 0, 0, 0, 0, 0, 0][..],
+        &[1][..],
+        &DAVE.0[..],
+        &[233, 3, 0, 0, 0, 0, 0, 0][..],
+    ]
+    .concat();
+    let bid_layout =
+        [&[3u8, 7, 0, 0, 0][..], &ALICE.0[..], &BOB.0[..], &[100, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 2][..]].concat();
+    let refund_layout = [&[13u8, 0][..], &ALICE.0[..], &[100, 0, 0, 0, 0, 0, 0, 0, 6][..]].concat();
+    let mut cases = vec![
+        (AuctionEvent::Register(AuctionEventData { auction_id: 7 }), vec![0u8, 7, 0, 0, 0]),
+        (
+            AuctionEvent::Finalize(FinalizeEventData {
+                auction_id: 7,
+                finalizer:  Address::Account(ALICE),
+                commission_payouts: vec![(BOB, Amount::from_micro_ccd(100))],
+                finalize_reward: Amount::from_micro_ccd(10),
+                price:      Amount::from_micro_ccd(200),
+                winner:     Some(DAVE),
+                settled_at: Timestamp::from_timestamp_millis(1001),
+            }),
+            finalize_layout,
+        ),
+        (AuctionEvent::Close(AuctionEventData { auction_id: 7 }), vec![2u8, 7, 0, 0, 0]),
+        (
+            AuctionEvent::Bid(BidEventData {
+                auction_id: 7,
+                bidder:     ALICE,
+                beneficiary: BOB,
+                amount:     Amount::from_micro_ccd(100),
+                data:       vec![1, 2],
+            }),
+            bid_layout,
+        ),
+        (AuctionEvent::DeliveryPending(AuctionEventData { auction_id: 7 }), vec![6u8, 7, 0, 0, 0]),
+        (
+            AuctionEvent::Cancel(CancelEventData {
+                auction_id: 7,
+                bidder:     None,
+                refund:     Amount::zero(),
+                fee:        Amount::zero(),
+            }),
+            vec![7u8, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        ),
+        (
+            AuctionEvent::Refund(RefundEventData {
+                auction_id: None,
+                account:    ALICE,
+                amount:     Amount::from_micro_ccd(100),
+                reason:     RefundReason::Claimed,
+            }),
+            refund_layout,
+        ),
+        (
+            AuctionEvent::ItemUpdated(ItemUpdatedEventData {
+                auction_id: 7,
+                item_hash:  [1; 32],
+            }),
+            [&[19u8, 7, 0, 0, 0][..], &[1; 32][..]].concat(),
+        ),
+        (
+            AuctionEvent::BackupBid(BackupBidEventData {
+                auction_id: 7,
+                bidder:     DAVE,
+                amount:     Amount::from_micro_ccd(100),
+            }),
+            [&[21u8, 7, 0, 0, 0][..], &DAVE.0[..], &[100, 0, 0, 0, 0, 0, 0, 0][..]].concat(),
+        ),
+    ];
+
+    // The remaining events are laid out as their tag followed by their data,
+    // so each tag is pinned to its event and its data type.
+    let tagged = |tag: u8, data: Vec<u8>| [vec![tag], data].concat();
+    let token_contract = ContractAddress::new(1, 0);
+    let rescue = || RescueEventData {
+        token_contract,
+        token_id: TokenIdU8(2),
+        amount: TokenAmountU64(3),
+        to: Address::Account(ALICE),
+    };
+    let reclaim = || ReclaimEventData {
+        auction_id: 7,
+        account:    Address::Account(BOB),
+        refund:     Amount::from_micro_ccd(100),
+        tokens:     TokenAmountU64(1),
+    };
+    let approval = || ApprovalEventData {
+        auction_id: 7,
+        outcome:    ApprovalOutcome::SettledByDefault,
+    };
+    let commission_change = || CommissionChange {
+        commission_tiers:      vec![CommissionTier {
+            threshold: Amount::from_micro_ccd(100),
+            bps:       250,
+        }],
+        commission_recipients: Some(vec![CommissionShare {
+            recipient: CommissionSink::Account(CAROL),
+            share_bps: 10000,
+        }]),
+        effective_at:          Timestamp::from_timestamp_millis(1001),
+    };
+    let ask = || AskEventData {
+        auction_id: 7,
+        asker:      BOB,
+        ask:        Amount::from_micro_ccd(100),
+    };
+    let retract = || RetractEventData {
+        auction_id: 7,
+        bidder: BOB,
+        bid: Amount::from_micro_ccd(100),
+        penalty_payouts: vec![(CAROL, Amount::from_micro_ccd(5))],
+    };
+    let offer = || OfferEventData {
+        offer_id:   3,
+        listing_id: 7,
+        offerer:    DAVE,
+        amount:     Amount::from_micro_ccd(100),
+    };
+    let payout_fallback = || PayoutFallbackEventData {
+        auction_id:   7,
+        owner:        Address::Account(ALICE),
+        amount:       Amount::from_micro_ccd(100),
+        payout_token: (token_contract, TokenIdU8(2)),
+    };
+    let backup = || BackupBidEventData {
+        auction_id: 7,
+        bidder:     DAVE,
+        amount:     Amount::from_micro_ccd(100),
+    };
+    let balance = || BalanceEventData {
+        account: BOB,
+        amount:  Amount::from_micro_ccd(100),
+        balance: Amount::from_micro_ccd(300),
+    };
+    let item = || ItemEventData {
+        auction_id: 7,
+        collection: token_contract,
+        token_id:   TokenIdU8(2),
+        amount:     TokenAmountU64(1),
+        price:      Amount::from_micro_ccd(100),
+        actor:      Address::Account(ALICE),
+    };
+    let commission_sink = || CommissionSinkEventData {
+        auction_id: 7,
+        contract:   token_contract,
+        amount:     Amount::from_micro_ccd(5),
+    };
+    let token_bid = || TokenBidEventData {
+        auction_id: 7,
+        bidder:     BOB,
+        tokens:     TokenAmountU64(4),
+        value:      Amount::from_micro_ccd(100),
+    };
+    let token_refund = || TokenRefundEventData {
+        auction_id: 7,
+        account:    BOB,
+        tokens:     TokenAmountU64(4),
+        reason:     RefundReason::Outbid,
+    };
+    let token_payout = || TokenPayoutEventData {
+        auction_id:        7,
+        owner:             Address::Account(ALICE),
+        owner_tokens:      TokenAmountU64(3),
+        commission_tokens: TokenAmountU64(1),
+    };
+    let return_deferred = || ReturnDeferredEventData {
+        auction_id: 7,
+        owner:      Address::Account(ALICE),
+        amount:     TokenAmountU64(1),
+    };
+    let payout_deferred = || PayoutDeferredEventData {
+        auction_id: 7,
+        account:    CAROL,
+        amount:     Amount::from_micro_ccd(5),
+        payout:     PayoutKind::Commission,
+    };
+    let tokens_deferred = || TokensDeferredEventData {
+        auction_id: 7,
+        recipient: Address::Account(BOB),
+        token_contract,
+        token_id: TokenIdU8(2),
+        amount: TokenAmountU64(4),
+    };
+    cases.extend([
+        (AuctionEvent::Rescue(rescue()), tagged(4, to_bytes(&rescue()))),
+        (AuctionEvent::Reclaim(reclaim()), tagged(5, to_bytes(&reclaim()))),
+        (AuctionEvent::Approval(approval()), tagged(8, to_bytes(&approval()))),
+        (AuctionEvent::CommissionChangeScheduled(commission_change()), tagged(9, to_bytes(&commission_change()))),
+        (AuctionEvent::CommissionChangeCancelled(commission_change()), tagged(10, to_bytes(&commission_change()))),
+        (AuctionEvent::CommissionChangeApplied(commission_change()), tagged(11, to_bytes(&commission_change()))),
+        (AuctionEvent::Ask(ask()), tagged(12, to_bytes(&ask()))),
+        (AuctionEvent::Retract(retract()), tagged(14, to_bytes(&retract()))),
+        (AuctionEvent::OfferMade(offer()), tagged(15, to_bytes(&offer()))),
+        (AuctionEvent::OfferCancelled(offer()), tagged(16, to_bytes(&offer()))),
+        (AuctionEvent::OfferAccepted(offer()), tagged(17, to_bytes(&offer()))),
+        (AuctionEvent::OfferExpired(offer()), tagged(18, to_bytes(&offer()))),
+        (AuctionEvent::PayoutFallback(payout_fallback()), tagged(20, to_bytes(&payout_fallback()))),
+        (AuctionEvent::BackupPromoted(backup()), tagged(22, to_bytes(&backup()))),
+        (AuctionEvent::BalanceCredited(balance()), tagged(23, to_bytes(&balance()))),
+        (AuctionEvent::BalanceDebited(balance()), tagged(24, to_bytes(&balance()))),
+        (AuctionEvent::ItemListed(item()), tagged(25, to_bytes(&item()))),
+        (AuctionEvent::ItemBid(item()), tagged(26, to_bytes(&item()))),
+        (AuctionEvent::ItemSold(item()), tagged(27, to_bytes(&item()))),
+        (AuctionEvent::ItemUnlisted(item()), tagged(28, to_bytes(&item()))),
+        (AuctionEvent::ItemReturned(item()), tagged(29, to_bytes(&item()))),
+        (AuctionEvent::CommissionDeposited(commission_sink()), tagged(30, to_bytes(&commission_sink()))),
+        (AuctionEvent::CommissionAccrued(commission_sink()), tagged(31, to_bytes(&commission_sink()))),
+        (AuctionEvent::TokenBid(token_bid()), tagged(32, to_bytes(&token_bid()))),
+        (AuctionEvent::TokenRefund(token_refund()), tagged(33, to_bytes(&token_refund()))),
+        (AuctionEvent::TokenPayout(token_payout()), tagged(34, to_bytes(&token_payout()))),
+        (AuctionEvent::ReturnDeferred(return_deferred()), tagged(35, to_bytes(&return_deferred()))),
+        (AuctionEvent::PayoutDeferred(payout_deferred()), tagged(36, to_bytes(&payout_deferred()))),
+        (AuctionEvent::TokensDeferred(tokens_deferred()), tagged(37, to_bytes(&tokens_deferred()))),
+    ]);
+
+    // Every tag is used by exactly one event.
+    let mut tags: Vec<u8> = cases.iter().map(|(_, expected)| expected[0]).collect();
+    tags.sort_unstable();
+    assert_eq!(tags, (0..=37).collect::<Vec<u8>>());
+
+    for (event, expected) in cases {
+        let bytes = to_bytes(&event);
+        assert_eq!(bytes, expected, "Unexpected layout for {:?}", event);
+        let parsed: AuctionEvent = from_bytes(&bytes).expect("Event deserializes");
+        assert_eq!(parsed, event);
+    }
+
+    // Unknown tags must not be parsed as any of the known events.
+    assert!(from_bytes::<AuctionEvent>(&[42, 7, 0, 0, 0]).is_err());
+}
+
+/// Test that the contract cannot be initialized with commission shares that
+/// are empty, contain a zero share or do not add up to 10000 basis points.
+#[test]
+fn test_init_rejects_invalid_commission_shares() {
+    let (mut chain, module_reference) = initialize_chain();
+    let share = |account, share_bps| CommissionShare {
+        recipient: CommissionSink::Account(account),
+        share_bps,
+    };
+
+    for commission_recipients in
+        [vec![], vec![share(CAROL, 0), share(DAVE, 10000)], vec![share(CAROL, 5000), share(DAVE, 4000)]]
+    {
+        let parameter = InitParameter {
+            commission_recipients,
+            ..default_init_parameter()
+        };
+        init_auction(&mut chain, module_reference, &parameter).expect_err("Invalid commission shares");
+    }
+}
+
+/// A chain with an initialized auction contract and a mock CIS-2 token
+/// contract. Alice and Carol each own tokens 1 to 3 and have made the auction
+/// contract an operator of their tokens. Carol is the admin of the auction
+/// contract.
+struct TestEnv {
+    chain:   Chain,
+    auction: ContractAddress,
+    token:   ContractAddress,
+    mocks:   ModuleReference, // Module of the mock contracts
+}
+
+impl TestEnv {
+    fn new() -> Self { Self::with_init_parameter(&default_init_parameter()) }
+
+    fn with_init_parameter(parameter: &InitParameter) -> Self {
+        let (mut chain, module_reference) = initialize_chain();
+        let auction =
+            init_auction(&mut chain, module_reference, parameter).expect("Initialize auction").contract_address;
+
+        let mocks = deploy_module(&mut chain, &Path::new(env!("CARGO_MANIFEST_DIR")).join("mock-contracts"));
+        let token = chain
+            .contract_init(SIGNER, CAROL, ENERGY, InitContractPayload {
+                amount:    Amount::zero(),
+                mod_ref:   mocks,
+                init_name: OwnedContractName::new_unchecked("init_cis2_mock".to_string()),
+                param:     OwnedParameter::empty(),
+            })
+            .expect("Initialize token")
+            .contract_address;
+
+        let mut env = TestEnv {
+            chain,
+            auction,
+            token,
+            mocks,
+        };
+        for owner in [ALICE, CAROL] {
+            for token_id in 1..=3 {
+                env.mint(Address::Account(owner), token_id, 1);
+            }
+            env.add_operator(owner, Address::Contract(auction));
+        }
+        env
+    }
+
+    /// Parameter for an auction of a single token ending at `AUCTION_END`.
+    fn auction_parameter(&self, item: &str, token_id: u8) -> NewAuctionParameter {
+        NewAuctionParameter {
+            item: item.to_string(),
+            end: AUCTION_END,
+            initial_price: 0,
+            token_contract: self.token,
+            token_id: TokenIdU8(token_id),
+            token_amount: TokenAmountU64(1),
+            settlement_exclusivity: None,
+            minimum_raise: Raise::Flat(Amount::zero()),
+            requires_owner_approval: false,
+            finalize_reward: Amount::zero(),
+            second_price: false,
+            payout_token: None,
+            accepts_backup_bids: false,
+            alt_bid_token: None,
+        }
+    }
+
+    /// Send an update to the given entrypoint of the auction contract.
+    fn update(
+        &mut self,
+        sender: AccountAddress,
+        entrypoint: &str,
+        amount: Amount,
+        parameter: &impl Serial,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        self.chain.contract_update(SIGNER, sender, Address::Account(sender), ENERGY, UpdateContractPayload {
+            amount,
+            address: self.auction,
+            receive_name: OwnedReceiveName::new_unchecked(format!("auction.{}", entrypoint)),
+            message: OwnedParameter::from_serial(parameter).expect("Serialize parameter"),
+        })
+    }
+
+    /// Invoke a view of the auction contract and parse its return value.
+    fn view<T: Deserial>(&self, entrypoint: &str, parameter: &impl Serial) -> T {
+        self.chain
+            .contract_invoke(ALICE, Address::Account(ALICE), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      self.auction,
+                receive_name: OwnedReceiveName::new_unchecked(format!("auction.{}", entrypoint)),
+                message:      OwnedParameter::from_serial(parameter).expect("Serialize parameter"),
+            })
+            .expect("Invoke view")
+            .parse_return_value()
+            .expect("Return value is valid")
+    }
+
+    /// Create an auction owned by `owner` and return its ID.
+    fn create_auction(&mut self, owner: AccountAddress, parameter: &NewAuctionParameter) -> u32 {
+        self.update(owner, "create_auction", Amount::zero(), parameter)
+            .expect("Create auction")
+            .parse_return_value()
+            .expect("Return value is valid")
+    }
+
+    fn bid(
+        &mut self,
+        bidder: AccountAddress,
+        auction_id: u32,
+        amount: Amount,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        self.update(bidder, "bid", amount, &BidParameter {
+            auction_id,
+            beneficiary: None,
+            data: AdditionalData::empty(),
+        })
+    }
+
+    fn finalize(
+        &mut self,
+        sender: AccountAddress,
+        auction_id: u32,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        self.update(sender, "finalize", Amount::zero(), &AuctionIdParameter {
+            auction_id,
+        })
+    }
+
+    fn get_auction(&self, auction_id: u32) -> Auction {
+        self.view("get_auction", &AuctionIdParameter {
+            auction_id,
+        })
+    }
+
+    /// The marketplace event data for an auction of a single token of the
+    /// mock token contract.
+    fn item_event(&self, auction_id: u32, token_id: u8, price: Amount, actor: Address) -> ItemEventData {
+        ItemEventData {
+            auction_id,
+            collection: self.token,
+            token_id: TokenIdU8(token_id),
+            amount: TokenAmountU64(1),
+            price,
+            actor,
+        }
+    }
+
+    /// The events logged by the auction contract in an update.
+    fn events(&self, update: &ContractInvokeSuccess) -> Vec<AuctionEvent> {
+        update
+            .events()
+            .filter(|(address, _)| *address == self.auction)
+            .flat_map(|(_, events)| events.iter().filter_map(|event| event.parse().ok()))
+            .collect()
+    }
+
+    /// Check that the CCD balance of the auction contract is exactly the sum
+    /// of the standing bids and backup bids on unsettled auctions, the budgets
+    /// of unsettled reverse auctions, the open offers, the pending refunds,
+    /// the pending proceeds and the balances of the test accounts.
+    fn assert_ccd_invariant(&self) {
+        let auctions: Vec<Auction> = self.view("view_auctions", &());
+        let refunds: Vec<(AccountAddress, Amount)> = self.view("view_refunds", &PageParameter {
+            skip: 0,
+            take: MAX_PAGE_SIZE,
+        });
+        let reverse_budgets = auctions
+            .iter()
+            .filter(|auction| auction.auction_state == AuctionState::NotSoldYet)
+            .fold(Amount::zero(), |total, auction| match auction.kind {
+                AuctionKind::Reverse {
+                    budget,
+                } => total + budget,
+                _ => total,
+            });
+        let standing_bids = auctions
+            .iter()
+            .filter(|auction| {
+                !matches!(auction.kind, AuctionKind::Reverse {
+                    ..
+                })
+            })
+            .filter(|auction| {
+                matches!(
+                    auction.auction_state,
+                    AuctionState::NotSoldYet
+                        | AuctionState::PendingDelivery(_)
+                        | AuctionState::Lapsed {
+                            bid_reclaimed: false,
+                            ..
+                        }
+                )
+            })
+            // Bids in an alternative bid token are held in the token
+            .filter(|auction| auction.token_bid.is_none())
+            .fold(Amount::zero(), |total, auction| total + auction.highest_bid);
+        let pending_refunds = refunds.iter().fold(Amount::zero(), |total, (_, refund)| total + *refund);
+        let open_offers = (0..auctions.len() as u32)
+            .flat_map(|auction_id| {
+                self.view::<Vec<(u64, Offer)>>("view_offers", &AuctionIdParameter {
+                    auction_id,
+                })
+            })
+            .fold(Amount::zero(), |total, (_, offer)| total + offer.amount);
+        // Contract owners and contract commission sinks can have proceeds
+        let state: StateView = self.view("view_state", &StateViewParameter {
+            auctions: PageParameter {
+                skip: 0,
+                take: 0,
+            },
+            refunds:  PageParameter {
+                skip: 0,
+                take: 0,
+            },
+        });
+        let StateView::V1(state) = state;
+        let sinks = state.commission_recipients.into_iter().filter_map(|share| match share.recipient {
+            CommissionSink::Contract {
+                address,
+                ..
+            } => Some(address),
+            CommissionSink::Account(_) => None,
+        });
+        let mut contract_owners: Vec<ContractAddress> = Vec::new();
+        let owners = auctions.iter().filter_map(|auction| match auction.owner {
+            Address::Contract(owner) => Some(owner),
+            Address::Account(_) => None,
+        });
+        for owner in owners.chain(sinks) {
+            if !contract_owners.contains(&owner) {
+                contract_owners.push(owner);
+            }
+        }
+        let pending_proceeds = contract_owners
+            .iter()
+            .fold(Amount::zero(), |total, owner| total + self.view::<Amount>("view_proceeds", owner));
+        let backup_bids = (0..auctions.len() as u32)
+            .filter_map(|auction_id| {
+                self.view::<Option<BackupBid>>("view_backup_bid", &AuctionIdParameter {
+                    auction_id,
+                })
+            })
+            .fold(Amount::zero(), |total, backup| total + backup.amount);
+        let balances = [ALICE, BOB, CAROL, DAVE]
+            .iter()
+            .fold(Amount::zero(), |total, account| total + self.view::<Amount>("view_balance", account));
+        assert_eq!(
+            self.chain.contract_balance(self.auction),
+            Some(standing_bids + pending_refunds + reverse_budgets + open_offers + pending_proceeds + backup_bids + balances)
+        );
+    }
+
+    /// Check that the tokens of every auction are sold, still escrowed for it
+    /// or returned, and that the escrow of each token is what remains
+    /// escrowed for the auctions of it plus the bids held in it and the
+    /// tokens kept for recipients to claim.
+    fn assert_token_invariant(&self) {
+        let auctions: Vec<Auction> = self.view("view_auctions", &());
+        let summary: EscrowSummary = self.view("view_escrow_summary", &());
+        for auction in auctions.iter().filter(|auction| !matches!(auction.kind, AuctionKind::Reverse { .. })) {
+            let (sold, remaining) = (auction.amount_sold.0, auction.amount_remaining.0);
+            match auction.auction_state {
+                AuctionState::Sold { .. } => assert_eq!((sold, remaining), (auction.token_amount.0, 0)),
+                AuctionState::NotSoldYet | AuctionState::PendingDelivery(_) if auction.escrow_confirmed => {
+                    assert_eq!((sold, remaining), (0, auction.token_amount.0))
+                }
+                _ => assert!(sold == 0 && (remaining == 0 || remaining == auction.token_amount.0)),
+            }
+        }
+        for EscrowedTokens {
+            token_contract: contract,
+            token_id,
+            amount: escrowed,
+        } in summary.tokens
+        {
+            let remaining: u64 = auctions
+                .iter()
+                .filter(|auction| auction.token_contract == contract && auction.token_id == token_id)
+                .map(|auction| auction.amount_remaining.0)
+                .sum();
+            let bids: u64 = auctions
+                .iter()
+                .filter(|auction| auction.auction_state == AuctionState::NotSoldYet)
+                .filter(|auction| auction.alt_bid_token.is_some_and(|alt| alt.contract == contract && alt.token_id == token_id))
+                .filter_map(|auction| auction.token_bid.map(|tokens| tokens.0))
+                .sum();
+            let claims: u64 = summary
+                .token_claims
+                .iter()
+                .filter(|claim| claim.token_contract == contract && claim.token_id == token_id)
+                .map(|claim| claim.amount.0)
+                .sum();
+            assert_eq!(escrowed.0, remaining + bids + claims, "Escrow of token {:?}", token_id);
+        }
+    }
+
+    /// Make the transfers of the mock token contract fail, or succeed again.
+    fn set_fail_transfers(&mut self, fail: bool) {
+        self.chain
+            .contract_update(SIGNER, CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      self.token,
+                receive_name: OwnedReceiveName::new_unchecked("cis2_mock.setFailTransfers".to_string()),
+                message:      OwnedParameter::from_serial(&fail).expect("Parameter within size bounds"),
+            })
+            .expect("Set failing transfers");
+    }
+
+    /// Make transfers of the mock token contract skip the receive hook of
+    /// contracts, or call it again.
+    fn set_skip_receive_hook(&mut self, skip: bool) {
+        self.chain
+            .contract_update(SIGNER, CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      self.token,
+                receive_name: OwnedReceiveName::new_unchecked("cis2_mock.setSkipReceiveHook".to_string()),
+                message:      OwnedParameter::from_serial(&skip).expect("Parameter within size bounds"),
+            })
+            .expect("Set skipping the receive hook");
+    }
+
+    /// Initialize one of the mock contracts without parameter, such as
+    /// `swap_mock`, `proxy_mock` or `treasury_mock`.
+    fn init_mock(&mut self, contract_name: &str) -> ContractAddress {
+        self.chain
+            .contract_init(SIGNER, CAROL, ENERGY, InitContractPayload {
+                amount:    Amount::zero(),
+                mod_ref:   self.mocks,
+                init_name: OwnedContractName::new_unchecked(format!("init_{}", contract_name)),
+                param:     OwnedParameter::empty(),
+            })
+            .expect("Initialize mock contract")
+            .contract_address
+    }
+
+    /// Make a mock proxy contract call the given entrypoint of a contract.
+    fn forward(
+        &mut self,
+        proxy: ContractAddress,
+        to: ContractAddress,
+        entrypoint: &str,
+        parameter: &impl Serial,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        let parameter = ForwardParams {
+            to,
+            entrypoint: OwnedEntrypointName::new_unchecked(entrypoint.to_string()),
+            parameter: to_bytes(parameter),
+        };
+        self.chain.contract_update(SIGNER, CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      proxy,
+            receive_name: OwnedReceiveName::new_unchecked("proxy_mock.forward".to_string()),
+            message:      OwnedParameter::from_serial(&parameter).expect("Serialize parameter"),
+        })
+    }
+
+    /// Make all swaps of a mock swap contract fail, or succeed again.
+    fn set_fail_swaps(&mut self, swap: ContractAddress, fail: bool) {
+        self.chain
+            .contract_update(SIGNER, CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      swap,
+                receive_name: OwnedReceiveName::new_unchecked("swap_mock.setFailSwaps".to_string()),
+                message:      OwnedParameter::from_serial(&fail).expect("Parameter within size bounds"),
+            })
+            .expect("Set failing swaps");
+    }
+
+    /// Make all deposits to a mock treasury contract fail, or succeed again.
+    fn set_fail_deposits(&mut self, treasury: ContractAddress, fail: bool) {
+        self.chain
+            .contract_update(SIGNER, CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      treasury,
+                receive_name: OwnedReceiveName::new_unchecked("treasury_mock.setFailDeposits".to_string()),
+                message:      OwnedParameter::from_serial(&fail).expect("Parameter within size bounds"),
+            })
+            .expect("Set failing deposits");
+    }
+
+    /// Make the mock token contract report supporting CIS-2, or deny it.
+    fn set_supports_cis2(&mut self, supports: bool) {
+        self.chain
+            .contract_update(SIGNER, CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      self.token,
+                receive_name: OwnedReceiveName::new_unchecked("cis2_mock.setSupportsCis2".to_string()),
+                message:      OwnedParameter::from_serial(&supports).expect("Parameter within size bounds"),
+            })
+            .expect("Set CIS-2 support");
+    }
+
+    /// Transfer a token of the mock token contract to the auction contract,
+    /// with the given data for its `onReceivingCIS2` hook.
+    fn transfer_to_auction(
+        &mut self,
+        owner: AccountAddress,
+        token_id: u8,
+        data: AdditionalData,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        self.transfer_amount_to_auction(owner, token_id, 1, data)
+    }
+
+    /// Bid `amount` of a token of the mock token contract on an auction
+    /// accepting it as its alternative bid token.
+    fn bid_tokens(
+        &mut self,
+        bidder: AccountAddress,
+        auction_id: u32,
+        token_id: u8,
+        amount: u64,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        self.transfer_amount_to_auction(bidder, token_id, amount, AdditionalData::from(to_bytes(&TransferData::Bid(auction_id))))
+    }
+
+    /// Transfer `amount` of a token of the mock token contract to the
+    /// auction contract, with the given data for its `onReceivingCIS2` hook.
+    fn transfer_amount_to_auction(
+        &mut self,
+        owner: AccountAddress,
+        token_id: u8,
+        amount: u64,
+        data: AdditionalData,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        let parameter = TransferParams(vec![Transfer {
+            token_id: TokenIdU8(token_id),
+            amount: TokenAmountU64(amount),
+            from: Address::Account(owner),
+            to: Receiver::Contract(self.auction, OwnedEntrypointName::new_unchecked("onReceivingCIS2".to_string())),
+            data,
+        }]);
+        self.chain.contract_update(SIGNER, owner, Address::Account(owner), ENERGY, UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      self.token,
+            receive_name: OwnedReceiveName::new_unchecked("cis2_mock.transfer".to_string()),
+            message:      OwnedParameter::from_serial(&parameter).expect("Parameter within size bounds"),
+        })
+    }
+
+    /// Mint tokens of the mock token contract.
+    fn mint(&mut self, owner: Address, token_id: u8, amount: u64) {
+        let parameter = MintParams {
+            owner,
+            token_id: TokenIdU8(token_id),
+            amount: TokenAmountU64(amount),
+        };
+        self.chain
+            .contract_update(SIGNER, CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      self.token,
+                receive_name: OwnedReceiveName::new_unchecked("cis2_mock.mint".to_string()),
+                message:      OwnedParameter::from_serial(&parameter).expect("Serialize parameter"),
+            })
+            .expect("Mint tokens");
+    }
+
+    /// Make `operator` an operator of all tokens of `owner`.
+    fn add_operator(&mut self, owner: AccountAddress, operator: Address) {
+        let parameter = UpdateOperatorParams(vec![UpdateOperator {
+            update: OperatorUpdate::Add,
+            operator,
+        }]);
+        self.chain
+            .contract_update(SIGNER, owner, Address::Account(owner), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      self.token,
+                receive_name: OwnedReceiveName::new_unchecked("cis2_mock.updateOperator".to_string()),
+                message:      OwnedParameter::from_serial(&parameter).expect("Serialize parameter"),
+            })
+            .expect("Update operator");
+    }
+
+    /// The balance of a token of the mock token contract.
+    fn token_balance(&self, address: Address, token_id: u8) -> TokenAmountU64 {
+        let parameter = BalanceOfQueryParams {
+            queries: vec![BalanceOfQuery {
+                token_id: TokenIdU8(token_id),
+                address,
+            }],
+        };
+        let response: BalanceOfQueryResponse<TokenAmountU64> = self
+            .chain
+            .contract_invoke(CAROL, Address::Account(CAROL), ENERGY, UpdateContractPayload {
+                amount:       Amount::zero(),
+                address:      self.token,
+                receive_name: OwnedReceiveName::new_unchecked("cis2_mock.balanceOf".to_string()),
+                message:      OwnedParameter::from_serial(&parameter).expect("Serialize parameter"),
+            })
+            .expect("Query balance")
+            .parse_return_value()
+            .expect("Return value is valid");
+        response.0[0]
+    }
+}
+
+/// Asserts that an update failed with the given error.
+fn assert_error(result: Result<ContractInvokeSuccess, ContractInvokeError>, error: BidError) {
+    let rv: BidError = result.expect_err("Update fails").parse_return_value().expect("Return value is valid");
+    assert_eq!(rv, error);
+}
+
+/// Setup the chain with the test accounts and the auction module deployed.
+///
+/// The 'microCCD per euro' exchange rate is set to `1_000_000`, so 1 CCD = 1
+/// euro.
+fn initialize_chain() -> (Chain, ModuleReference) {
+    let mut chain = Chain::builder()
+        .micro_ccd_per_euro(
+            ExchangeRate::new(1_000_000, 1).expect("Exchange rate is in valid range"),
+        )
+        .build()
+        .expect("Exchange rate is in valid range");
+
+    // Create some accounts on the chain.
+    chain.create_account(Account::new(ALICE, ACC_INITIAL_BALANCE));
+    chain.create_account(Account::new(BOB, ACC_INITIAL_BALANCE));
+    chain.create_account(Account::new(CAROL, ACC_INITIAL_BALANCE));
+    chain.create_account(Account::new(DAVE, ACC_INITIAL_BALANCE));
+
+    // Load and deploy the module.
+    let module_reference = deploy_module(&mut chain, Path::new(env!("CARGO_MANIFEST_DIR")));
+
+    (chain, module_reference)
+}
+
+/// Deploys the module built in the crate at `dir`. Fails if the module is
+/// missing or older than the sources of the crate, so that the tests never run
+/// against an outdated build.
+fn deploy_module(chain: &mut Chain, dir: &Path) -> ModuleReference {
+    let path = dir.join("concordium-out/module.wasm.v1");
+    let built = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or_else(|_| panic!("{} is missing, build it as described in the README", path.display()));
+    let changed = [dir.join("Cargo.toml"), dir.join("src")].iter().map(|source| last_modified(source)).max();
+    assert!(
+        changed.is_some_and(|changed| changed <= built),
+        "{} is older than its sources, rebuild it as described in the README",
+        path.display()
+    );
+
+    let module = module_load_v1(&path).expect("Module is valid");
+    chain.module_deploy_v1(SIGNER, CAROL, module).expect("Deploy valid module").module_reference
+}
+
+/// The last modification time of a file, or of the files in a directory.
+fn last_modified(path: &Path) -> SystemTime {
+    let metadata = fs::metadata(path).expect("Source exists");
+    if !metadata.is_dir() {
+        return metadata.modified().expect("Modification time is available");
+    }
+    fs::read_dir(path)
+        .expect("Source directory is readable")
+        .map(|entry| last_modified(&entry.expect("Directory entry is readable").path()))
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// The init parameter used by most tests: Carol receives the full commission.
+fn default_init_parameter() -> InitParameter {
+    InitParameter {
+        commission_recipients: vec![CommissionShare {
+            recipient: CommissionSink::Account(CAROL),
+            share_bps: 10000,
+        }],
+        commission_tiers:      vec![CommissionTier {
+            threshold: Amount::zero(),
+            bps:       1000,
+        }],
+        commission_change_delay: Duration::from_days(7),
+        auto_settle_late_bids: false,
+        reclaim_grace_period:  Duration::from_days(90),
+        cancellation_fee_bps:  500,
+        owner_approval_window: Duration::from_hours(72),
+        retraction_penalty_bps: 200,
+        retraction_cutoff:     Duration::from_minutes(10),
+        max_active_auctions:   20,
+        max_auction_duration:  Duration::from_days(365),
+        delivery_window:       Duration::from_days(7),
+    }
+}
+
+/// Initialize an auction contract, with Carol as the admin.
+fn init_auction(
+    chain: &mut Chain,
+    module_reference: ModuleReference,
+    parameter: &InitParameter,
+) -> Result<ContractInitSuccess, ContractInitError> {
+    chain.contract_init(SIGNER, CAROL, ENERGY, InitContractPayload {
+        amount:    Amount::zero(),
+        mod_ref:   module_reference,
+        init_name: OwnedContractName::new_unchecked("init_auction".to_string()),
+        param:     OwnedParameter::from_serial(parameter).expect("Serialize parameter"),
+    })
+}